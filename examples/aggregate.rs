@@ -0,0 +1,18 @@
+// Aggregates a measurements file with either solver and prints the summary:
+//
+//     cargo run --release --example aggregate -- data/measurements.txt sol2
+use std::env;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = env::args().skip(1);
+    let path = args.next().ok_or("usage: aggregate <file> [sol1|sol2]")?;
+
+    let out = match args.next().as_deref() {
+        None | Some("sol1") => sol1::solve(path)?,
+        Some("sol2") => sol2::solve(path)?,
+        Some(other) => return Err(format!("unknown solver: {other}").into()),
+    };
+    print!("{out}");
+
+    Ok(())
+}
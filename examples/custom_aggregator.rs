@@ -0,0 +1,24 @@
+// Builds statistics the CLI does not print from the per-station aggregates:
+// the most volatile stations and the mean over every measurement.
+//
+//     cargo run --release --example custom_aggregator
+use sol1::StationStats;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut stats: Vec<StationStats> = sol1::solve_stats_bytes(findlib::SAMPLE)?;
+
+    stats.sort_by_key(|s| std::cmp::Reverse(s.max - s.min));
+    println!("Widest temperature range:");
+    for s in stats.iter().take(5) {
+        println!("  {:<24} {:>5.1}", s.name, (s.max - s.min) as f64 / 10.0);
+    }
+
+    let sum: i64 = stats.iter().map(|s| s.sum).sum();
+    let count: u64 = stats.iter().map(|s| s.count).sum();
+    println!(
+        "Overall mean of {count} measurements: {:.1}",
+        sum as f64 / 10.0 / count as f64
+    );
+
+    Ok(())
+}
@@ -0,0 +1,7 @@
+// Aggregates the sample dataset bundled with the crate, no input file needed:
+//
+//     cargo run --release --example quickstart
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    print!("{}", sol1::solve_builtin_sample()?);
+    Ok(())
+}
@@ -0,0 +1,14 @@
+// Aggregates measurements piped through stdin:
+//
+//     cat data/measurements.txt | cargo run --release --example stream_stdin
+use findlib::SolveOptions;
+use std::io;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // sol1 reads the pipe front to back in buffers, scanning one while the
+    // next is read, so the input is never held in memory whole. The reading
+    // happens on a thread of its own, so it gets `Stdin`, not a lock.
+    let stats = sol1::solve_stats_reader(io::stdin(), &SolveOptions::default())?;
+    print!("{}", findlib::format_stats(&stats));
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+include!("src/stations.rs");
+
+// Size of the embedded sample; large enough to split across every worker.
+const SAMPLE_BYTES: usize = 1 << 20;
+const SEED: u64 = 42;
+
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+// Approximates N(mean, 10.0) in tenths with the Irwin-Hall sum of twelve
+// uniforms. Integer-only, so the sample is byte-identical on every platform.
+fn measurement(rng: &mut SplitMix64, mean_tenths: i32) -> i32 {
+    let sum: i32 = (0..12).map(|_| (rng.next() % 1000) as i32).sum();
+    (mean_tenths + (sum - 5994) / 10).clamp(-999, 999)
+}
+
+fn main() {
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-changed=src/stations.rs");
+
+    let mut rng = SplitMix64(SEED);
+    let mut out = String::with_capacity(SAMPLE_BYTES + 128);
+    while out.len() < SAMPLE_BYTES {
+        let (name, mean) = STATIONS[(rng.next() % STATIONS.len() as u64) as usize];
+        let t = measurement(&mut rng, (mean * 10.0).round() as i32);
+        let sign = if t < 0 { "-" } else { "" };
+        out.push_str(&format!("{name};{sign}{}.{}\n", t.abs() / 10, t.abs() % 10));
+    }
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("sample.txt");
+    fs::write(dest, out).unwrap();
+}
@@ -0,0 +1,321 @@
+use crate::validate::parse_value;
+use crate::{Columns, StationStats};
+
+/// Time buckets to aggregate each station's rows into, by the timestamp in
+/// column `column` (counted from 1, like [`Columns::value`]): every row
+/// falls into the bucket of `width` seconds that holds its timestamp,
+/// aligned to the Unix epoch in UTC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Buckets {
+    pub column: usize,
+    pub width: u64,
+}
+
+impl Buckets {
+    /// The start of the bucket holding `secs`, in seconds since the epoch.
+    pub fn start_of(&self, secs: i64) -> i64 {
+        let width = self.width as i64;
+        secs.div_euclid(width) * width
+    }
+}
+
+/// One station's aggregates over the bucket starting at `start`, in
+/// seconds since the epoch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BucketStats {
+    pub start: i64,
+    pub stats: StationStats,
+}
+
+/// Parses a bucket width such as `30s`, `15m`, `1h` or `1d` into seconds.
+pub fn parse_width(s: &str) -> Result<u64, String> {
+    let bad = || format!("invalid bucket width {s:?}, expected e.g. 15m, 1h or 1d");
+    let at = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(bad)?;
+    let n: u64 = s[..at].parse().map_err(|_| bad())?;
+    let unit = match &s[at..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(bad()),
+    };
+    match n.checked_mul(unit) {
+        Some(width) if width > 0 && width <= i64::MAX as u64 => Ok(width),
+        _ => Err(bad()),
+    }
+}
+
+/// Parses a timestamp into seconds since the epoch: either a whole number
+/// of seconds, or ISO 8601 `YYYY-MM-DD[THH:MM[:SS[.fff]]]` with an optional
+/// `Z` or `±HH:MM` offset (UTC without one). A space may stand in for the
+/// `T`; fractions of a second are dropped.
+pub fn parse_timestamp(s: &[u8]) -> Option<i64> {
+    if s.len() < 10 || s[4] != b'-' {
+        let (neg, digits) = match s.split_first()? {
+            (b'-', rest) => (true, rest),
+            _ => (false, s),
+        };
+        let secs = number(digits)?;
+        return Some(if neg { -secs } else { secs });
+    }
+    let (date, rest) = s.split_at(10);
+    let [y0, y1, y2, y3, b'-', m0, m1, b'-', d0, d1] = *date else {
+        return None;
+    };
+    let year = number(&[y0, y1, y2, y3])?;
+    let month = number(&[m0, m1])?;
+    let day = number(&[d0, d1])?;
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    let (time, offset) = match rest {
+        [] => (0, 0),
+        [b'T' | b' ', time @ ..] => clock(time)?,
+        _ => return None,
+    };
+    Some(days * 86_400 + time - offset)
+}
+
+/// Formats seconds since the epoch as ISO 8601 in UTC, e.g.
+/// `2024-03-01T13:00:00Z`.
+pub fn format_timestamp(secs: i64) -> String {
+    let (days, time) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Splits one row into station, value in tenths and timestamp in seconds,
+/// checking the station and value as [`crate::parse_row_with`] does. The
+/// reason is what [`crate::ParseError::reason`] reports.
+pub fn parse_bucketed_row(
+    line: &[u8],
+    columns: Columns,
+    timestamp: usize,
+) -> Result<(&[u8], i32, i64), &'static str> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let mut fields = line.split(|&b| b == columns.delimiter);
+    let name = fields.next().unwrap_or_default();
+    let (mut value, mut time) = (None, None);
+    for (i, field) in fields.enumerate().take(columns.value.max(timestamp) - 1) {
+        if i + 2 == columns.value {
+            value = Some(field);
+        }
+        if i + 2 == timestamp {
+            time = Some(field);
+        }
+    }
+    let (Some(value), Some(time)) = (value, time) else {
+        return Err("too few fields");
+    };
+    if name.is_empty() {
+        return Err("empty station name");
+    }
+    if std::str::from_utf8(name).is_err() {
+        return Err("station name is not UTF-8");
+    }
+    let value = parse_value(value).ok_or("value is not -?d?d.d")?;
+    let time = parse_timestamp(time).ok_or("timestamp is not epoch seconds or ISO 8601")?;
+    Ok((name, value, time))
+}
+
+// `HH:MM[:SS[.fff]][Z|±HH:MM]` as seconds into the day and the offset from
+// UTC in seconds.
+fn clock(s: &[u8]) -> Option<(i64, i64)> {
+    let [h0, h1, b':', m0, m1, rest @ ..] = s else {
+        return None;
+    };
+    let (hours, minutes) = (number(&[*h0, *h1])?, number(&[*m0, *m1])?);
+    let (seconds, rest) = match rest {
+        [b':', s0, s1, rest @ ..] => (number(&[*s0, *s1])?, rest),
+        _ => (0, rest),
+    };
+    let rest = match rest {
+        [b'.', frac @ ..] => {
+            let digits = frac.iter().take_while(|b| b.is_ascii_digit()).count();
+            if digits == 0 {
+                return None;
+            }
+            &frac[digits..]
+        }
+        _ => rest,
+    };
+    if hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let offset = match rest {
+        [] | [b'Z'] => 0,
+        [sign @ (b'+' | b'-'), h0, h1, b':', m0, m1] => {
+            let offset = number(&[*h0, *h1])? * 3600 + number(&[*m0, *m1])? * 60;
+            if *sign == b'-' { -offset } else { offset }
+        }
+        _ => return None,
+    };
+    Some((hours * 3600 + minutes * 60 + seconds, offset))
+}
+
+// Decimal digits only, at most 18 of them so the result fits.
+fn number(digits: &[u8]) -> Option<i64> {
+    if digits.is_empty() || digits.len() > 18 || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    Some(
+        digits
+            .iter()
+            .fold(0, |acc, &d| acc * 10 + i64::from(d - b'0')),
+    )
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days since 1970-01-01 of a proleptic Gregorian date, after Howard
+// Hinnant's `days_from_civil`: years start in March so the leap day is last.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// The inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let march_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * march_month + 2) / 5 + 1;
+    let month = if march_month < 10 {
+        march_month + 3
+    } else {
+        march_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_width() {
+        assert_eq!(Ok(30), parse_width("30s"));
+        assert_eq!(Ok(900), parse_width("15m"));
+        assert_eq!(Ok(3600), parse_width("1h"));
+        assert_eq!(Ok(86_400), parse_width("1d"));
+        for bad in [
+            "",
+            "h",
+            "0h",
+            "1",
+            "1w",
+            "-1h",
+            "1.5h",
+            "99999999999999999999d",
+        ] {
+            assert!(parse_width(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(Some(0), parse_timestamp(b"1970-01-01"));
+        assert_eq!(
+            Some(1_709_298_000),
+            parse_timestamp(b"2024-03-01T13:00:00Z")
+        );
+        assert_eq!(Some(1_709_298_000), parse_timestamp(b"2024-03-01 13:00"));
+        assert_eq!(
+            Some(1_709_298_000),
+            parse_timestamp(b"2024-03-01T14:00:00.250+01:00")
+        );
+        assert_eq!(Some(1_709_164_800), parse_timestamp(b"2024-02-29"));
+        assert_eq!(Some(-86_400), parse_timestamp(b"1969-12-31T00:00:00"));
+        assert_eq!(Some(1_709_298_000), parse_timestamp(b"1709298000"));
+        assert_eq!(Some(-5), parse_timestamp(b"-5"));
+        for bad in [
+            &b""[..],
+            b"-",
+            b"12a",
+            b"2023-02-29",
+            b"2024-13-01",
+            b"2024-03-01T24:00",
+            b"2024-03-01T13",
+            b"2024-03-01T13:00:00.Z",
+            b"2024-03-01T13:00+1",
+        ] {
+            assert_eq!(
+                None,
+                parse_timestamp(bad),
+                "{:?}",
+                String::from_utf8_lossy(bad)
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        for secs in [0, 1_709_298_000, 1_709_164_800, -86_400, -62_135_596_800] {
+            assert_eq!(
+                Some(secs),
+                parse_timestamp(format_timestamp(secs).as_bytes())
+            );
+        }
+        assert_eq!("2024-02-29T00:00:00Z", format_timestamp(1_709_164_800));
+    }
+
+    #[test]
+    fn test_start_of() {
+        let hours = Buckets {
+            column: 3,
+            width: 3600,
+        };
+        assert_eq!(1_709_298_000, hours.start_of(1_709_299_799));
+        assert_eq!(-3600, hours.start_of(-1));
+    }
+
+    #[test]
+    fn test_parse_bucketed_row() {
+        let tsv = Columns {
+            delimiter: b'\t',
+            value: 3,
+        };
+        assert_eq!(
+            Ok((&b"Oslo"[..], -34, 60)),
+            parse_bucketed_row(b"Oslo\t60\t-3.4\tx\r", tsv, 2)
+        );
+        assert_eq!(
+            Err("too few fields"),
+            parse_bucketed_row(b"Oslo\t60", tsv, 2)
+        );
+        assert_eq!(
+            Err("timestamp is not epoch seconds or ISO 8601"),
+            parse_bucketed_row(b"Oslo\tnoon\t1.0", tsv, 2)
+        );
+        assert_eq!(
+            Err("value is not -?d?d.d"),
+            parse_bucketed_row(b"Oslo;1;60", Columns::CHALLENGE, 3)
+        );
+        assert_eq!(
+            Err("empty station name"),
+            parse_bucketed_row(b";1.0;60", Columns::CHALLENGE, 3)
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+
+/// A compressed container around the input, recognised by its magic bytes
+/// rather than the file name, so renamed or extensionless dumps work too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// gzip, one member or several concatenated (as `pigz` writes).
+    Gzip,
+    /// zstd, one frame or several (as `pzstd` writes).
+    Zstd,
+}
+
+impl Compression {
+    /// The compression `data` starts with, if any. No `name;value` text
+    /// starts with either magic.
+    pub fn detect(data: &[u8]) -> Option<Compression> {
+        match data {
+            [0x1f, 0x8b, 0x08, ..] => Some(Compression::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Some(Compression::Gzip),
+            Compression::detect(b"\x1f\x8b\x08\0\0\0")
+        );
+        assert_eq!(
+            Some(Compression::Zstd),
+            Compression::detect(b"\x28\xb5\x2f\xfd\x04")
+        );
+        assert_eq!(None, Compression::detect(b"Oslo;1.0\n"));
+        assert_eq!(None, Compression::detect(b"\x1f\x8b"));
+        assert_eq!(None, Compression::detect(b""));
+    }
+}
@@ -0,0 +1,26 @@
+use std::fs;
+
+/// True on CPUs that mix performance and efficiency cores (Intel hybrid
+/// parts, detected through the separate `cpu_core`/`cpu_atom` PMUs Linux
+/// exposes for them). On such machines equal-sized chunks leave the
+/// E-cores as stragglers, so callers should split work finer and let the
+/// faster cores pick up the slack.
+pub fn is_hybrid() -> bool {
+    has_cpus("/sys/devices/cpu_core/cpus") && has_cpus("/sys/devices/cpu_atom/cpus")
+}
+
+fn has_cpus(path: &str) -> bool {
+    fs::read_to_string(path).is_ok_and(|s| !s.trim().is_empty())
+}
+
+/// Worker count when none is configured: `RAYON_NUM_THREADS` if set, as
+/// rayon's own pool would use, otherwise the available parallelism. Asking
+/// here rather than `rayon::current_num_threads` avoids building rayon's
+/// global pool just to learn its size.
+pub fn default_threads() -> usize {
+    std::env::var("RAYON_NUM_THREADS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+}
@@ -0,0 +1,136 @@
+use crate::StationStats;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::RwLock;
+use std::{fs, io};
+
+/// Stable `u32` IDs for station names, kept across runs in a file with one
+/// name per line: a name's ID is its line number, counting from 0. New
+/// names are only ever appended, so an ID once given never changes and
+/// outputs from different runs can be joined on it.
+///
+/// Lookups take a read lock and assigning an ID a write lock, so one
+/// dictionary can be shared between threads.
+#[derive(Debug, Default)]
+pub struct StationDict {
+    inner: RwLock<Names>,
+}
+
+#[derive(Debug, Default)]
+struct Names {
+    ids: HashMap<String, u32>,
+    names: Vec<String>,
+}
+
+impl StationDict {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the dictionary at `path`; a missing file is an empty one.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e),
+        };
+        let dict = Self::new();
+        for (idx, name) in text.lines().enumerate() {
+            if dict.id(name) as usize != idx {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: duplicate name {name:?}", path.display(), idx + 1),
+                ));
+            }
+        }
+        Ok(dict)
+    }
+
+    /// Writes the dictionary to `path`, replacing the old file only once
+    /// the new one is complete.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let inner = self.inner.read().unwrap();
+        let mut text = String::new();
+        for name in &inner.names {
+            if name.contains(['\n', '\r']) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("station name {name:?} contains a line break"),
+                ));
+            }
+            text.push_str(name);
+            text.push('\n');
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, path)
+    }
+
+    /// The ID of `name`, assigning the next free one if it has none.
+    pub fn id(&self, name: &str) -> u32 {
+        if let Some(id) = self.get(name) {
+            return id;
+        }
+        let mut inner = self.inner.write().unwrap();
+        // Another thread may have added it between the two locks.
+        if let Some(&id) = inner.ids.get(name) {
+            return id;
+        }
+        let id = u32::try_from(inner.names.len()).expect("more than u32::MAX stations");
+        inner.ids.insert(name.to_string(), id);
+        inner.names.push(name.to_string());
+        id
+    }
+
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.inner.read().unwrap().ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: u32) -> Option<String> {
+        self.inner.read().unwrap().names.get(id as usize).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Replaces each station's name with its ID, assigning IDs as needed,
+    /// and sorts the result by ID.
+    pub fn key_by_id(&self, stats: Vec<StationStats>) -> Vec<StationStats> {
+        let mut keyed: Vec<(u32, StationStats)> = stats
+            .into_iter()
+            .map(|mut s| {
+                let id = self.id(&s.name);
+                s.name = id.to_string();
+                (id, s)
+            })
+            .collect();
+        keyed.sort_unstable_by_key(|(id, _)| *id);
+        keyed.into_iter().map(|(_, s)| s).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_survive_reload() {
+        let path = std::env::temp_dir().join(format!("station-dict-{}.txt", std::process::id()));
+        let dict = StationDict::new();
+        assert_eq!(
+            (0, 1, 0),
+            (dict.id("Oslo"), dict.id("Rome"), dict.id("Oslo"))
+        );
+        dict.save(&path).unwrap();
+
+        let dict = StationDict::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((Some(1), 2), (dict.get("Rome"), dict.id("Lima")));
+        assert_eq!(Some("Oslo".to_string()), dict.name(0));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{ChunkError, FileError, ParseError};
+use std::io;
+
+/// Why a solve failed. Solvers return it rather than a boxed error so
+/// callers can tell an unreadable file from malformed input or an option
+/// the solver rejects without matching on messages.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SolveError {
+    /// Opening, reading or decompressing the input failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// The input could not be mapped into memory.
+    #[error("mapping the input: {0}")]
+    Map(#[source] io::Error),
+    /// A malformed row under [`crate::Validation::Strict`], a station name
+    /// that is not UTF-8 included.
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    /// The trusted scan read fewer or more measurements than lines, so
+    /// some line was not `name;value` and the result cannot be trusted.
+    #[error("parsed {records} measurements from {lines} lines")]
+    Miscounted { records: u64, lines: u64 },
+    /// A block whose scan failed under [`crate::PanicPolicy::Fail`].
+    #[error(transparent)]
+    Chunk(#[from] ChunkError),
+    /// An option, input format, compression or kernel the solver cannot
+    /// honour here.
+    #[error("{0}")]
+    Unsupported(String),
+    /// One of several inputs failed.
+    #[error(transparent)]
+    File(#[from] FileError),
+    /// The thread running the solve panicked.
+    #[error("solver thread panicked")]
+    Panicked,
+}
+
+impl SolveError {
+    /// The failure inside a [`SolveError::File`], or this error itself.
+    pub fn cause(&self) -> &SolveError {
+        match self {
+            SolveError::File(f) => &f.error,
+            e => e,
+        }
+    }
+
+    /// Whether the input itself is at fault: a malformed row, a miscount or
+    /// a block the scan choked on, or bytes that do not decompress.
+    pub fn is_malformed(&self) -> bool {
+        match self.cause() {
+            SolveError::Parse(_) | SolveError::Miscounted { .. } | SolveError::Chunk(_) => true,
+            SolveError::Io(e) => e.kind() == io::ErrorKind::InvalidData,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_malformed_through_file() {
+        let parse = ParseError {
+            line: 2,
+            byte_offset: 9,
+            reason: "no ';'",
+        };
+        let in_file = |error| {
+            SolveError::from(FileError {
+                path: "b.txt".to_string(),
+                error: Box::new(error),
+            })
+        };
+        let err = in_file(parse.into());
+        assert!(err.is_malformed());
+        assert_eq!("b.txt: line 2 (byte 9): no ';'", err.to_string());
+
+        let missing = in_file(io::Error::from(io::ErrorKind::NotFound).into());
+        assert!(matches!(missing.cause(), SolveError::Io(_)));
+        assert!(!missing.is_malformed());
+        assert!(!SolveError::Unsupported("no".into()).is_malformed());
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How rows are laid out in the input; see
+/// [`crate::SolveOptions::input_format`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// `name;value` lines, the challenge format.
+    #[default]
+    Text,
+    /// One `{"station": "...", "temp": 12.3}` object per line.
+    Jsonl,
+    /// Length-prefixed binary rows; see `sol1::binary`.
+    Binary,
+    /// An Arrow IPC file (Feather v2) with `station` and `temp` columns.
+    Arrow,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" | "txt" => Ok(InputFormat::Text),
+            "jsonl" => Ok(InputFormat::Jsonl),
+            "binary" => Ok(InputFormat::Binary),
+            "arrow" | "feather" => Ok(InputFormat::Arrow),
+            _ => Err(format!(
+                "unknown input format {s:?}, expected text, jsonl, binary or arrow"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InputFormat::Text => "text",
+            InputFormat::Jsonl => "jsonl",
+            InputFormat::Binary => "binary",
+            InputFormat::Arrow => "arrow",
+        })
+    }
+}
+
+/// Where a text row's station and value are. The row is split on
+/// `delimiter`; the station is the first field and the value the `value`th,
+/// counted from 1 as `cut -f` does. The default is the challenge's
+/// `name;value`; see [`crate::SolveOptions::text_columns`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Columns {
+    pub delimiter: u8,
+    pub value: usize,
+}
+
+impl Columns {
+    /// `name;value`, the challenge's layout.
+    pub const CHALLENGE: Columns = Columns {
+        delimiter: b';',
+        value: 2,
+    };
+
+    /// `name<delimiter>value` with nothing after the value, the layout the
+    /// solvers' fast paths scan.
+    pub fn is_pair(&self) -> bool {
+        self.value <= 2
+    }
+
+    /// The station and value fields of `line`, or `None` if it has too few
+    /// fields. In a pair, the value is everything after the first
+    /// delimiter.
+    pub fn split<'a>(&self, line: &'a [u8]) -> Option<(&'a [u8], &'a [u8])> {
+        let delimiter = self.delimiter;
+        if self.is_pair() {
+            let at = line.iter().position(|&b| b == delimiter)?;
+            return Some((&line[..at], &line[at + 1..]));
+        }
+        let mut fields = line.split(|&b| b == delimiter);
+        let station = fields.next()?;
+        Some((station, fields.nth(self.value - 2)?))
+    }
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Self::CHALLENGE
+    }
+}
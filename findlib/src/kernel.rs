@@ -0,0 +1,69 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Code path used to find the `;` that ends each station name and the
+/// newlines between rows.
+///
+/// [`Kernel::detect`] picks the widest one the CPU supports; benchmarks can
+/// pin a specific one through [`crate::SolveOptions::force_kernel`] so
+/// results from different machines compare the same code. Solvers without
+/// vectorised scanning ignore it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    /// One byte at a time.
+    Scalar,
+    /// Eight bytes at a time in a general-purpose register.
+    Swar,
+    /// 32 bytes at a time with AVX2 compares (x86_64 only).
+    Avx2,
+}
+
+impl Kernel {
+    /// Every kernel, supported here or not.
+    pub const ALL: [Kernel; 3] = [Kernel::Scalar, Kernel::Swar, Kernel::Avx2];
+
+    /// Best kernel for the running CPU: AVX2 where it exists, else SWAR
+    /// (aarch64 included).
+    pub fn detect() -> Kernel {
+        if Kernel::Avx2.is_supported() {
+            Kernel::Avx2
+        } else {
+            Kernel::Swar
+        }
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Kernel::Scalar | Kernel::Swar => true,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => std::arch::is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            Kernel::Avx2 => false,
+        }
+    }
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scalar" => Ok(Kernel::Scalar),
+            "swar" => Ok(Kernel::Swar),
+            "avx2" => Ok(Kernel::Avx2),
+            _ => Err(format!(
+                "unknown kernel {s:?}, expected scalar, swar or avx2"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Kernel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kernel::Scalar => "scalar",
+            Kernel::Swar => "swar",
+            Kernel::Avx2 => "avx2",
+        })
+    }
+}
@@ -1,6 +1,54 @@
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 
+mod buckets;
+mod compression;
+pub mod cpu;
+mod dict;
+mod error;
+mod format;
+mod kernel;
+mod options;
+#[cfg(feature = "profiling")]
+mod profiling;
+mod progress;
+mod query;
+mod solver;
+mod stations;
+mod stats;
+mod timings;
+mod validate;
+mod warning;
+
+pub use buckets::{
+    BucketStats, Buckets, format_timestamp, parse_bucketed_row, parse_timestamp, parse_width,
+};
+pub use compression::Compression;
+pub use dict::StationDict;
+pub use error::SolveError;
+pub use format::{Columns, InputFormat};
+pub use kernel::Kernel;
+pub use options::{PanicPolicy, Preview, PreviewFn, SolveOptions};
+#[cfg(feature = "profiling")]
+pub use profiling::FlamegraphProfiler;
+pub use progress::{Progress, ProgressFn, ProgressSink};
+pub use query::{Order, SortKey, query};
+#[cfg(feature = "regex")]
+pub use regex::bytes::Regex;
+pub use solver::{Backend, FileError, SolveReport, Solver, solve_in_turn};
+pub use stations::STATIONS;
+pub use stats::{
+    Distribution, Recent, Stat, StationStats, format_official, format_selected, format_stats,
+    mean_tenths, merge_stats, stream_selected, write_selected, write_stat, write_tenths,
+};
+pub use timings::{ChunkStats, ChunksFn, ChunksSink, Timings, TimingsFn, TimingsSink};
+pub use validate::{ParseError, Validation, parse_row, parse_row_with};
+pub use warning::{ChunkError, Warning, WarningFn, WarningSink, Warnings};
+
+/// Deterministic ~1 MiB measurements file generated at build time, so tests
+/// and examples can run on a fresh clone without `data/measurements.txt`.
+pub const SAMPLE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/sample.txt"));
+
 pub fn read_file<P: AsRef<Path>>(file_name: P) -> String {
     fs::read_to_string(&file_name)
         .unwrap_or_else(|e| panic!("failed to read {}: {e}", file_name.as_ref().display()))
@@ -15,14 +63,14 @@ pub fn find(root: &Path, ext: &str) -> io::Result<Vec<PathBuf>> {
             let path = entry.path();
             if path.is_dir() {
                 walk(&path, ext, out)?;
-            } else if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
-                if let Some(e) = Path::new(name).extension().and_then(|s| s.to_str()) {
-                    let wanted = ext.trim_start_matches('.');
-                    if e == wanted {
-                        let mut base = path.clone();
-                        base.set_extension("");
-                        out.push(base);
-                    }
+            } else if let Some(name) = path.file_name().and_then(|s| s.to_str())
+                && let Some(e) = Path::new(name).extension().and_then(|s| s.to_str())
+            {
+                let wanted = ext.trim_start_matches('.');
+                if e == wanted {
+                    let mut base = path.clone();
+                    base.set_extension("");
+                    out.push(base);
                 }
             }
         }
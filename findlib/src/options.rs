@@ -0,0 +1,484 @@
+use crate::{
+    ChunkStats, ChunksSink, Columns, InputFormat, Kernel, ProgressSink, SolveError, StationStats,
+    Timings, TimingsSink, Validation, Warning, WarningSink, Warnings,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Receives partial results while a solve is running; see
+/// [`SolveOptions::preview_every`].
+pub type PreviewFn = dyn Fn(&[StationStats]) + Send + Sync;
+
+#[derive(Clone)]
+pub struct Preview {
+    pub interval: Duration,
+    pub sink: Arc<PreviewFn>,
+}
+
+impl fmt::Debug for Preview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Preview")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What a solve does when scanning a block of the input fails, on a symbol
+/// the parser does not handle or in a panic; see
+/// [`SolveOptions::on_chunk_panic`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Stop every worker and fail with a [`crate::ChunkError`] naming the
+    /// block.
+    #[default]
+    Fail,
+    /// Leave the block's rows out, report a [`crate::Warning::SkippedChunk`]
+    /// and carry on.
+    Skip,
+}
+
+impl std::str::FromStr for PanicPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(PanicPolicy::Fail),
+            "skip" => Ok(PanicPolicy::Skip),
+            _ => Err(format!("unknown panic policy {s:?}, expected fail or skip")),
+        }
+    }
+}
+
+/// Settings shared by the solvers. Start from [`SolveOptions::default`],
+/// which aggregates everything, and chain the setters.
+#[derive(Clone, Debug, Default)]
+pub struct SolveOptions {
+    stations: Option<HashSet<Box<[u8]>>>,
+    max_rows: Option<u64>,
+    preview: Option<Preview>,
+    kernel: Option<Kernel>,
+    hash_seed: Option<u64>,
+    untrusted: bool,
+    format: InputFormat,
+    columns: Columns,
+    threads: Option<usize>,
+    timings: Option<TimingsSink>,
+    chunks: Option<ChunksSink>,
+    progress: Option<ProgressSink>,
+    warnings: Option<WarningSink>,
+    panic_policy: PanicPolicy,
+    validation: Validation,
+    release_pages: bool,
+    max_resident: Option<usize>,
+    memory_watermark: Option<u64>,
+    distribution: bool,
+    keep_last: Option<usize>,
+    unsorted: bool,
+    #[cfg(feature = "regex")]
+    extractor: Option<regex::bytes::Regex>,
+}
+
+impl SolveOptions {
+    /// Only aggregate the named stations. Rows for any other station are
+    /// skipped before they reach the solver's table, so they never allocate
+    /// an entry.
+    pub fn include_stations<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[u8]>,
+    {
+        self.stations = Some(names.into_iter().map(|n| n.as_ref().into()).collect());
+        self
+    }
+
+    /// Stop once every station passed to [`Self::include_stations`] has
+    /// `n` measurements; rows past the `n`th for a station are ignored. The
+    /// result describes a prefix-ish sample rather than the whole file, which
+    /// is the point for interactive looks at huge inputs. Without an include
+    /// list this has no effect.
+    pub fn max_rows_per_station(mut self, n: u64) -> Self {
+        self.max_rows = Some(n);
+        self
+    }
+
+    /// Call `sink` every `interval` with the aggregate so far, sorted by
+    /// name. Workers hand over copies of their tables between blocks, so the
+    /// figures lag the scan by up to one interval and never block it.
+    pub fn preview_every<F>(mut self, interval: Duration, sink: F) -> Self
+    where
+        F: Fn(&[StationStats]) + Send + Sync + 'static,
+    {
+        self.preview = Some(Preview {
+            interval,
+            sink: Arc::new(sink),
+        });
+        self
+    }
+
+    pub fn preview(&self) -> Option<&Preview> {
+        self.preview.as_ref()
+    }
+
+    /// Pin the scan kernel instead of using [`Kernel::detect`].
+    pub fn force_kernel(mut self, kernel: Kernel) -> Self {
+        self.kernel = Some(kernel);
+        self
+    }
+
+    pub fn kernel(&self) -> Kernel {
+        self.kernel.unwrap_or_else(Kernel::detect)
+    }
+
+    /// The kernel pinned by [`Self::force_kernel`], if any, for solvers
+    /// with no kernels to reject it.
+    pub fn forced_kernel(&self) -> Option<Kernel> {
+        self.kernel
+    }
+
+    /// Seed the key table's hash with `seed` instead of a fresh random value
+    /// per run, so table layout (and timing) is reproducible.
+    pub fn hash_seed(mut self, seed: u64) -> Self {
+        self.hash_seed = Some(seed);
+        self
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.hash_seed
+    }
+
+    /// Station names may come from an adversary: hash keys with a keyed,
+    /// collision-resistant hash over all of their bytes rather than the
+    /// fast prefix hashes, trading speed for protection against collision
+    /// flooding. An explicit [`Self::hash_seed`] is ignored in this mode.
+    pub fn untrusted_input(mut self, untrusted: bool) -> Self {
+        self.untrusted = untrusted;
+        self
+    }
+
+    pub fn is_untrusted(&self) -> bool {
+        self.untrusted
+    }
+
+    /// Scan with `n` workers instead of [`crate::cpu::default_threads`].
+    /// Solvers start their own threads (sol2 a rayon pool per solve) rather
+    /// than using rayon's global pool, so a host application's pool is
+    /// neither resized nor kept busy.
+    pub fn threads(mut self, n: usize) -> Self {
+        self.threads = Some(n.max(1));
+        self
+    }
+
+    pub fn workers(&self) -> usize {
+        self.threads.unwrap_or_else(crate::cpu::default_threads)
+    }
+
+    /// Call `sink` with the solve's [`Timings`] once it finishes. Solvers
+    /// that do not measure them never call it.
+    pub fn report_timings<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&Timings) + Send + Sync + 'static,
+    {
+        self.timings = Some(TimingsSink(Arc::new(sink)));
+        self
+    }
+
+    pub fn timings(&self) -> Option<&TimingsSink> {
+        self.timings.as_ref()
+    }
+
+    /// Call `sink` with the [`ChunkStats`] of every block the scan handed
+    /// to a worker once the solve finishes, to study how work was spread.
+    /// Counting per block costs a little; solvers that do not schedule
+    /// blocks never call it.
+    pub fn report_chunks<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&[ChunkStats]) + Send + Sync + 'static,
+    {
+        self.chunks = Some(ChunksSink(Arc::new(sink)));
+        self
+    }
+
+    pub fn chunks(&self) -> Option<&ChunksSink> {
+        self.chunks.as_ref()
+    }
+
+    /// Call `sink` with the bytes scanned so far and the input's size each
+    /// time a worker finishes a block, from that worker's thread, so keep
+    /// it cheap. Solvers that take several files one at a time count each
+    /// on its own.
+    pub fn report_progress<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        self.progress = Some(ProgressSink(Arc::new(sink)));
+        self
+    }
+
+    pub fn progress(&self) -> Option<&ProgressSink> {
+        self.progress.as_ref()
+    }
+
+    /// Call `sink` with each [`Warning`] the solve raises: things it worked
+    /// around, like running on fewer threads than asked for, that leave the
+    /// result intact but may explain a slow run. Without a sink they are
+    /// dropped.
+    pub fn report_warnings<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&Warning) + Send + Sync + 'static,
+    {
+        self.warnings = Some(WarningSink(Arc::new(sink)));
+        self
+    }
+
+    /// Also push each [`Warning`] onto `into`, after handing it to the sink
+    /// set with [`Self::report_warnings`], if any.
+    pub fn collect_warnings(mut self, into: &Warnings) -> Self {
+        let (into, sink) = (into.clone(), self.warnings.take());
+        self.report_warnings(move |w| {
+            if let Some(sink) = &sink {
+                (sink.0)(w);
+            }
+            into.push(w.clone());
+        })
+    }
+
+    pub fn warnings(&self) -> Option<&WarningSink> {
+        self.warnings.as_ref()
+    }
+
+    /// What to do when scanning a block fails. Skipping scans every block
+    /// into a table of its own first, so a failed block leaves nothing
+    /// behind; that costs a little. Solvers that do not scan in blocks let
+    /// the panic through.
+    pub fn on_chunk_panic(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    pub fn panic_policy(&self) -> PanicPolicy {
+        self.panic_policy
+    }
+
+    /// Check every text row instead of trusting the input: fail on a
+    /// malformed one, or skip them all and count them. Checked rows
+    /// go through a slower parser than the default. Not supported with
+    /// other input formats or an extractor.
+    pub fn validate(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    pub fn validation(&self) -> Validation {
+        self.validation
+    }
+
+    /// Drop each block's pages from the process's memory once it is
+    /// scanned, so its footprint stays bounded on a file much larger than
+    /// RAM. Only mapped input on Unix is released; a page touched again is
+    /// reread from the file. Solvers that do not scan mapped input in
+    /// blocks ignore it.
+    pub fn release_pages(mut self, release: bool) -> Self {
+        self.release_pages = release;
+        self
+    }
+
+    pub fn releases_pages(&self) -> bool {
+        self.release_pages
+    }
+
+    /// Keep no more than about `bytes` of a file in memory at once: a file
+    /// larger than that is read or mapped in windows of rows, each dropped
+    /// once scanned, instead of mapped whole. For 32-bit targets, where a
+    /// large file does not fit the address space, and for machines with
+    /// less RAM than input. Compressed files are still mapped whole, and
+    /// windowed files cannot be previewed. Input already in memory is not
+    /// affected.
+    pub fn max_resident_bytes(mut self, bytes: usize) -> Self {
+        self.max_resident = Some(bytes);
+        self
+    }
+
+    pub fn max_resident(&self) -> Option<usize> {
+        self.max_resident
+    }
+
+    /// Whether a file of `len` bytes is to be read in windows; see
+    /// [`Self::max_resident_bytes`].
+    pub fn is_windowed(&self, len: u64) -> bool {
+        self.max_resident.is_some_and(|max| len > max as u64)
+    }
+
+    /// A soft limit on the process's resident memory. Each time it is
+    /// passed, the scan sheds memory without losing any progress: scanned
+    /// pages of a mapped file are released, the workers' tables are flushed
+    /// into one, and half the workers stop claiming blocks, leaving theirs
+    /// to the rest. Each crossing is reported as a [`Warning::Watermark`].
+    /// Only sol1 polls it, and only on Linux: where the resident set
+    /// cannot be read it raises a [`Warning::NoWatermark`] and scans as if
+    /// no limit were set. Other solvers reject it; see
+    /// [`Self::check_plain_scan`].
+    pub fn memory_watermark(mut self, bytes: u64) -> Self {
+        self.memory_watermark = Some(bytes);
+        self
+    }
+
+    pub fn watermark(&self) -> Option<u64> {
+        self.memory_watermark
+    }
+
+    /// Hand the stations back in whatever order the solver's tables hold
+    /// them instead of sorted by name. With millions of stations the sort
+    /// costs as much as a good part of the scan, and a caller that writes
+    /// them straight out or sorts them its own way does not need it. sol1
+    /// and sol2 skip it for a single input; merging the results of several
+    /// inputs, or of a windowed sol2 scan, still sorts.
+    pub fn unsorted(mut self, unsorted: bool) -> Self {
+        self.unsorted = unsorted;
+        self
+    }
+
+    pub fn is_unsorted(&self) -> bool {
+        self.unsorted
+    }
+
+    /// Also keep each station's [`crate::Distribution`], for
+    /// [`crate::Stat::Stddev`] and [`crate::Stat::P50`]. That is a 16 KiB
+    /// histogram per station and thread, so it is off unless asked for.
+    /// Only sol1 collects it; the other solvers refuse.
+    pub fn collect_distribution(mut self, collect: bool) -> Self {
+        self.distribution = collect;
+        self
+    }
+
+    pub fn collects_distribution(&self) -> bool {
+        self.distribution
+    }
+
+    /// Also keep each station's `n` latest values (at least one) in a
+    /// [`crate::Recent`], for [`crate::Stat::Last`] and for callers that
+    /// answer "what is it now" as well as "what was it". Latest means
+    /// furthest into the input, or into the last of several. Only sol1
+    /// keeps them; the other solvers refuse.
+    pub fn keep_last(mut self, n: usize) -> Self {
+        self.keep_last = Some(n.max(1));
+        self
+    }
+
+    pub fn kept_last(&self) -> Option<usize> {
+        self.keep_last
+    }
+
+    /// Hands `warning` to the sink set with [`Self::report_warnings`], if any.
+    pub fn warn(&self, warning: Warning) {
+        if let Some(sink) = &self.warnings {
+            (sink.0)(&warning);
+        }
+    }
+
+    /// Read rows in `format` instead of `name;value` text.
+    pub fn input_format(mut self, format: InputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn format(&self) -> InputFormat {
+        self.format
+    }
+
+    /// Where text rows keep their station and value, for tab- or
+    /// comma-separated files and ones with extra columns. Pairs with
+    /// another delimiter are scanned as fast as `name;value`; other layouts
+    /// split each line. Only applies to [`InputFormat::Text`].
+    pub fn text_columns(mut self, columns: Columns) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    pub fn columns(&self) -> Columns {
+        self.columns
+    }
+
+    /// Take each row's station and value from the first and second capture
+    /// groups of `re` instead of splitting `name;value`, so any line-based
+    /// log can be aggregated. Values are decimals, rounded to tenths; lines
+    /// that do not match, or whose value does not parse, are skipped. This
+    /// path runs the regex per line and is far slower than the default.
+    #[cfg(feature = "regex")]
+    pub fn extract_with(mut self, re: regex::bytes::Regex) -> Self {
+        self.extractor = Some(re);
+        self
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn extractor(&self) -> Option<&regex::bytes::Regex> {
+        self.extractor.as_ref()
+    }
+
+    /// Whether rows are extracted by a regex (see `extract_with`). Always
+    /// false without the `regex` feature, so solvers that cannot extract
+    /// reject an extractor whether or not they are built with it.
+    pub fn extracts(&self) -> bool {
+        #[cfg(feature = "regex")]
+        return self.extractor.is_some();
+        #[cfg(not(feature = "regex"))]
+        false
+    }
+
+    /// The included stations, or `None` when every station is aggregated.
+    pub fn stations(&self) -> Option<impl Iterator<Item = &[u8]>> {
+        self.stations.as_ref().map(|s| s.iter().map(|n| &n[..]))
+    }
+
+    pub fn max_rows(&self) -> Option<u64> {
+        self.max_rows
+    }
+
+    /// Whether rows for `station` should be aggregated.
+    #[inline]
+    pub fn includes(&self, station: &[u8]) -> bool {
+        self.stations.as_ref().is_none_or(|s| s.contains(station))
+    }
+
+    /// Fails with [`SolveError::Unsupported`] if any option asks for more
+    /// than a plain scan of text rows, for the solvers that do nothing else
+    /// (sol2 and sol3). Kernels are left to the caller, since only some of
+    /// those solvers have them.
+    pub fn check_plain_scan(&self) -> Result<(), SolveError> {
+        if self.format != InputFormat::Text {
+            let e = format!("{} input is not supported by this solver", self.format);
+            return Err(SolveError::Unsupported(e));
+        }
+        if self.stations.is_some() || self.preview.is_some() {
+            return Err(SolveError::Unsupported(
+                "station filters and previews are not supported by this solver".into(),
+            ));
+        }
+        if self.collects_distribution() || self.kept_last().is_some() {
+            return Err(SolveError::Unsupported(
+                "stddev, p50 and last values are not supported by this solver".into(),
+            ));
+        }
+        if self.extracts() {
+            return Err(SolveError::Unsupported(
+                "extractors are not supported by this solver".into(),
+            ));
+        }
+        if self.chunks.is_some()
+            || self.panic_policy() != PanicPolicy::Fail
+            || self.releases_pages()
+        {
+            return Err(SolveError::Unsupported(
+                "chunk reports, chunk panic policies and page release are not supported by this solver"
+                    .into(),
+            ));
+        }
+        if self.memory_watermark.is_some() {
+            return Err(SolveError::Unsupported(
+                "the memory watermark is not supported by this solver".into(),
+            ));
+        }
+        Ok(())
+    }
+}
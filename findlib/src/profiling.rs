@@ -0,0 +1,49 @@
+use criterion::profiler::Profiler;
+use pprof::ProfilerGuard;
+use std::fs::{self, File};
+use std::path::Path;
+
+/// Criterion profiler for the solvers' benches: samples the benchmarked
+/// code with pprof while `cargo bench -- --profile-time N` runs and writes
+/// `flamegraph.svg` next to criterion's other output for that benchmark
+/// (`target/criterion/<group>/<id>/profile/`).
+pub struct FlamegraphProfiler {
+    frequency: i32,
+    active: Option<ProfilerGuard<'static>>,
+}
+
+impl FlamegraphProfiler {
+    /// Samples `frequency` times per second.
+    pub fn new(frequency: i32) -> Self {
+        Self {
+            frequency,
+            active: None,
+        }
+    }
+}
+
+impl Profiler for FlamegraphProfiler {
+    fn start_profiling(&mut self, benchmark_id: &str, _benchmark_dir: &Path) {
+        let guard = ProfilerGuard::new(self.frequency)
+            .unwrap_or_else(|e| panic!("starting profiler for {benchmark_id}: {e}"));
+        self.active = Some(guard);
+    }
+
+    fn stop_profiling(&mut self, benchmark_id: &str, benchmark_dir: &Path) {
+        let Some(guard) = self.active.take() else {
+            return;
+        };
+        let report = guard
+            .report()
+            .build()
+            .unwrap_or_else(|e| panic!("building profile for {benchmark_id}: {e}"));
+        fs::create_dir_all(benchmark_dir)
+            .unwrap_or_else(|e| panic!("creating {}: {e}", benchmark_dir.display()));
+        let path = benchmark_dir.join("flamegraph.svg");
+        let file =
+            File::create(&path).unwrap_or_else(|e| panic!("creating {}: {e}", path.display()));
+        report
+            .flamegraph(file)
+            .unwrap_or_else(|e| panic!("writing {}: {e}", path.display()));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::SolveOptions;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Receives `(bytes_done, bytes_total)` while a solve scans; see
+/// [`crate::SolveOptions::report_progress`]. `bytes_total` is 0 when the
+/// size of the input is not known up front, as for a pipe.
+pub type ProgressFn = dyn Fn(u64, u64) + Send + Sync;
+
+#[derive(Clone)]
+pub struct ProgressSink(pub Arc<ProgressFn>);
+
+impl fmt::Debug for ProgressSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressSink").finish_non_exhaustive()
+    }
+}
+
+/// The bytes a solve has scanned so far, shared by its workers, which add
+/// each block as they finish it and so call the sink from their threads.
+pub struct Progress<'a> {
+    sink: &'a ProgressSink,
+    done: AtomicU64,
+    total: u64,
+}
+
+impl<'a> Progress<'a> {
+    /// A counter for `total` bytes of input, if `opts` asks for progress.
+    pub fn new(opts: &'a SolveOptions, total: u64) -> Option<Self> {
+        Some(Self {
+            sink: opts.progress()?,
+            done: AtomicU64::new(0),
+            total,
+        })
+    }
+
+    /// Counts `bytes` more as scanned and reports the new total. Reports
+    /// from different workers may arrive out of order.
+    pub fn add(&self, bytes: u64) {
+        let done = self.done.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        (self.sink.0)(done, self.total);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_progress_adds_up() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        assert!(Progress::new(&SolveOptions::default(), 10).is_none());
+        let opts = SolveOptions::default().report_progress({
+            let seen = seen.clone();
+            move |done, total| seen.lock().unwrap().push((done, total))
+        });
+        let progress = Progress::new(&opts, 10).unwrap();
+        std::thread::scope(|s| {
+            for _ in 0..5 {
+                s.spawn(|| progress.add(2));
+            }
+        });
+        let mut seen = seen.lock().unwrap().clone();
+        seen.sort_unstable();
+        assert_eq!(vec![(2, 10), (4, 10), (6, 10), (8, 10), (10, 10)], seen);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::StationStats;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// What [`query`] ranks stations by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Min,
+    /// The exact mean, not the one rounded to a tenth for printing.
+    Mean,
+    Max,
+}
+
+/// Which end of the ranking [`query`] returns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// Lowest first, e.g. the coldest stations.
+    Asc,
+    /// Highest first, e.g. the hottest stations.
+    Desc,
+}
+
+/// The `n` stations of `stats` that come first ranked by `by` in `order`,
+/// in that order. Ties go to the name that sorts first either way, so the
+/// result does not depend on the order of `stats`.
+pub fn query(stats: &[StationStats], by: SortKey, order: Order, n: usize) -> Vec<StationStats> {
+    let cmp = |a: &&StationStats, b: &&StationStats| {
+        let ranked = match by {
+            SortKey::Min => a.min.cmp(&b.min),
+            SortKey::Mean => compare_means(a, b),
+            SortKey::Max => a.max.cmp(&b.max),
+        };
+        match order {
+            Order::Asc => ranked,
+            Order::Desc => ranked.reverse(),
+        }
+        .then_with(|| a.name.cmp(&b.name))
+    };
+    let mut ranked: Vec<&StationStats> = stats.iter().collect();
+    if n < ranked.len() {
+        ranked.select_nth_unstable_by(n, cmp);
+        ranked.truncate(n);
+    }
+    ranked.sort_unstable_by(cmp);
+    ranked.into_iter().cloned().collect()
+}
+
+// `a.sum / a.count` against `b.sum / b.count`, without rounding either.
+fn compare_means(a: &StationStats, b: &StationStats) -> Ordering {
+    let (a_count, b_count) = (i128::from(a.count.max(1)), i128::from(b.count.max(1)));
+    (i128::from(a.sum) * b_count).cmp(&(i128::from(b.sum) * a_count))
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "min" => Ok(SortKey::Min),
+            "mean" => Ok(SortKey::Mean),
+            "max" => Ok(SortKey::Max),
+            _ => Err(format!("unknown sort key {s:?}, expected min, mean or max")),
+        }
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SortKey::Min => "min",
+            SortKey::Mean => "mean",
+            SortKey::Max => "max",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(stats: &[StationStats]) -> Vec<&str> {
+        stats.iter().map(|s| &s.name[..]).collect()
+    }
+
+    #[test]
+    fn test_query() {
+        let stats = [
+            StationStats::new("Abha", -50, 300, 100, 3),
+            StationStats::new("Lima", 0, 250, 200, 6),
+            StationStats::new("Oslo", -120, 150, 10, 2),
+            StationStats::new("Rome", 20, 350, 401, 12),
+        ];
+        assert_eq!(
+            vec!["Rome", "Abha"],
+            names(&query(&stats, SortKey::Mean, Order::Desc, 2))
+        );
+        assert_eq!(
+            vec!["Oslo", "Abha"],
+            names(&query(&stats, SortKey::Min, Order::Asc, 2))
+        );
+        assert_eq!(
+            vec!["Rome", "Abha", "Lima", "Oslo"],
+            names(&query(&stats, SortKey::Max, Order::Desc, 10))
+        );
+        // Abha and Lima tie on the exact mean; 401/12 is a hair above it
+        // but rounds to the same tenth.
+        assert_eq!(
+            vec!["Oslo", "Abha", "Lima", "Rome"],
+            names(&query(&stats, SortKey::Mean, Order::Asc, 4))
+        );
+        assert!(query(&stats, SortKey::Max, Order::Asc, 0).is_empty());
+    }
+}
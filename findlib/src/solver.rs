@@ -0,0 +1,132 @@
+use crate::stats::place_after;
+use crate::{SolveError, SolveOptions, StationStats, Warning, Warnings, merge_stats};
+use std::error::Error;
+use std::fmt;
+
+/// One implementation of the challenge. The workspace ships several; this
+/// lets callers pick one at runtime and compare them on the same input.
+pub trait Solver: Sync {
+    /// Short name, as accepted by the CLI's `--solver`.
+    fn name(&self) -> &'static str;
+
+    /// Aggregates the file at `filename`, sorted by station name. Options
+    /// the solver cannot honour are errors rather than silently ignored.
+    fn solve_stats(
+        &self,
+        filename: String,
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError>;
+
+    /// Like [`Self::solve_stats`], over measurements already in memory.
+    fn solve_stats_bytes(
+        &self,
+        data: &[u8],
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError>;
+
+    /// Aggregates several files into one result, as if they were one file,
+    /// e.g. daily shards of the same measurements. An error in one of them
+    /// is a [`FileError`] naming it. By default the files are solved one
+    /// after another with [`solve_in_turn`].
+    fn solve_stats_files(
+        &self,
+        filenames: &[String],
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        solve_in_turn(filenames, opts, |path, opts| self.solve_stats(path, opts))
+    }
+
+    /// Like [`Self::solve_stats_files`], but also returns every warning the
+    /// solve raised, for callers that would rather look at them afterwards
+    /// (or fail on any) than set a sink. A sink in `opts` still sees each.
+    fn solve_report(
+        &self,
+        filenames: &[String],
+        opts: &SolveOptions,
+    ) -> Result<SolveReport, SolveError> {
+        let warnings = Warnings::new();
+        let opts = opts.clone().collect_warnings(&warnings);
+        let stats = self.solve_stats_files(filenames, &opts)?;
+        Ok(SolveReport {
+            stats,
+            warnings: warnings.take(),
+            backends: Vec::new(),
+        })
+    }
+}
+
+/// Solves each of `filenames` with `solve`, one after another, and merges
+/// their results, the recent values of later files after those of earlier
+/// ones. Options that count rows, such as a per-station quota, count them
+/// in each file on its own. An error in one file is a [`FileError`] naming
+/// it.
+pub fn solve_in_turn(
+    filenames: &[String],
+    opts: &SolveOptions,
+    mut solve: impl FnMut(String, &SolveOptions) -> Result<Vec<StationStats>, SolveError>,
+) -> Result<Vec<StationStats>, SolveError> {
+    let mut parts = Vec::with_capacity(filenames.len());
+    let mut end = 0;
+    for path in filenames {
+        let mut stats = solve(path.clone(), opts).map_err(|error| FileError {
+            path: path.clone(),
+            error: Box::new(error),
+        })?;
+        end = place_after(&mut stats, end);
+        parts.push(stats);
+    }
+    Ok(merge_stats(parts))
+}
+
+/// A solve's result, what it warned about and how it read the input; see
+/// [`Solver::solve_report`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SolveReport {
+    /// Per-station aggregates, sorted by name.
+    pub stats: Vec<StationStats>,
+    /// In the order they were raised.
+    pub warnings: Vec<Warning>,
+    /// How each file was read, in order, from solvers that choose between
+    /// ways of reading; empty from the others.
+    pub backends: Vec<Backend>,
+}
+
+/// A way of getting a file's bytes in; see [`SolveReport::backends`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// Mapped whole, for a file already in the page cache.
+    Mmap,
+    /// Read front to back into buffers, as pipes are.
+    Streaming,
+    /// Read in blocks through io_uring, bypassing the page cache.
+    IoUring,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Backend::Mmap => "mmap",
+            Backend::Streaming => "streaming",
+            Backend::IoUring => "io_uring",
+        })
+    }
+}
+
+/// A failure in one of the files given to [`Solver::solve_stats_files`].
+#[derive(Debug)]
+pub struct FileError {
+    pub path: String,
+    pub error: Box<SolveError>,
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.error)
+    }
+}
+
+impl Error for FileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.error)
+    }
+}
@@ -0,0 +1,570 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Aggregated measurements for one station. Temperatures are kept in tenths
+/// of a degree exactly as parsed; conversion to degrees happens on output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StationStats {
+    pub name: String,
+    pub min: i32,
+    pub max: i32,
+    pub sum: i64,
+    pub count: u64,
+    /// Only kept with [`crate::SolveOptions::collect_distribution`].
+    pub dist: Option<Distribution>,
+    /// Only kept with [`crate::SolveOptions::keep_last`].
+    pub last: Option<Recent>,
+}
+
+// One bin per tenth of a degree over the challenge's -99.9..=99.9.
+const LOWEST: i32 = -999;
+const BINS: usize = 1999;
+
+/// What [`Stat::Stddev`] and [`Stat::P50`] need beyond a station's min,
+/// max, sum and count: the sum of squares, and a histogram with a bin per
+/// tenth of a degree from -99.9 to 99.9. A value outside that range counts
+/// towards the nearest end bin, so only the median is clamped by it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Distribution {
+    sum_sq: u128,
+    bins: Box<[u64]>,
+}
+
+impl Distribution {
+    pub fn new() -> Self {
+        Self {
+            sum_sq: 0,
+            bins: vec![0; BINS].into_boxed_slice(),
+        }
+    }
+
+    /// Records one value in tenths of a degree.
+    #[inline]
+    pub fn add(&mut self, tenths: i32) {
+        self.sum_sq += (i64::from(tenths) * i64::from(tenths)) as u128;
+        let bin = (tenths.clamp(LOWEST, -LOWEST) - LOWEST) as usize;
+        self.bins[bin] += 1;
+    }
+
+    /// Folds `other` into `self`, as if both had been recorded together.
+    pub fn merge(&mut self, other: &Distribution) {
+        self.sum_sq += other.sum_sq;
+        for (mine, theirs) in self.bins.iter_mut().zip(&other.bins) {
+            *mine += theirs;
+        }
+    }
+}
+
+impl Default for Distribution {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A station's most recent values: a ring of at most `cap` rows, ordered
+/// by where they are in the input. Rows reach a solver's tables out of
+/// order (each thread scans its own blocks), so each is kept with its
+/// position and merging keeps the latest ones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recent {
+    cap: usize,
+    // (position, tenths), oldest first.
+    rows: VecDeque<(u64, i32)>,
+}
+
+impl Recent {
+    /// An empty ring for the last `cap` values, at least one.
+    pub fn new(cap: usize) -> Self {
+        let cap = cap.max(1);
+        Self {
+            cap,
+            rows: VecDeque::with_capacity(cap),
+        }
+    }
+
+    /// Records a value in tenths of a degree from the row at `pos`, unless
+    /// the ring is full of later ones.
+    #[inline]
+    pub fn push(&mut self, pos: u64, tenths: i32) {
+        let at = match self.rows.back() {
+            Some(&(last, _)) if last < pos => self.rows.len(),
+            _ => self.rows.partition_point(|&(p, _)| p < pos),
+        };
+        if at == 0 && self.rows.len() == self.cap {
+            return;
+        }
+        self.rows.insert(at, (pos, tenths));
+        if self.rows.len() > self.cap {
+            self.rows.pop_front();
+        }
+    }
+
+    /// Folds `other`, whose positions count from the same place, into
+    /// `self`.
+    pub fn merge(&mut self, other: &Recent) {
+        for &(pos, tenths) in &other.rows {
+            self.push(pos, tenths);
+        }
+    }
+
+    /// Moves every position from counting from `from` to counting from
+    /// `to`, so rows of buffers or inputs scanned one after another order
+    /// after the earlier ones.
+    pub fn rebase(&mut self, from: u64, to: u64) {
+        for (pos, _) in &mut self.rows {
+            *pos = *pos - from + to;
+        }
+    }
+
+    /// The kept values in tenths, oldest first.
+    pub fn values(&self) -> impl Iterator<Item = i32> + '_ {
+        self.rows.iter().map(|&(_, tenths)| tenths)
+    }
+
+    /// The latest value in tenths.
+    pub fn latest(&self) -> Option<i32> {
+        self.rows.back().map(|&(_, tenths)| tenths)
+    }
+
+    // Past the last position kept.
+    fn end(&self) -> u64 {
+        self.rows.back().map_or(0, |&(pos, _)| pos + 1)
+    }
+}
+
+/// A per-station statistic that can be selected for output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stat {
+    Min,
+    Mean,
+    Max,
+    /// `max - min`, a quick volatility measure.
+    Range,
+    /// The number of measurements, printed as an integer.
+    Count,
+    /// Population standard deviation; needs a [`Distribution`].
+    Stddev,
+    /// Median, to the tenth; needs a [`Distribution`].
+    P50,
+    /// The latest value in the input; needs [`Recent`] values.
+    Last,
+}
+
+impl Stat {
+    /// The challenge's `min/mean/max` selection.
+    pub const DEFAULT: &[Stat] = &[Stat::Min, Stat::Mean, Stat::Max];
+
+    /// Whether solves must keep each station's [`Distribution`] for this
+    /// statistic (see [`crate::SolveOptions::collect_distribution`]).
+    pub fn needs_distribution(self) -> bool {
+        matches!(self, Stat::Stddev | Stat::P50)
+    }
+}
+
+impl FromStr for Stat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "min" => Ok(Stat::Min),
+            "mean" => Ok(Stat::Mean),
+            "max" => Ok(Stat::Max),
+            "range" => Ok(Stat::Range),
+            "count" => Ok(Stat::Count),
+            "stddev" => Ok(Stat::Stddev),
+            "p50" => Ok(Stat::P50),
+            "last" => Ok(Stat::Last),
+            _ => Err(format!(
+                "unknown stat {s:?}, expected min, mean, max, range, count, stddev, p50 or last"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Stat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stat::Min => "min",
+            Stat::Mean => "mean",
+            Stat::Max => "max",
+            Stat::Range => "range",
+            Stat::Count => "count",
+            Stat::Stddev => "stddev",
+            Stat::P50 => "p50",
+            Stat::Last => "last",
+        })
+    }
+}
+
+impl StationStats {
+    /// A station with these aggregates, and neither a [`Distribution`] nor
+    /// [`Recent`] values.
+    pub fn new(name: impl Into<String>, min: i32, max: i32, sum: i64, count: u64) -> Self {
+        Self {
+            name: name.into(),
+            min,
+            max,
+            sum,
+            count,
+            dist: None,
+            last: None,
+        }
+    }
+
+    /// Value of `stat` in tenths of a degree, except [`Stat::Count`]. The
+    /// statistics that need a distribution or recent values are 0 without.
+    pub fn get(&self, stat: Stat) -> i64 {
+        match stat {
+            Stat::Min => self.min as i64,
+            Stat::Mean => self.mean(),
+            Stat::Max => self.max as i64,
+            Stat::Range => self.max as i64 - self.min as i64,
+            Stat::Count => self.count as i64,
+            Stat::Stddev => self.stddev().unwrap_or(0),
+            Stat::P50 => self.p50().unwrap_or(0),
+            Stat::Last => self.last.as_ref().and_then(Recent::latest).unwrap_or(0) as i64,
+        }
+    }
+
+    /// Folds `other` into `self`, as if both had been aggregated together.
+    pub fn merge(&mut self, other: &StationStats) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+        match (&mut self.dist, &other.dist) {
+            (Some(mine), Some(theirs)) => mine.merge(theirs),
+            // Half a distribution would be a wrong one.
+            _ => self.dist = None,
+        }
+        match (&mut self.last, &other.last) {
+            (Some(mine), Some(theirs)) => mine.merge(theirs),
+            _ => self.last = None,
+        }
+    }
+
+    /// Population standard deviation in tenths of a degree, rounded to
+    /// nearest, if the distribution was kept.
+    pub fn stddev(&self) -> Option<i64> {
+        let dist = self.dist.as_ref()?;
+        if self.count == 0 {
+            return Some(0);
+        }
+        // n² times the variance, exactly; only the root is inexact.
+        let n = i128::from(self.count);
+        let scaled = n * dist.sum_sq as i128 - i128::from(self.sum).pow(2);
+        Some(((scaled.max(0) as f64).sqrt() / n as f64).round() as i64)
+    }
+
+    /// The median in tenths of a degree, if the distribution was kept: the
+    /// lower of the middle two values when the count is even.
+    pub fn p50(&self) -> Option<i64> {
+        let dist = self.dist.as_ref()?;
+        let rank = self.count.div_ceil(2).max(1);
+        let mut seen = 0;
+        for (i, &n) in dist.bins.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Some(i64::from(LOWEST) + i as i64);
+            }
+        }
+        Some(0)
+    }
+
+    /// Mean in tenths of a degree, rounded half up; see [`mean_tenths`].
+    pub fn mean(&self) -> i64 {
+        mean_tenths(self.sum, self.count)
+    }
+}
+
+/// Combines results of separate solves, each sorted by name, into one
+/// sorted result with every station's parts merged.
+pub fn merge_stats(parts: impl IntoIterator<Item = Vec<StationStats>>) -> Vec<StationStats> {
+    let mut all: BTreeMap<String, StationStats> = BTreeMap::new();
+    for s in parts.into_iter().flatten() {
+        match all.get_mut(&s.name) {
+            Some(mine) => mine.merge(&s),
+            None => {
+                all.insert(s.name.clone(), s);
+            }
+        }
+    }
+    all.into_values().collect()
+}
+
+/// Moves the recent values in `stats`, one input's result, after every
+/// position up to `end`, and returns the end of the positions kept now: for
+/// merging results of inputs that follow each other.
+pub(crate) fn place_after(stats: &mut [StationStats], end: u64) -> u64 {
+    let mut next = end;
+    for last in stats.iter_mut().filter_map(|s| s.last.as_mut()) {
+        last.rebase(0, end);
+        next = next.max(last.end());
+    }
+    next
+}
+
+/// `sum / count` in tenths of a degree, rounded half up (towards positive
+/// infinity) as the challenge's reference does, but exactly: without going
+/// through floating point, a mean of -0.05 is 0.0 and one of -0.15 is -0.1.
+pub fn mean_tenths(sum: i64, count: u64) -> i64 {
+    let denom = count as i64;
+    (2 * sum + denom).div_euclid(2 * denom)
+}
+
+/// Appends `tenths` as a decimal with one fractional digit, e.g. `-12.3`.
+/// Zero is always `0.0`: an integer has no negative zero, so a mean that
+/// rounds to zero from below cannot print as `-0.0` the way an `f64` would.
+pub fn write_tenths(out: &mut String, tenths: i64) {
+    if tenths < 0 {
+        out.push('-');
+    }
+    let abs = tenths.unsigned_abs();
+    out.push_str(itoa::Buffer::new().format(abs / 10));
+    out.push('.');
+    out.push(char::from(b'0' + (abs % 10) as u8));
+}
+
+/// Formats `stats` (sorted by name) in the challenge's
+/// `{name=min/mean/max, ...}` layout.
+pub fn format_stats(stats: &[StationStats]) -> String {
+    format_selected(stats, Stat::DEFAULT)
+}
+
+/// Like [`format_stats`], but prints the `selection` of statistics for each
+/// station, in order, separated by `/`.
+pub fn format_selected(stats: &[StationStats], selection: &[Stat]) -> String {
+    let mut out = String::with_capacity(stats.len().saturating_mul(32) + 3);
+    write_selected(&mut out, stats, selection);
+    out
+}
+
+/// Appends what [`format_selected`] returns to `out`, so callers formatting
+/// repeatedly can keep one buffer instead of allocating per call.
+pub fn write_selected(out: &mut String, stats: &[StationStats], selection: &[Stat]) {
+    out.push('{');
+    for (idx, v) in stats.iter().enumerate() {
+        write_entry(out, idx, v, selection);
+    }
+    out.push('}');
+    out.push('\n');
+}
+
+/// Writes what [`format_selected`] returns to `out` a station at a time,
+/// so the whole line is never held in memory: for results with millions of
+/// stations, into a buffered writer.
+pub fn stream_selected(
+    out: &mut impl io::Write,
+    stats: &[StationStats],
+    selection: &[Stat],
+) -> io::Result<()> {
+    let mut entry = String::new();
+    out.write_all(b"{")?;
+    for (idx, v) in stats.iter().enumerate() {
+        entry.clear();
+        write_entry(&mut entry, idx, v, selection);
+        out.write_all(entry.as_bytes())?;
+    }
+    out.write_all(b"}\n")
+}
+
+// Appends the `idx`th station of the line, `name=a/b/c`, after a comma
+// unless it is the first.
+fn write_entry(out: &mut String, idx: usize, v: &StationStats, selection: &[Stat]) {
+    if idx > 0 {
+        out.push_str(", ");
+    }
+    out.push_str(&v.name);
+    out.push('=');
+    for (i, &stat) in selection.iter().enumerate() {
+        if i > 0 {
+            out.push('/');
+        }
+        write_stat(out, v, stat);
+    }
+}
+
+/// Appends `s`'s value of `stat`: [`Stat::Count`] as an integer, every
+/// other one as [`write_tenths`] does.
+pub fn write_stat(out: &mut String, s: &StationStats, stat: Stat) {
+    match stat {
+        Stat::Count => out.push_str(itoa::Buffer::new().format(s.count)),
+        _ => write_tenths(out, s.get(stat)),
+    }
+}
+
+/// Formats `stats` byte for byte as the challenge's Java baseline
+/// (`CalculateAverage_baseline`) prints them, to compare against its
+/// published expected outputs. That differs from [`format_stats`] in one
+/// corner: names are ordered as `String.compareTo` orders them, by UTF-16
+/// code unit. Neither prints `-0.0`, and both round means exactly where
+/// the baseline's `double` sums can miss a tie by a hair.
+pub fn format_official(stats: &[StationStats]) -> String {
+    let mut sorted: Vec<&StationStats> = stats.iter().collect();
+    sorted.sort_by(|a, b| a.name.encode_utf16().cmp(b.name.encode_utf16()));
+    let mut out = String::with_capacity(stats.len().saturating_mul(32) + 3);
+    out.push('{');
+    for (idx, v) in sorted.into_iter().enumerate() {
+        if idx > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(&v.name);
+        out.push('=');
+        write_tenths(&mut out, v.min as i64);
+        out.push('/');
+        write_tenths(&mut out, v.mean());
+        out.push('/');
+        write_tenths(&mut out, v.max as i64);
+    }
+    out.push('}');
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_selected_range() {
+        let stats = [StationStats::new("Oslo", -32, 105, 150, 3)];
+        let got = format_selected(&stats, &[Stat::Min, Stat::Max, Stat::Range]);
+        assert_eq!("{Oslo=-3.2/10.5/13.7}\n", got);
+        let mut streamed = Vec::new();
+        let two = [stats[0].clone(), StationStats::new("Abha", 0, 10, 10, 2)];
+        stream_selected(&mut streamed, &two, &[Stat::Max]).unwrap();
+        assert_eq!(format_selected(&two, &[Stat::Max]).as_bytes(), streamed);
+    }
+
+    #[test]
+    fn test_stddev_and_p50() {
+        let station = |values: &[i32]| {
+            let mut dist = Distribution::new();
+            values.iter().for_each(|&v| dist.add(v));
+            StationStats {
+                dist: Some(dist),
+                ..StationStats::new(
+                    "Oslo",
+                    *values.iter().min().unwrap(),
+                    *values.iter().max().unwrap(),
+                    values.iter().map(|&v| i64::from(v)).sum(),
+                    values.len() as u64,
+                )
+            }
+        };
+        // 2, 4, 4, 4, 5, 5, 7, 9 degrees: stddev 2, lower median 4.
+        let mut s = station(&[20, 40, 40, 40]);
+        s.merge(&station(&[50, 50, 70, 90]));
+        assert_eq!(station(&[20, 40, 40, 40, 50, 50, 70, 90]), s);
+        let selection = [Stat::Count, Stat::Stddev, Stat::P50];
+        assert_eq!("{Oslo=8/2.0/4.0}\n", format_selected(&[s], &selection));
+        // Out of range values are clamped in the histogram only.
+        let wide = station(&[-5000, -5000, 0]);
+        assert_eq!((Some(-999), Some(2357)), (wide.p50(), wide.stddev()));
+        // Without a distribution on both sides there is none.
+        let mut s = station(&[10]);
+        s.merge(&StationStats {
+            dist: None,
+            last: None,
+            ..station(&[20])
+        });
+        assert_eq!((None, 0), (s.dist.as_ref(), s.get(Stat::P50)));
+    }
+
+    #[test]
+    fn test_recent_keeps_latest() {
+        let mut r = Recent::new(3);
+        for (pos, tenths) in [(5, 50), (1, 10), (9, 90), (7, 70), (2, 20)] {
+            r.push(pos, tenths);
+        }
+        assert_eq!(vec![50, 70, 90], r.values().collect::<Vec<_>>());
+        let mut other = Recent::new(3);
+        other.push(8, 80);
+        let mut merged = r.clone();
+        merged.merge(&other);
+        assert_eq!(vec![70, 80, 90], merged.values().collect::<Vec<_>>());
+
+        // A later input's rows follow, however early in it they are.
+        let station = |last| StationStats {
+            last: Some(last),
+            ..StationStats::new("Oslo", 0, 0, 0, 1)
+        };
+        let mut parts = [vec![station(r)], vec![station(other)]];
+        let end = place_after(&mut parts[0], 0);
+        assert_eq!(10, end);
+        place_after(&mut parts[1], end);
+        let merged = merge_stats(parts);
+        assert_eq!("{Oslo=8.0}\n", format_selected(&merged, &[Stat::Last]));
+    }
+
+    #[test]
+    fn test_merge_stats() {
+        let merged = merge_stats([
+            vec![
+                StationStats::new("Abha", -5, 10, 5, 2),
+                StationStats::new("Oslo", 0, 3, 3, 2),
+            ],
+            vec![],
+            vec![
+                StationStats::new("Bern", 1, 2, 3, 2),
+                StationStats::new("Oslo", -4, 1, -3, 2),
+            ],
+        ]);
+        assert_eq!(
+            "{Abha=-0.5/0.3/1.0, Bern=0.1/0.2/0.2, Oslo=-0.4/0.0/0.3}\n",
+            format_stats(&merged)
+        );
+    }
+
+    #[test]
+    fn test_format_official() {
+        // U+10000 sorts after U+FF61 by UTF-8 bytes, before it by UTF-16.
+        let stats = [
+            StationStats::new("\u{ff61}", -10, 10, -1, 2),
+            StationStats::new("\u{10000}", -10, 10, -3, 2),
+            StationStats::new("Oslo", -10, 10, 3, 2),
+        ];
+        assert_eq!(
+            "{Oslo=-1.0/0.2/1.0, \u{10000}=-1.0/-0.1/1.0, \u{ff61}=-1.0/0.0/1.0}\n",
+            format_official(&stats)
+        );
+        assert_eq!("{}\n", format_official(&[]));
+    }
+
+    #[test]
+    fn test_mean_rounds_half_up() {
+        // (sum, count) in tenths, and the mean rounded to tenths: ties go
+        // towards positive infinity on both sides of zero.
+        for (sum, count, want) in [
+            (-1, 2, 0),
+            (1, 2, 1),
+            (-3, 2, -1),
+            (3, 2, 2),
+            (-5, 2, -2),
+            (-1997, 2, -998),
+            (1997, 2, 999),
+            (-47, 4, -12),
+            (-1, 3, 0),
+            (-2, 3, -1),
+            (-999, 1, -999),
+            (0, 7, 0),
+            (-999 * 1_000_000_001, 1_000_000_001, -999),
+        ] {
+            assert_eq!(want, mean_tenths(sum, count), "{sum}/{count}");
+        }
+    }
+
+    #[test]
+    fn test_no_negative_zero() {
+        // -0.0 as a value, and a mean of -0.03 that rounds to zero.
+        let stats = [StationStats::new("Oslo", -1, 0, -1, 3)];
+        assert_eq!("{Oslo=-0.1/0.0/0.0}\n", format_stats(&stats));
+        assert_eq!("{Oslo=-0.1/0.0/0.0}\n", format_official(&stats));
+        assert_eq!(0, mean_tenths(-1, 2 * 10 + 1));
+        let mut out = String::new();
+        write_tenths(&mut out, -0);
+        assert_eq!("0.0", out);
+    }
+}
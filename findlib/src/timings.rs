@@ -0,0 +1,59 @@
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Where a solve spent its time, split so constant costs can be told apart
+/// from throughput; see [`crate::SolveOptions::report_timings`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timings {
+    /// Opening and mapping (or reading) the input; zero for in-memory input.
+    pub load: Duration,
+    /// From the scan's start until the last worker was running; zero when
+    /// the input was small enough to scan on the calling thread.
+    pub spawn: Duration,
+    /// Scanning, after `spawn`.
+    pub scan: Duration,
+    /// Merging the workers' tables and sorting the result.
+    pub merge: Duration,
+}
+
+/// Receives a solve's [`Timings`] when it finishes.
+pub type TimingsFn = dyn Fn(&Timings) + Send + Sync;
+
+#[derive(Clone)]
+pub struct TimingsSink(pub Arc<TimingsFn>);
+
+impl fmt::Debug for TimingsSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimingsSink").finish_non_exhaustive()
+    }
+}
+
+/// One block of a solve's scan: what it held, who scanned it and for how
+/// long; see [`crate::SolveOptions::report_chunks`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkStats {
+    /// Bytes of the input the block covered.
+    pub range: Range<usize>,
+    /// Index of the worker thread that scanned it.
+    pub worker: usize,
+    /// Rows aggregated, after any station filter.
+    pub rows: u64,
+    /// Distinct stations among those rows.
+    pub keys: usize,
+    pub elapsed: Duration,
+}
+
+/// Receives every [`ChunkStats`] of a solve, ordered by offset, when it
+/// finishes.
+pub type ChunksFn = dyn Fn(&[ChunkStats]) + Send + Sync;
+
+#[derive(Clone)]
+pub struct ChunksSink(pub Arc<ChunksFn>);
+
+impl fmt::Debug for ChunksSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunksSink").finish_non_exhaustive()
+    }
+}
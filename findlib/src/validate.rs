@@ -0,0 +1,182 @@
+use crate::Columns;
+use std::fmt;
+use std::str::FromStr;
+
+/// How a solve treats rows that are not `<name>;<-?d?d.d>`; see
+/// [`crate::SolveOptions::validate`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Validation {
+    /// Trust the input. The fast parsers assume every row is well formed;
+    /// a malformed one may panic (sol1) or be misread (sol2).
+    #[default]
+    Trusted,
+    /// Check every row and fail on a malformed one with a [`ParseError`].
+    Strict,
+    /// Check every row, leave the malformed ones out and report how many
+    /// with a [`crate::Warning::SkippedRows`].
+    Lenient,
+}
+
+impl FromStr for Validation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trusted" => Ok(Validation::Trusted),
+            "strict" => Ok(Validation::Strict),
+            "lenient" => Ok(Validation::Lenient),
+            _ => Err(format!(
+                "unknown validation {s:?}, expected trusted, strict or lenient"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Validation::Trusted => "trusted",
+            Validation::Strict => "strict",
+            Validation::Lenient => "lenient",
+        })
+    }
+}
+
+/// A malformed row found under [`Validation::Strict`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Line of the row, counting from 1.
+    pub line: u64,
+    /// Where the row starts in the input.
+    pub byte_offset: usize,
+    /// What is wrong with it.
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {} (byte {}): {}",
+            self.line, self.byte_offset, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Splits one row, without its newline, into station and value in tenths,
+/// accepting exactly `<name>;<-?d?d.d>` with a non-empty UTF-8 name. The `\r`
+/// of a CRLF line ending is dropped first. The reason is what
+/// [`ParseError::reason`] reports.
+pub fn parse_row(line: &[u8]) -> Result<(&[u8], i32), &'static str> {
+    parse_row_with(line, Columns::CHALLENGE)
+}
+
+/// [`parse_row`] for rows laid out as `columns` say. A pair takes exactly
+/// one delimiter; with more columns, the ones besides the station and value
+/// are not looked at.
+pub fn parse_row_with(line: &[u8], columns: Columns) -> Result<(&[u8], i32), &'static str> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let (none, several) = match columns.delimiter {
+        b';' => ("no ';'", "more than one ';'"),
+        _ => ("no delimiter", "more than one delimiter"),
+    };
+    let Some((name, value)) = columns.split(line) else {
+        return Err(if columns.is_pair() {
+            none
+        } else {
+            "too few fields"
+        });
+    };
+    if name.is_empty() {
+        return Err("empty station name");
+    }
+    if columns.is_pair() && value.contains(&columns.delimiter) {
+        return Err(several);
+    }
+    if std::str::from_utf8(name).is_err() {
+        return Err("station name is not UTF-8");
+    }
+    parse_value(value)
+        .map(|v| (name, v))
+        .ok_or("value is not -?d?d.d")
+}
+
+// Strict `-?d?d.d`, unlike the hot-path parsers which trust their input.
+pub(crate) fn parse_value(v: &[u8]) -> Option<i32> {
+    let (neg, v) = match v.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, v),
+    };
+    let (int, frac) = match v {
+        [a, b'.', f] => (&[*a][..], *f),
+        [a, b, b'.', f] => (&[*a, *b][..], *f),
+        _ => return None,
+    };
+    let mut acc = 0i32;
+    for &d in int.iter().chain(std::iter::once(&frac)) {
+        if !d.is_ascii_digit() {
+            return None;
+        }
+        acc = acc * 10 + (d - b'0') as i32;
+    }
+    Some(if neg { -acc } else { acc })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_row() {
+        assert_eq!(Ok((&b"Oslo"[..], -34)), parse_row(b"Oslo;-3.4"));
+        assert_eq!(Ok((&b"St. John's"[..], 152)), parse_row(b"St. John's;15.2"));
+        assert_eq!(Ok((&b"A"[..], 999)), parse_row(b"A;99.9"));
+        assert_eq!(Ok((&b"A"[..], 10)), parse_row(b"A;1.0\r"));
+        assert_eq!(Err("no ';'"), parse_row(b"noise"));
+        assert_eq!(Err("empty station name"), parse_row(b";1.0"));
+        assert_eq!(Err("more than one ';'"), parse_row(b"A;B;1.0"));
+        assert_eq!(Err("station name is not UTF-8"), parse_row(b"\xffA;1.0"));
+        for bad in [
+            &b"A;"[..],
+            b"A;1.23",
+            b"A;123.4",
+            b"A;1,2",
+            b"A;-.5",
+            b"A;1.0\r\r",
+        ] {
+            assert_eq!(Err("value is not -?d?d.d"), parse_row(bad), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_row_with_columns() {
+        let tsv = Columns {
+            delimiter: b'\t',
+            value: 2,
+        };
+        assert_eq!(
+            Ok((&b"Oslo"[..], -34)),
+            parse_row_with(b"Oslo\t-3.4\r", tsv)
+        );
+        assert_eq!(Err("no delimiter"), parse_row_with(b"Oslo;-3.4", tsv));
+        assert_eq!(
+            Err("more than one delimiter"),
+            parse_row_with(b"A\tB\t1.0", tsv)
+        );
+        // The third of four columns; the rest is not looked at.
+        let csv = Columns {
+            delimiter: b',',
+            value: 3,
+        };
+        let row = b"Oslo,2024-01-01T00:00,-3.4,x\r";
+        assert_eq!(Ok((&b"Oslo"[..], -34)), parse_row_with(row, csv));
+        assert_eq!(Ok((&b"Oslo"[..], 10)), parse_row_with(b"Oslo,,1.0", csv));
+        assert_eq!(Err("too few fields"), parse_row_with(b"Oslo,1.0", csv));
+        assert_eq!(
+            Err("value is not -?d?d.d"),
+            parse_row_with(b"Oslo,1.0,", csv)
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+/// A block of the input whose scan failed, on a value with a byte no
+/// number has or in a panic; see [`crate::SolveOptions::on_chunk_panic`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkError {
+    /// Bytes of the input the block covered.
+    pub range: Range<usize>,
+    /// Why: the byte, or the panic's message.
+    pub message: String,
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Range { start, end } = self.range;
+        write!(f, "scanning bytes {start}..{end} failed: {}", self.message)
+    }
+}
+
+impl std::error::Error for ChunkError {}
+
+/// Something a solve worked around instead of failing on; see
+/// [`crate::SolveOptions::report_warnings`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// Only `started` of `wanted` worker threads could be started (a
+    /// container's pids limit, say); the rest of the work ran on fewer.
+    FewerWorkers {
+        wanted: usize,
+        started: usize,
+        error: String,
+    },
+    /// The thread that reads streamed input ahead of the scan could not be
+    /// started, so reading and scanning took turns.
+    NoReadAhead { error: String },
+    /// The thread that emits previews could not be started; none were.
+    NoPreviews { error: String },
+    /// io_uring is not available (an old kernel, a seccomp policy that
+    /// blocks it, or not Linux), so sol3 read with plain reads.
+    NoIoUring { error: String },
+    /// Scanning a block failed and, per [`crate::PanicPolicy::Skip`], its
+    /// rows were left out of the result.
+    SkippedChunk(ChunkError),
+    /// Per [`crate::Validation::Lenient`], `count` malformed rows were left
+    /// out of the result.
+    SkippedRows { count: u64 },
+    /// `count` stations' names were not valid UTF-8; invalid bytes were
+    /// replaced with U+FFFD, which may have merged stations whose names
+    /// differed only there.
+    LossyNames { count: u64 },
+    /// A station table's probe passed too many other names, as crafted
+    /// names make it, and so can very many that differ in only a few bits
+    /// (`S0000001`, `S0000002`, ...); `tables` tables switched from the
+    /// seeded hash to the keyed one (as with `--untrusted-input`) mid-scan.
+    Rekeyed { tables: u64 },
+    /// Resident memory reached `resident` bytes, past the
+    /// [`crate::SolveOptions::memory_watermark`]; the scan went on with
+    /// `workers` workers after shedding what it could.
+    Watermark {
+        resident: u64,
+        watermark: u64,
+        workers: usize,
+    },
+    /// A [`crate::SolveOptions::memory_watermark`] was set but the resident
+    /// set could not be read (not Linux, or /proc out of reach), so the
+    /// scan ran without it.
+    NoWatermark { error: String },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::FewerWorkers {
+                wanted,
+                started,
+                error,
+            } => write!(
+                f,
+                "started {started} of {wanted} worker threads ({error}); scanned with fewer"
+            ),
+            Warning::NoReadAhead { error } => write!(
+                f,
+                "could not start the reader thread ({error}); reading and scanning in turn"
+            ),
+            Warning::NoPreviews { error } => write!(
+                f,
+                "could not start the preview thread ({error}); no previews are emitted"
+            ),
+            Warning::NoIoUring { error } => write!(
+                f,
+                "could not set up io_uring ({error}); reading with plain reads"
+            ),
+            Warning::SkippedChunk(e) => write!(f, "{e}; its rows were skipped"),
+            Warning::SkippedRows { count } => write!(f, "skipped {count} malformed rows"),
+            Warning::LossyNames { count } => write!(
+                f,
+                "{count} station names were not valid UTF-8; invalid bytes were replaced"
+            ),
+            Warning::Rekeyed { tables } => write!(
+                f,
+                "{tables} station tables saw long probe chains (crafted names, or many \
+                 near-identical ones) and switched to the keyed hash"
+            ),
+            Warning::Watermark {
+                resident,
+                watermark,
+                workers,
+            } => write!(
+                f,
+                "resident memory reached {resident} bytes, past the {watermark}-byte \
+                 watermark; flushed tables and scanning with {workers} workers"
+            ),
+            Warning::NoWatermark { error } => write!(
+                f,
+                "could not read the resident set ({error}); the memory watermark is ignored"
+            ),
+        }
+    }
+}
+
+/// Warnings gathered for the caller to look at once the solve is over, to
+/// log them or to fail on them; see
+/// [`crate::SolveOptions::collect_warnings`]. Clones share one collection.
+#[derive(Clone, Debug, Default)]
+pub struct Warnings(Arc<Mutex<Vec<Warning>>>);
+
+impl Warnings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, warning: Warning) {
+        self.0.lock().unwrap().push(warning);
+    }
+
+    /// The warnings gathered so far, in the order they were raised, leaving
+    /// none behind.
+    pub fn take(&self) -> Vec<Warning> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+}
+
+/// Receives each [`Warning`] as it happens.
+pub type WarningFn = dyn Fn(&Warning) + Send + Sync;
+
+#[derive(Clone)]
+pub struct WarningSink(pub Arc<WarningFn>);
+
+impl fmt::Debug for WarningSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WarningSink").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Any input may be rejected with an error, but it must never panic or read
+// out of bounds.
+fuzz_target!(|data: &[u8]| {
+    let _ = sol2::solve_bytes(data);
+});
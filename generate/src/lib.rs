@@ -0,0 +1,128 @@
+//! Writes measurement files for the challenge: [`generate`] makes seeded,
+//! parallel output for both the `generate` binary and the `onebrc generate`
+//! subcommand.
+
+use findlib::STATIONS;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::Normal;
+use rayon::prelude::*;
+use std::io::{self, Write};
+
+/// Most unique station names the challenge allows.
+pub const MAX_STATIONS: usize = 10_000;
+
+// Rows per block. Each block has its own RNG seeded from the run's seed and
+// the block's index, so the output only depends on the seed, not on how
+// many threads produced it.
+const BLOCK_ROWS: u64 = 1 << 18;
+
+/// Writes `rows` measurements for `stations` stations to `out`, each value
+/// drawn from a normal distribution (σ = 10) around its station's mean and
+/// clamped to the challenge's ±99.9 range. The first stations are the
+/// reference generator's; past its 413 the names repeat with a number
+/// appended. Blocks are generated on `workers` threads and written in order.
+pub fn generate(
+    out: &mut impl Write,
+    rows: u64,
+    stations: usize,
+    seed: u64,
+    workers: usize,
+) -> io::Result<()> {
+    generate_blocks(out, rows, stations, seed, workers, BLOCK_ROWS)
+}
+
+fn generate_blocks(
+    out: &mut impl Write,
+    rows: u64,
+    stations: usize,
+    seed: u64,
+    workers: usize,
+    block_rows: u64,
+) -> io::Result<()> {
+    if stations == 0 || stations > MAX_STATIONS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("station count must be 1..={MAX_STATIONS}, got {stations}"),
+        ));
+    }
+    let stations: Vec<(String, Normal<f64>)> = (0..stations).map(station).collect();
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(workers)
+        .build()
+        .map_err(io::Error::other)?;
+    let blocks = rows.div_ceil(block_rows);
+    // A batch per round keeps every thread busy while holding only
+    // `workers` blocks in memory.
+    for batch in (0..blocks).step_by(workers.max(1)) {
+        let end = (batch + workers.max(1) as u64).min(blocks);
+        let bufs: Vec<Vec<u8>> = pool.install(|| {
+            (batch..end)
+                .into_par_iter()
+                .map(|idx| {
+                    let n = block_rows.min(rows - idx * block_rows);
+                    block(&stations, seed, idx, n)
+                })
+                .collect()
+        });
+        for buf in bufs {
+            out.write_all(&buf)?;
+        }
+    }
+    Ok(())
+}
+
+fn station(idx: usize) -> (String, Normal<f64>) {
+    let (name, mean) = STATIONS[idx % STATIONS.len()];
+    let name = match idx / STATIONS.len() {
+        0 => name.to_string(),
+        n => format!("{name} {}", n + 1),
+    };
+    (name, Normal::new(mean as f64, 10.0).unwrap())
+}
+
+fn block(stations: &[(String, Normal<f64>)], seed: u64, idx: u64, rows: u64) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(seed ^ idx.wrapping_mul(0x9E3779B97F4A7C15));
+    let mut buf = String::with_capacity(rows as usize * 16);
+    for _ in 0..rows {
+        let (name, dist) = &stations[rng.random_range(0..stations.len())];
+        let tenths = (rng.sample(dist) * 10.0).round().clamp(-999.0, 999.0) as i64;
+        buf.push_str(name);
+        buf.push(';');
+        findlib::write_tenths(&mut buf, tenths);
+        buf.push('\n');
+    }
+    buf.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_is_seeded_and_valid() {
+        let run = |workers| {
+            let mut out = Vec::new();
+            generate_blocks(&mut out, 1000, 500, 42, workers, 64).unwrap();
+            out
+        };
+        let out = run(1);
+        assert_eq!(out, run(3));
+
+        let text = std::str::from_utf8(&out).unwrap();
+        assert_eq!(1000, text.lines().count());
+        for line in text.lines() {
+            let (name, value) = line.split_once(';').unwrap();
+            assert!(!name.is_empty() && name.len() <= 100, "{line}");
+            assert_eq!(
+                Some(1),
+                value.split_once('.').map(|(_, f)| f.len()),
+                "{line}"
+            );
+            let value: f64 = value.parse().unwrap();
+            assert!((-99.9..=99.9).contains(&value), "{line}");
+        }
+        assert!(text.contains(" 2;"), "names past the reference list");
+    }
+}
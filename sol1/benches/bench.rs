@@ -1,4 +1,5 @@
 use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use findlib::FlamegraphProfiler;
 use std::fs;
 use std::hint::black_box;
 use std::path::PathBuf;
@@ -25,10 +26,45 @@ fn criterion_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+// The built-in sample from memory, so there is a number to compare without
+// generating `data/measurements.txt` and without disk reads in the loop.
+fn sample_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sol1-bytes");
+    group.throughput(Throughput::Bytes(findlib::SAMPLE.len() as u64));
+    group.bench_function(BenchmarkId::from_parameter("sample"), |b| {
+        b.iter(|| black_box(sol1::solve_bytes(black_box(findlib::SAMPLE)).unwrap()))
+    });
+    group.finish();
+}
+
+// 200K stations, four rows each: merging the workers' tables and sorting
+// the stations cost as much as the scan here.
+fn cardinality_benchmark(c: &mut Criterion) {
+    let stations = 200_000u64;
+    let mut data = Vec::new();
+    for i in 0..stations * 4 {
+        let name = (i % stations).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 40;
+        let value = (i % 1999) as i64 - 999;
+        let sign = if value < 0 { "-" } else { "" };
+        let value = value.abs();
+        let row = format!("S{name:07};{sign}{}.{}\n", value / 10, value % 10);
+        data.extend_from_slice(row.as_bytes());
+    }
+
+    let mut group = c.benchmark_group("sol1-cardinality");
+    group.sample_size(20);
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function(BenchmarkId::from_parameter("200k-stations"), |b| {
+        b.iter(|| black_box(sol1::solve_stats_bytes(black_box(&data)).unwrap()))
+    });
+    group.finish();
+}
+
 criterion_group!(
     name = benches;
-    config = Criterion::default();
-    targets = criterion_benchmark,
+    // `cargo bench -- --profile-time 10` writes a flamegraph per benchmark.
+    config = Criterion::default().with_profiler(FlamegraphProfiler::new(100));
+    targets = criterion_benchmark, sample_benchmark, cardinality_benchmark,
 );
 
 criterion_main!(benches);
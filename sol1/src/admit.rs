@@ -0,0 +1,251 @@
+use ahash::{AHashMap, RandomState};
+use findlib::{
+    ChunkError, PanicPolicy, ParseError, Progress, SolveError, SolveOptions, Validation, Warning,
+};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Per-row gate shared by all workers: applies the include list and, with
+/// `max_rows_per_station`, a global quota that lets the run end early. It
+/// also collects the blocks whose scan failed and the malformed rows
+/// checked scans found, which can end it too, and counts the bytes
+/// scanned for progress reports.
+pub(crate) struct Admit<'a> {
+    opts: &'a SolveOptions,
+    run: Arc<Run<'a>>,
+    failures: Mutex<Vec<ChunkError>>,
+    // The earliest malformed row found under `Validation::Strict`; its line
+    // is 0 until `locate_rejection` numbers it.
+    rejected: Mutex<Option<ParseError>>,
+    mapped: bool,
+}
+
+// What the inputs of one solve share (see `Admit::another`).
+struct Run<'a> {
+    quota: Option<Quota<'a>>,
+    failed: AtomicBool,
+    skipped_rows: AtomicU64,
+    progress: Option<Progress<'a>>,
+}
+
+struct Quota<'a> {
+    max: u64,
+    counts: AHashMap<&'a [u8], AtomicU64>,
+    // Stations that have not reached `max` yet.
+    open: AtomicUsize,
+}
+
+impl<'a> Admit<'a> {
+    pub(crate) fn new(opts: &'a SolveOptions) -> Self {
+        let quota = match (opts.stations(), opts.max_rows()) {
+            (Some(names), Some(max)) => {
+                let counts: AHashMap<_, _> = names.map(|n| (n, AtomicU64::new(0))).collect();
+                // A zero quota is met before the first row.
+                let open = if max == 0 { 0 } else { counts.len() };
+                Some(Quota {
+                    max,
+                    counts,
+                    open: AtomicUsize::new(open),
+                })
+            }
+            _ => None,
+        };
+        Self {
+            opts,
+            run: Arc::new(Run {
+                quota,
+                failed: AtomicBool::new(false),
+                skipped_rows: AtomicU64::new(0),
+                progress: Progress::new(opts, 0),
+            }),
+            failures: Mutex::default(),
+            rejected: Mutex::default(),
+            mapped: false,
+        }
+    }
+
+    /// The gate for another input of the same solve, which scans as if it
+    /// followed this one: quotas, progress and a failure that ends the run
+    /// are shared, while each input keeps its own failed blocks and
+    /// rejected row, at offsets of its own.
+    pub(crate) fn another(&self) -> Self {
+        Self {
+            opts: self.opts,
+            run: self.run.clone(),
+            failures: Mutex::default(),
+            rejected: Mutex::default(),
+            mapped: false,
+        }
+    }
+
+    /// Sets the input's size for progress reports, which is unknown (0)
+    /// otherwise. With several inputs, their total size, before
+    /// [`Self::another`] is called.
+    pub(crate) fn input_len(mut self, len: u64) -> Self {
+        Arc::get_mut(&mut self.run)
+            .expect("input_len is set before the gate is shared")
+            .progress = Progress::new(self.opts, len);
+        self
+    }
+
+    /// Counts a block of `bytes` as scanned, for progress reports.
+    pub(crate) fn scanned(&self, bytes: usize) {
+        if let Some(progress) = &self.run.progress {
+            progress.add(bytes as u64);
+        }
+    }
+
+    /// Marks the input as mapped from a file, whose scanned blocks may be
+    /// released if the options ask for it. Heap data never is.
+    pub(crate) fn mapped(mut self, mapped: bool) -> Self {
+        self.mapped = mapped;
+        self
+    }
+
+    /// Whether the input is mapped from a file.
+    pub(crate) fn is_mapped(&self) -> bool {
+        self.mapped
+    }
+
+    /// Whether workers release each block's pages once it is scanned.
+    pub(crate) fn releases_pages(&self) -> bool {
+        self.mapped && self.opts.releases_pages()
+    }
+
+    pub(crate) fn opts(&self) -> &SolveOptions {
+        self.opts
+    }
+
+    /// Whether this row for `station` goes into the table.
+    #[inline]
+    pub(crate) fn row(&self, station: &[u8]) -> bool {
+        let Some(q) = &self.run.quota else {
+            return self.opts.includes(station);
+        };
+        let Some(count) = q.counts.get(station) else {
+            return false;
+        };
+        let seen = count.fetch_add(1, Ordering::Relaxed);
+        if seen + 1 == q.max {
+            q.open.fetch_sub(1, Ordering::Relaxed);
+        }
+        seen < q.max
+    }
+
+    /// Hasher for the workers' tables. aHash is already keyed with random
+    /// per-process keys, which is what untrusted input needs; a fixed seed
+    /// only replaces those keys to make the layout reproducible.
+    pub(crate) fn hasher(&self) -> RandomState {
+        match self.opts.seed() {
+            Some(seed) if !self.opts.is_untrusted() => {
+                RandomState::with_seeds(seed, !seed, seed.rotate_left(32), seed.swap_bytes())
+            }
+            _ => RandomState::new(),
+        }
+    }
+
+    /// True once every quota is met, or a block failed under
+    /// [`PanicPolicy::Fail`], or a row under [`Validation::Strict`], and
+    /// scanning further is pointless.
+    pub(crate) fn is_done(&self) -> bool {
+        self.run.failed.load(Ordering::Relaxed)
+            || self
+                .run
+                .quota
+                .as_ref()
+                .is_some_and(|q| q.open.load(Ordering::Relaxed) == 0)
+    }
+
+    /// Records a block whose scan failed. Under [`PanicPolicy::Fail`] the
+    /// run is over: every worker stops within one block.
+    pub(crate) fn fail(&self, err: ChunkError) {
+        self.failures.lock().unwrap().push(err);
+        if self.opts.panic_policy() == PanicPolicy::Fail {
+            self.run.failed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Records a malformed row starting at `byte_offset` of the scanned
+    /// buffer. Under [`Validation::Strict`] that ends the run like a failed
+    /// block does; the earliest row found is the one reported.
+    pub(crate) fn reject(&self, byte_offset: usize, reason: &'static str) {
+        let mut rejected = self.rejected.lock().unwrap();
+        if rejected
+            .as_ref()
+            .is_none_or(|r| byte_offset < r.byte_offset)
+        {
+            *rejected = Some(ParseError {
+                line: 0,
+                byte_offset,
+                reason,
+            });
+        }
+        self.run.failed.store(true, Ordering::Relaxed);
+    }
+
+    /// Counts malformed rows left out under [`Validation::Lenient`].
+    pub(crate) fn skip_rows(&self, n: u64) {
+        if n > 0 {
+            self.run.skipped_rows.fetch_add(n, Ordering::Relaxed);
+        }
+    }
+
+    /// Numbers the line of a row rejected while scanning `buf`, a buffer
+    /// that starts `base` bytes and `lines` lines into the input, and makes
+    /// its offset relative to the input.
+    pub(crate) fn locate_rejection(&self, buf: &[u8], base: usize, lines: u64) {
+        if let Some(r) = self.rejected.lock().unwrap().as_mut()
+            && r.line == 0
+        {
+            let before = buf[..r.byte_offset].iter().filter(|&&b| b == b'\n').count();
+            r.line = lines + before as u64 + 1;
+            r.byte_offset += base;
+        }
+    }
+
+    pub(crate) fn failures(&self) -> usize {
+        self.failures.lock().unwrap().len()
+    }
+
+    /// Moves the failures recorded after the first `since` on by `base`
+    /// bytes, for scans of a buffer that starts `base` bytes into the input.
+    pub(crate) fn rebase_failures(&self, since: usize, base: usize) {
+        for e in &mut self.failures.lock().unwrap()[since..] {
+            e.range = e.range.start + base..e.range.end + base;
+        }
+    }
+
+    /// Settles the failed blocks and malformed rows once the scan is over:
+    /// a rejected row, or else the first failed block, is the solve's
+    /// error. Under [`PanicPolicy::Skip`] each failed block becomes a
+    /// warning, and so does the count of rows [`Validation::Lenient`] left
+    /// out. With several inputs, each is finished in turn, and the last
+    /// counts the rows left out of all of them.
+    pub(crate) fn finish(self) -> Result<(), SolveError> {
+        if let Some(r) = self.rejected.into_inner().unwrap() {
+            return Err(r.into());
+        }
+        if self.opts.validation() == Validation::Lenient
+            && let Some(run) = Arc::into_inner(self.run)
+        {
+            let count = run.skipped_rows.into_inner();
+            if count > 0 {
+                self.opts.warn(Warning::SkippedRows { count });
+            }
+        }
+        let mut failures = self.failures.into_inner().unwrap();
+        failures.sort_unstable_by_key(|e| e.range.start);
+        match self.opts.panic_policy() {
+            PanicPolicy::Fail => failures
+                .into_iter()
+                .next()
+                .map_or(Ok(()), |e| Err(e.into())),
+            PanicPolicy::Skip => {
+                for e in failures {
+                    self.opts.warn(Warning::SkippedChunk(e));
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Arrow IPC files (Feather v2) with a `station` string column and a
+//! `temp` column in degrees, read with the `arrow` feature.
+//!
+//! The file is decoded a record batch at a time and each batch is
+//! aggregated straight from its column buffers. A dictionary-encoded
+//! station column, the usual layout for a few stations over many rows, is
+//! aggregated per dictionary key: names are only hashed once per batch,
+//! when the keys' aggregates are merged by name.
+
+use super::{Admit, Aggregator, Kept, into_stats, to_tenths};
+use ahash::AHashMap;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float32Type, Float64Type};
+use arrow_array::{Array, RecordBatch};
+use arrow_ipc::reader::FileReader;
+use findlib::{SolveError, SolveOptions, StationStats, Timings};
+use std::borrow::Cow;
+use std::io::{self, Cursor};
+use std::time::Instant;
+
+/// Aggregates `data`, which holds an Arrow IPC file.
+pub(crate) fn solve_stats(
+    data: &[u8],
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
+    #[cfg(feature = "regex")]
+    if opts.extractor().is_some() {
+        return Err(SolveError::Unsupported(
+            "an extractor reads text rows, not arrow columns".into(),
+        ));
+    }
+    if opts.preview().is_some() || opts.chunks().is_some() {
+        return Err(SolveError::Unsupported(
+            "arrow input is not scanned in chunks, so it has no previews or chunk reports".into(),
+        ));
+    }
+    let admit = Admit::new(opts).input_len(data.len() as u64);
+    let kept = Kept::of(opts);
+    let scan_start = Instant::now();
+    let reader = FileReader::try_new(Cursor::new(data), None).map_err(malformed)?;
+    // Progress counts a batch as an equal share of the file.
+    let share = data.len() / reader.num_batches().max(1);
+    let mut res: AHashMap<String, Aggregator> = AHashMap::new();
+    let mut rows = 0;
+    for batch in reader {
+        let batch = batch.map_err(malformed)?;
+        scan_batch(&batch, rows, &admit, kept, &mut res)?;
+        rows += batch.num_rows() as u64;
+        admit.scanned(share);
+        if admit.is_done() {
+            break;
+        }
+    }
+    admit.finish()?;
+    let merge_start = Instant::now();
+    let stats = into_stats(res.into_values().collect(), opts);
+    if let Some(sink) = opts.timings() {
+        (sink.0)(&Timings {
+            scan: merge_start - scan_start,
+            merge: merge_start.elapsed(),
+            ..Timings::default()
+        });
+    }
+    Ok(stats)
+}
+
+// Adds the rows of `batch`, the first of which is row `first` of the
+// file, to `res`. Rows with a null station or temp are skipped, like text
+// lines without a value.
+fn scan_batch(
+    batch: &RecordBatch,
+    first: u64,
+    admit: &Admit,
+    kept: Kept,
+    res: &mut AHashMap<String, Aggregator>,
+) -> Result<(), SolveError> {
+    let column = |name: &str| {
+        batch
+            .column_by_name(name)
+            .ok_or_else(|| invalid(format!("arrow input has no `{name}` column")))
+    };
+    let (station, temp) = (column("station")?, column("temp")?);
+    let temps = degrees(temp)?;
+    let valid = |i: usize| station.is_valid(i) && temp.is_valid(i);
+    let add = |agg: &mut Aggregator, i: usize| {
+        agg.add(to_tenths(temps[i]), first + i as u64);
+    };
+
+    if let Some(dict) = station.as_any_dictionary_opt() {
+        let names = strings(dict.values().as_ref())?;
+        if names.is_empty() {
+            return Ok(());
+        }
+        let mut keys: Vec<Option<Aggregator>> = names.iter().map(|_| None).collect();
+        for (i, key) in dict.normalized_keys().into_iter().enumerate() {
+            if valid(i) && admit.row(names[key]) {
+                add(keys[key].get_or_insert_with(|| Aggregator::new(kept)), i);
+            }
+        }
+        // A dictionary may hold a name more than once; `merge` joins them.
+        for (key, agg) in keys.into_iter().enumerate() {
+            if let Some(agg) = agg {
+                merge(res, names[key], agg);
+            }
+        }
+    } else {
+        let names = strings(station.as_ref())?;
+        let mut table: AHashMap<&[u8], Aggregator> = AHashMap::with_hasher(admit.hasher());
+        for (i, &name) in names.iter().enumerate() {
+            if valid(i) && admit.row(name) {
+                add(
+                    table.entry(name).or_insert_with(|| Aggregator::new(kept)),
+                    i,
+                );
+            }
+        }
+        for (name, agg) in table {
+            merge(res, name, agg);
+        }
+    }
+    Ok(())
+}
+
+fn merge(res: &mut AHashMap<String, Aggregator>, name: &[u8], agg: Aggregator) {
+    // Arrow strings are UTF-8 by the format's own rules.
+    let name = String::from_utf8_lossy(name);
+    match res.get_mut(name.as_ref()) {
+        Some(mine) => mine.merge(&agg),
+        None => {
+            let name = name.into_owned();
+            res.insert(name.clone(), Aggregator { name, ..agg });
+        }
+    }
+}
+
+// The values of a string column, nulls as empty names.
+fn strings(array: &dyn Array) -> Result<Vec<&[u8]>, SolveError> {
+    fn bytes(s: Option<&str>) -> &[u8] {
+        s.unwrap_or_default().as_bytes()
+    }
+    if let Some(a) = array.as_string_opt::<i32>() {
+        Ok(a.iter().map(bytes).collect())
+    } else if let Some(a) = array.as_string_opt::<i64>() {
+        Ok(a.iter().map(bytes).collect())
+    } else if let Some(a) = array.as_string_view_opt() {
+        Ok(a.iter().map(bytes).collect())
+    } else {
+        Err(invalid(format!(
+            "arrow `station` column is {}, not strings",
+            array.data_type()
+        )))
+    }
+}
+
+// The values of the `temp` column, borrowed from a float64 column.
+fn degrees(array: &dyn Array) -> Result<Cow<'_, [f64]>, SolveError> {
+    if let Some(a) = array.as_primitive_opt::<Float64Type>() {
+        Ok(Cow::Borrowed(a.values()))
+    } else if let Some(a) = array.as_primitive_opt::<Float32Type>() {
+        Ok(Cow::Owned(a.values().iter().map(|&t| t as f64).collect()))
+    } else {
+        Err(invalid(format!(
+            "arrow `temp` column is {}, not floats",
+            array.data_type()
+        )))
+    }
+}
+
+fn invalid(what: String) -> SolveError {
+    io::Error::new(io::ErrorKind::InvalidData, what).into()
+}
+
+fn malformed(e: impl std::fmt::Display) -> SolveError {
+    invalid(format!("reading arrow input: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{ArrayRef, DictionaryArray, Float64Array, Int32Array, StringArray};
+    use arrow_ipc::writer::FileWriter;
+    use findlib::InputFormat;
+    use std::sync::Arc;
+
+    fn file(batches: &[(ArrayRef, ArrayRef)]) -> Vec<u8> {
+        let first = RecordBatch::try_from_iter([
+            ("station", batches[0].0.clone()),
+            ("temp", batches[0].1.clone()),
+        ])
+        .unwrap();
+        let mut out = Vec::new();
+        let mut writer = FileWriter::try_new(&mut out, &first.schema()).unwrap();
+        for (station, temp) in batches {
+            let batch =
+                RecordBatch::try_from_iter([("station", station.clone()), ("temp", temp.clone())]);
+            writer.write(&batch.unwrap()).unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+        out
+    }
+
+    fn solve(data: &[u8], opts: SolveOptions) -> Result<String, SolveError> {
+        let opts = opts.input_format(InputFormat::Arrow);
+        crate::solve_stats_bytes_with(data, &opts).map(|s| findlib::format_stats(&s))
+    }
+
+    #[test]
+    fn test_strings() {
+        let station: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("Oslo"),
+            Some("Rome"),
+            None,
+            Some("Oslo"),
+        ]));
+        let temp: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(1.0),
+            Some(-4.5),
+            Some(9.9),
+            None,
+        ]));
+        let more: ArrayRef = Arc::new(StringArray::from(vec!["Oslo"]));
+        let data = file(&[
+            (station, temp),
+            (more, Arc::new(Float64Array::from(vec![2.0]))),
+        ]);
+        assert_eq!(
+            "{Oslo=1.0/1.5/2.0, Rome=-4.5/-4.5/-4.5}\n",
+            solve(&data, SolveOptions::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dictionary() {
+        // "Oslo" twice in the dictionary: both keys count as one station.
+        let keys = Int32Array::from(vec![0, 1, 2, 2]);
+        let values = Arc::new(StringArray::from(vec!["Oslo", "Rome", "Oslo"]));
+        let station: ArrayRef = Arc::new(DictionaryArray::new(keys, values));
+        let temp: ArrayRef = Arc::new(Float64Array::from(vec![1.0, -4.5, 2.0, 3.0]));
+        let data = file(&[(station, temp)]);
+        assert_eq!(
+            "{Oslo=1.0/2.0/3.0, Rome=-4.5/-4.5/-4.5}\n",
+            solve(&data, SolveOptions::default()).unwrap()
+        );
+        let quota = SolveOptions::default()
+            .include_stations(["Oslo"])
+            .max_rows_per_station(2);
+        assert_eq!("{Oslo=1.0/1.5/2.0}\n", solve(&data, quota).unwrap());
+    }
+
+    #[test]
+    fn test_missing_column() {
+        let batch = RecordBatch::try_from_iter([(
+            "name",
+            Arc::new(StringArray::from(vec!["Oslo"])) as ArrayRef,
+        )])
+        .unwrap();
+        let mut data = Vec::new();
+        let mut writer = FileWriter::try_new(&mut data, &batch.schema()).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+        let err = solve(&data, SolveOptions::default()).unwrap_err();
+        assert!(err.is_malformed());
+        assert_eq!("arrow input has no `station` column", err.to_string());
+        assert!(solve(b"Oslo;1.0\n", SolveOptions::default()).is_err());
+    }
+}
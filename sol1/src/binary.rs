@@ -0,0 +1,104 @@
+//! Length-prefixed binary rows, for benchmarks that want to time the tables
+//! without text parsing.
+//!
+//! A row is a `u8` name length, the name bytes and the value in tenths as a
+//! little-endian `i16`. Rows never straddle a [`FRAME`] boundary: a zero
+//! length pads to the next one. Any frame boundary is therefore a row start,
+//! and workers can split the input the way they split text at newlines.
+
+use super::{Admit, Table};
+use std::io::{self, Write};
+use std::ops::Range;
+
+pub const FRAME: usize = 1 << 16;
+
+/// Frame-aligned split of `data` into about `workers` ranges.
+pub(crate) fn chunk_by_frames(data: &[u8], workers: usize) -> Vec<Range<usize>> {
+    let size = (data.len() / workers.max(1))
+        .next_multiple_of(FRAME)
+        .max(FRAME);
+    (0..data.len())
+        .step_by(size)
+        .map(|s| s..(s + size).min(data.len()))
+        .collect()
+}
+
+/// The rows in `range`, which must start at a row (or frame) boundary.
+pub(crate) fn rows(buffer: &[u8], range: Range<usize>) -> impl Iterator<Item = (&[u8], i16)> {
+    let mut pos = range.start;
+    std::iter::from_fn(move || {
+        loop {
+            if pos >= range.end {
+                return None;
+            }
+            let len = buffer[pos] as usize;
+            if len == 0 {
+                pos = (pos + 1).next_multiple_of(FRAME);
+                continue;
+            }
+            let end = pos + 1 + len + 2;
+            // A truncated last row is dropped, like a line without a value.
+            if end > range.end {
+                pos = range.end;
+                return None;
+            }
+            let station = &buffer[pos + 1..pos + 1 + len];
+            let val = i16::from_le_bytes([buffer[end - 2], buffer[end - 1]]);
+            pos = end;
+            return Some((station, val));
+        }
+    })
+}
+
+pub(crate) fn scan_binary_into<'a>(
+    res: &mut Table<'a>,
+    range: Range<usize>,
+    buffer: &'a [u8],
+    admit: &Admit,
+) {
+    for (station, val) in rows(buffer, range) {
+        if admit.row(station) {
+            res.record(station, val as i32);
+        }
+    }
+}
+
+/// Why `name` and `val` cannot be written as a binary row, if they cannot.
+pub(crate) fn reject(name: &[u8], val: i32) -> Option<&'static str> {
+    if name.is_empty() {
+        Some("empty name")
+    } else if name.len() > u8::MAX as usize {
+        Some("name too long")
+    } else if i16::try_from(val).is_err() {
+        Some("value out of range")
+    } else {
+        None
+    }
+}
+
+/// Tracks the position in the current frame while writing binary rows.
+#[derive(Default)]
+pub(crate) struct Writer {
+    written: usize,
+}
+
+impl Writer {
+    /// Writes a row that passed [`reject`], padding to the next frame first
+    /// if it would not fit in this one.
+    pub(crate) fn write(&mut self, out: &mut impl Write, name: &[u8], val: i32) -> io::Result<()> {
+        const PADDING: [u8; 3 + u8::MAX as usize] = [0; 3 + u8::MAX as usize];
+
+        let size = 1 + name.len() + 2;
+        let left = FRAME - self.written % FRAME;
+        if size > left {
+            // `left < size`, so it fits in `PADDING`.
+            out.write_all(&PADDING[..left])?;
+            self.written += left;
+        }
+        out.write_all(&[name.len() as u8])?;
+        out.write_all(name)?;
+        out.write_all(&(val as i16).to_le_bytes())?;
+        self.written += size;
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+//! Per-station aggregates over time buckets (see [`findlib::Buckets`]).
+//! The table is keyed by station and bucket start instead of station alone.
+//! Every row is checked, since its timestamp has to be parsed anyway: the
+//! first malformed row fails the solve, as under `Validation::Strict`,
+//! unless `Validation::Lenient` leaves such rows out.
+
+use super::{Aggregator, Kept, NEWLINE, SMALL_INPUT, chunk_by_newlines, open_input, spawn_scoped};
+use ahash::AHashMap;
+use findlib::{
+    BucketStats, Buckets, Compression, InputFormat, ParseError, Progress, SolveError, SolveOptions,
+    StationStats, Validation, Warning, parse_bucketed_row,
+};
+use std::fs::File;
+use std::io::Read;
+use std::ops::Range;
+
+// One worker's rows.
+#[derive(Default)]
+struct Part<'a> {
+    table: AHashMap<(&'a [u8], i64), Aggregator>,
+    skipped: u64,
+    // Where the first malformed row starts, and why; it stops the worker.
+    rejected: Option<(usize, &'static str)>,
+}
+
+/// Aggregates `filename` per station and per bucket of `buckets`, sorted by
+/// station and then bucket start.
+pub fn solve_buckets(
+    filename: String,
+    opts: &SolveOptions,
+    buckets: Buckets,
+) -> Result<Vec<BucketStats>, SolveError> {
+    let mut file = File::open(&filename)?;
+    let meta = file.metadata()?;
+    if !meta.is_file() {
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        return solve_buckets_bytes(&buf, opts, buckets);
+    }
+    let data = open_input(file, meta.len())?;
+    solve_buckets_bytes(&data, opts, buckets)
+}
+
+/// Like [`solve_buckets`], for a caller-provided buffer.
+pub fn solve_buckets_bytes(
+    data: &[u8],
+    opts: &SolveOptions,
+    buckets: Buckets,
+) -> Result<Vec<BucketStats>, SolveError> {
+    check_options(data, opts, buckets)?;
+    let kept = Kept::of(opts);
+    let progress = Progress::new(opts, data.len() as u64);
+    let scan = |range: Range<usize>| {
+        let len = range.len() as u64;
+        let part = scan(data, range, opts, buckets, kept);
+        if let Some(progress) = &progress {
+            progress.add(len);
+        }
+        part
+    };
+    let parts = if data.len() < SMALL_INPUT {
+        vec![scan(0..data.len())]
+    } else {
+        std::thread::scope(|scope| {
+            // A range whose thread cannot start is scanned here.
+            let handles: Vec<_> = chunk_by_newlines(data, opts.workers())
+                .into_iter()
+                .map(|r| {
+                    let range = r.clone();
+                    spawn_scoped(scope, || scan(range)).map_err(|_| r)
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| match h {
+                    Ok(h) => h.join().unwrap(),
+                    Err(r) => scan(r),
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // Ranges are in input order, so the first rejection is the earliest.
+    if let Some((at, reason)) = parts.iter().find_map(|p| p.rejected) {
+        let before = data[..at].iter().filter(|&&b| b == NEWLINE).count();
+        return Err(SolveError::Parse(ParseError {
+            line: before as u64 + 1,
+            byte_offset: at,
+            reason,
+        }));
+    }
+    let skipped: u64 = parts.iter().map(|p| p.skipped).sum();
+    if skipped > 0 {
+        opts.warn(Warning::SkippedRows { count: skipped });
+    }
+    let mut parts = parts.into_iter();
+    let mut all = parts.next().map(|p| p.table).unwrap_or_default();
+    for part in parts {
+        for (key, agg) in part.table {
+            match all.get_mut(&key) {
+                Some(mine) => mine.merge(&agg),
+                None => {
+                    all.insert(key, agg);
+                }
+            }
+        }
+    }
+
+    let mut res: Vec<BucketStats> = all
+        .into_iter()
+        .map(|((name, start), v)| BucketStats {
+            start,
+            stats: StationStats {
+                // Checked to be UTF-8 when parsed.
+                name: String::from_utf8_lossy(name).into_owned(),
+                min: v.min,
+                max: v.max,
+                sum: v.sum,
+                count: v.count,
+                dist: v.dist,
+                last: v.last,
+            },
+        })
+        .collect();
+    res.sort_unstable_by(|a, b| (&a.stats.name, a.start).cmp(&(&b.stats.name, b.start)));
+    Ok(res)
+}
+
+fn check_options(data: &[u8], opts: &SolveOptions, buckets: Buckets) -> Result<(), SolveError> {
+    let columns = opts.columns();
+    if buckets.column < 2 || buckets.column == columns.value {
+        return Err(SolveError::Unsupported(format!(
+            "the timestamp column must be past the station and not the value ({})",
+            columns.value
+        )));
+    }
+    if opts.format() != InputFormat::Text {
+        return Err(SolveError::Unsupported(format!(
+            "buckets only apply to text rows, not {}",
+            opts.format()
+        )));
+    }
+    if let Some(kind) = Compression::detect(data) {
+        return Err(SolveError::Unsupported(format!(
+            "buckets do not read {kind} input"
+        )));
+    }
+    #[cfg(feature = "regex")]
+    if opts.extractor().is_some() {
+        return Err(SolveError::Unsupported(
+            "an extractor does not find timestamps".into(),
+        ));
+    }
+    if opts.max_rows().is_some() || opts.preview().is_some() {
+        return Err(SolveError::Unsupported(
+            "buckets support neither quotas nor previews".into(),
+        ));
+    }
+    Ok(())
+}
+
+// The rows starting in `range`, which starts at a row.
+fn scan<'a>(
+    data: &'a [u8],
+    range: Range<usize>,
+    opts: &SolveOptions,
+    buckets: Buckets,
+    kept: Kept,
+) -> Part<'a> {
+    let mut part = Part::default();
+    let strict = opts.validation() != Validation::Lenient;
+    let mut at = range.start;
+    for line in data[range].split(|&b| b == NEWLINE) {
+        let start = at;
+        at += line.len() + 1;
+        if matches!(line, b"" | b"\r") {
+            continue;
+        }
+        match parse_bucketed_row(line, opts.columns(), buckets.column) {
+            Ok((name, val, time)) => {
+                if !opts.includes(name) {
+                    continue;
+                }
+                part.table
+                    .entry((name, buckets.start_of(time)))
+                    .or_insert_with(|| Aggregator::new(kept))
+                    .add(val, start as u64);
+            }
+            Err(reason) if strict => {
+                part.rejected = Some((start, reason));
+                break;
+            }
+            Err(_) => part.skipped += 1,
+        }
+    }
+    part
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use findlib::{Columns, format_timestamp};
+
+    const HOURLY: Buckets = Buckets {
+        column: 3,
+        width: 3600,
+    };
+
+    fn rows(got: &[BucketStats]) -> Vec<(String, String, i32, i32, i64, u64)> {
+        got.iter()
+            .map(|b| {
+                let s = &b.stats;
+                (
+                    s.name.clone(),
+                    format_timestamp(b.start),
+                    s.min,
+                    s.max,
+                    s.sum,
+                    s.count,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_buckets() {
+        let data = b"Rome;1.0;2024-03-01T13:59:59Z\n\
+            Oslo;-2.0;2024-03-01T13:00:00Z\n\
+            Oslo;4.0;1709301600\n\
+            Oslo;3.0;2024-03-01T13:30:00Z\r\n\
+            \n\
+            Oslo;5.0;2024-03-01 14:10";
+        let want = vec![
+            ("Oslo".into(), "2024-03-01T13:00:00Z".into(), -20, 30, 10, 2),
+            ("Oslo".into(), "2024-03-01T14:00:00Z".into(), 40, 50, 90, 2),
+            ("Rome".into(), "2024-03-01T13:00:00Z".into(), 10, 10, 10, 1),
+        ];
+        let opts = SolveOptions::default();
+        assert_eq!(
+            want,
+            rows(&solve_buckets_bytes(data, &opts, HOURLY).unwrap())
+        );
+
+        // The same rows split over threads.
+        let mut big = Vec::new();
+        while big.len() < SMALL_INPUT {
+            big.extend_from_slice(data);
+            big.push(NEWLINE);
+        }
+        let reps = big.len() as u64 / (data.len() as u64 + 1);
+        let got = solve_buckets_bytes(&big, &opts.clone().threads(4), HOURLY).unwrap();
+        let counts: Vec<u64> = got.iter().map(|b| b.stats.count).collect();
+        assert_eq!(vec![2 * reps, 2 * reps, reps], counts);
+
+        let daily = Buckets {
+            width: 86_400,
+            ..HOURLY
+        };
+        let got = solve_buckets_bytes(data, &opts.clone().include_stations(["Oslo"]), daily);
+        assert_eq!(
+            vec![(
+                "Oslo".into(),
+                "2024-03-01T00:00:00Z".into(),
+                -20,
+                50,
+                100,
+                4
+            )],
+            rows(&got.unwrap())
+        );
+    }
+
+    #[test]
+    fn test_bucket_columns_and_rejections() {
+        let tsv = Columns {
+            delimiter: b'\t',
+            value: 3,
+        };
+        let opts = SolveOptions::default().text_columns(tsv);
+        let by_column_2 = Buckets {
+            column: 2,
+            ..HOURLY
+        };
+        let data = b"Oslo\t0\t1.0\nOslo\tnoon\t2.0\nOslo\t7200\t3.0\n";
+        let warnings = findlib::Warnings::default();
+        let lenient = opts
+            .clone()
+            .validate(Validation::Lenient)
+            .collect_warnings(&warnings);
+        let got = solve_buckets_bytes(data, &lenient, by_column_2).unwrap();
+        assert_eq!(
+            vec![0, 7200],
+            got.iter().map(|b| b.start).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![Warning::SkippedRows { count: 1 }], warnings.take());
+
+        // Rows are checked even when the input is trusted.
+        let err = solve_buckets_bytes(data, &opts, by_column_2).unwrap_err();
+        assert_eq!(
+            "line 2 (byte 11): timestamp is not epoch seconds or ISO 8601",
+            err.to_string()
+        );
+
+        let on_value = Buckets {
+            column: 3,
+            ..HOURLY
+        };
+        assert!(matches!(
+            solve_buckets_bytes(data, &opts, on_value),
+            Err(SolveError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,114 @@
+use super::{Admit, Table, scan_block};
+use findlib::{ChunkError, ChunkStats, ChunksSink, PanicPolicy};
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Collects [`ChunkStats`] for [`findlib::SolveOptions::report_chunks`].
+/// Each block is scanned into a table of its own and then folded into the
+/// worker's, so its rows and stations can be counted without a counter in
+/// the row loop of ordinary runs.
+#[derive(Default)]
+pub(crate) struct ChunkLog {
+    chunks: Mutex<Vec<ChunkStats>>,
+}
+
+impl ChunkLog {
+    pub(crate) fn len(&self) -> usize {
+        self.chunks.lock().unwrap().len()
+    }
+
+    /// Moves the blocks logged after the first `since` on by `base` bytes,
+    /// for scans of a buffer that starts `base` bytes into the input.
+    pub(crate) fn rebase(&self, since: usize, base: usize) {
+        for c in &mut self.chunks.lock().unwrap()[since..] {
+            c.range = c.range.start + base..c.range.end + base;
+        }
+    }
+
+    /// Adds the blocks `other` logged for an input that starts `base` bytes
+    /// into the inputs read one after another.
+    pub(crate) fn append(&self, other: ChunkLog, base: usize) {
+        let since = self.len();
+        let mut theirs = other.chunks.into_inner().unwrap();
+        self.chunks.lock().unwrap().append(&mut theirs);
+        self.rebase(since, base);
+    }
+
+    /// Hands every block to `sink`, ordered by offset.
+    pub(crate) fn report(self, sink: &ChunksSink) {
+        let mut chunks = self.chunks.into_inner().unwrap();
+        chunks.sort_unstable_by_key(|c| c.range.start);
+        (sink.0)(&chunks);
+    }
+}
+
+/// [`scan_block`], recording the block in `log` if there is one. A failed
+/// scan, or a panic in it, is handed to [`Admit::fail`] with the block's
+/// range.
+pub(crate) fn scan_logged<'a>(
+    log: Option<&ChunkLog>,
+    res: &mut Table<'a>,
+    range: Range<usize>,
+    data: &'a [u8],
+    admit: &Admit,
+    worker: usize,
+) {
+    // A skipped block must leave nothing behind in `res`, so like a logged
+    // one it gets a table of its own.
+    let skip = admit.opts().panic_policy() == PanicPolicy::Skip;
+    if log.is_none() && !skip {
+        contained(admit, &range, || {
+            scan_block(res, range.clone(), data, admit)
+        });
+        return;
+    }
+    let start = Instant::now();
+    let mut block = res.empty_like();
+    if !contained(admit, &range, || {
+        scan_block(&mut block, range.clone(), data, admit)
+    }) {
+        return;
+    }
+    let counts = log.map(|_| block.rows_and_keys());
+    res.absorb(block);
+    let elapsed = start.elapsed();
+    if let (Some(log), Some((rows, keys))) = (log, counts) {
+        log.chunks.lock().unwrap().push(ChunkStats {
+            range,
+            worker,
+            rows,
+            keys,
+            elapsed,
+        });
+    }
+}
+
+// Runs `scan`, turning its error or a panic into a failure of `range`, and
+// counts the range as scanned either way. Returns whether it finished.
+fn contained(
+    admit: &Admit,
+    range: &Range<usize>,
+    scan: impl FnOnce() -> Result<(), String>,
+) -> bool {
+    let scanned = panic::catch_unwind(AssertUnwindSafe(scan));
+    admit.scanned(range.len());
+    let message = match scanned {
+        Ok(Ok(())) => return true,
+        Ok(Err(message)) => message,
+        Err(payload) => match (
+            payload.downcast_ref::<&str>(),
+            payload.downcast_ref::<String>(),
+        ) {
+            (Some(s), _) => s.to_string(),
+            (_, Some(s)) => s.clone(),
+            _ => "unknown panic".to_string(),
+        },
+    };
+    admit.fail(ChunkError {
+        range: range.clone(),
+        message,
+    });
+    false
+}
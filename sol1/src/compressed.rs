@@ -0,0 +1,215 @@
+//! Compressed input, decompressed on the fly into the streaming scan (see
+//! `stream`) instead of into a temporary file. gzip needs the `flate2`
+//! feature and zstd the `zstd` feature.
+//!
+//! A zstd file of several frames is decompressed a batch of frames at a
+//! time, one thread per frame. gzip members cannot be found without
+//! inflating everything before them, so gzip, like a single zstd frame, is
+//! decompressed by the stream's reader thread while the workers scan.
+
+use super::Slots;
+use findlib::{Compression, SolveError, SolveOptions, StationStats};
+
+/// Aggregates `data`, which holds `kind`-compressed input.
+#[cfg_attr(
+    not(all(feature = "flate2", feature = "zstd")),
+    allow(unused_variables)
+)]
+pub(crate) fn solve_stats(
+    data: &[u8],
+    kind: Compression,
+    opts: &SolveOptions,
+    slots: Option<&Slots>,
+) -> Result<Vec<StationStats>, SolveError> {
+    match kind {
+        #[cfg(feature = "flate2")]
+        Compression::Gzip => {
+            super::stream::solve_stats(flate2::read::MultiGzDecoder::new(data), opts, slots)
+        }
+        #[cfg(not(feature = "flate2"))]
+        Compression::Gzip => Err(SolveError::Unsupported(
+            "gzip-compressed input needs sol1's `flate2` feature".into(),
+        )),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => zstd_frames::solve_stats(data, opts, slots),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => Err(SolveError::Unsupported(
+            "zstd-compressed input needs sol1's `zstd` feature".into(),
+        )),
+    }
+}
+
+#[cfg(feature = "zstd")]
+mod zstd_frames {
+    use super::super::{Slots, spawn_scoped, stream};
+    use findlib::{SolveError, SolveOptions, StationStats};
+    use std::io::{self, Read};
+    use std::ops::Range;
+    use std::sync::mpsc;
+    use zstd::zstd_safe;
+
+    pub(crate) fn solve_stats(
+        data: &[u8],
+        opts: &SolveOptions,
+        slots: Option<&Slots>,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        let frames = frames(data)?;
+        let workers = opts.workers();
+        if frames.len() < 2 || workers < 2 {
+            return stream::solve_stats(zstd::Decoder::with_buffer(data)?, opts, slots);
+        }
+        std::thread::scope(|scope| {
+            // A batch in the channel while the next one is decompressed.
+            let (tx, rx) = mpsc::sync_channel(workers);
+            let decoding = spawn_scoped(scope, || decode(data, &frames, workers, tx));
+            match decoding {
+                Ok(_) => stream::solve_stats(
+                    Frames {
+                        rx,
+                        left: frames.len(),
+                        buf: Vec::new(),
+                        pos: 0,
+                    },
+                    opts,
+                    slots,
+                ),
+                Err(_) => stream::solve_stats(zstd::Decoder::with_buffer(data)?, opts, slots),
+            }
+        })
+    }
+
+    // Where each frame of `data` lies, from the frame headers alone.
+    fn frames(data: &[u8]) -> io::Result<Vec<Range<usize>>> {
+        let mut frames = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let len = zstd_safe::find_frame_compressed_size(&data[pos..]).map_err(|code| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "zstd frame at byte {pos}: {}",
+                        zstd_safe::get_error_name(code)
+                    ),
+                )
+            })?;
+            frames.push(pos..pos + len);
+            pos += len;
+        }
+        Ok(frames)
+    }
+
+    // Decompresses `workers` frames at a time and sends them on in order,
+    // stopping at the first error or once the reader is gone.
+    fn decode(
+        data: &[u8],
+        frames: &[Range<usize>],
+        workers: usize,
+        tx: mpsc::SyncSender<io::Result<Vec<u8>>>,
+    ) {
+        let one = |r: &Range<usize>| zstd::decode_all(&data[r.clone()]);
+        for batch in frames.chunks(workers) {
+            let out: Vec<io::Result<Vec<u8>>> = std::thread::scope(|scope| {
+                // A frame whose thread cannot start is decompressed here.
+                let handles: Vec<_> = batch
+                    .iter()
+                    .map(|r| spawn_scoped(scope, move || one(r)).map_err(|_| r))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|h| match h {
+                        Ok(h) => h.join().unwrap(),
+                        Err(r) => one(r),
+                    })
+                    .collect()
+            });
+            for frame in out {
+                let failed = frame.is_err();
+                if tx.send(frame).is_err() || failed {
+                    return;
+                }
+            }
+        }
+    }
+
+    // The decompressed frames as one stream.
+    struct Frames {
+        rx: mpsc::Receiver<io::Result<Vec<u8>>>,
+        left: usize,
+        buf: Vec<u8>,
+        pos: usize,
+    }
+
+    impl Read for Frames {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            while self.pos == self.buf.len() {
+                if self.left == 0 {
+                    return Ok(0);
+                }
+                self.buf = self
+                    .rx
+                    .recv()
+                    .map_err(|_| io::Error::other("zstd decompression stopped"))??;
+                self.pos = 0;
+                self.left -= 1;
+            }
+            let n = out.len().min(self.buf.len() - self.pos);
+            out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_gzip_members() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        // Two members, as `pigz` or `cat a.gz b.gz` leave them.
+        let mut data = Vec::new();
+        for part in [&findlib::SAMPLE[..1000], &findlib::SAMPLE[1000..]] {
+            let mut enc = GzEncoder::new(Vec::new(), flate2::Compression::fast());
+            enc.write_all(part).unwrap();
+            data.extend(enc.finish().unwrap());
+        }
+        let want = crate::solve_stats_bytes(findlib::SAMPLE).unwrap();
+        assert_eq!(Some(Compression::Gzip), Compression::detect(&data));
+        assert_eq!(want, crate::solve_stats_bytes(&data).unwrap());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_frames() {
+        let want = crate::solve_stats_bytes(findlib::SAMPLE).unwrap();
+        let opts = SolveOptions::default().threads(3);
+        // One frame, then frames split mid-row that decode in batches.
+        let one = zstd::encode_all(findlib::SAMPLE, 1).unwrap();
+        assert_eq!(want, crate::solve_stats_bytes_with(&one, &opts).unwrap());
+        let mut many = Vec::new();
+        for part in findlib::SAMPLE.chunks(100_003) {
+            many.extend(zstd::encode_all(part, 1).unwrap());
+        }
+        assert_eq!(want, crate::solve_stats_bytes_with(&many, &opts).unwrap());
+        // A damaged frame fails instead of cutting the input short.
+        let cut = many.len() - 10;
+        many.truncate(cut);
+        assert!(crate::solve_stats_bytes_with(&many, &opts).is_err());
+    }
+
+    #[cfg(not(feature = "flate2"))]
+    #[test]
+    fn test_feature_missing() {
+        let data = b"\x1f\x8b\x08\0\0\0";
+        assert_eq!(Some(Compression::Gzip), Compression::detect(data));
+        let err = crate::solve_stats_bytes(data).unwrap_err();
+        assert_eq!(
+            "gzip-compressed input needs sol1's `flate2` feature",
+            err.to_string()
+        );
+    }
+}
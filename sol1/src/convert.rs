@@ -0,0 +1,151 @@
+use super::{JsonRow, NEWLINE, SEMICOLON, binary, parse_digits, to_tenths};
+use findlib::{Columns, InputFormat, write_tenths};
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+// One input row: its line (or, for binary input, row) number, name and
+// value in tenths.
+type Row<'a> = (usize, Cow<'a, [u8]>, i32);
+
+/// Rewrites the rows of `data`, read as `from`, to `out` as `to`, and
+/// returns how many were written. Rows are streamed one at a time, so
+/// memory does not grow with the input. Text input is split into fields by
+/// `columns`; text output is always `name;value`.
+///
+/// Input the solver would skip (text lines with too few fields, lines that
+/// are not JSON rows) is skipped here too. Rows the solver could not read,
+/// or that `to` cannot represent, are errors naming the line.
+pub fn convert(
+    data: &[u8],
+    from: InputFormat,
+    columns: Columns,
+    to: InputFormat,
+    mut out: impl Write,
+) -> io::Result<u64> {
+    check_input(from, columns)?;
+    if to == InputFormat::Arrow {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "convert writes rows, not arrow columns",
+        ));
+    }
+    let bad = |n: usize, what: &str| malformed(from, n, what);
+
+    let mut binary = binary::Writer::default();
+    let mut text = String::new();
+    let mut written = 0;
+    for row in rows(data, from, columns) {
+        let (n, name, val) = row.map_err(|(n, what)| bad(n, what))?;
+        match to {
+            InputFormat::Binary => {
+                if let Some(what) = binary::reject(&name, val) {
+                    return Err(bad(n, what));
+                }
+                binary.write(&mut out, &name, val)?;
+            }
+            InputFormat::Text => {
+                if name.contains(&SEMICOLON) || name.contains(&NEWLINE) {
+                    return Err(bad(n, "name contains ';' or a newline"));
+                }
+                text.clear();
+                text.push(';');
+                write_tenths(&mut text, val as i64);
+                text.push('\n');
+                out.write_all(&name)?;
+                out.write_all(text.as_bytes())?;
+            }
+            InputFormat::Jsonl => {
+                text.clear();
+                text.push_str("{\"station\": ");
+                text.push_str(&serde_json::to_string(&String::from_utf8_lossy(&name))?);
+                text.push_str(", \"temp\": ");
+                write_tenths(&mut text, val as i64);
+                text.push_str("}\n");
+                out.write_all(text.as_bytes())?;
+            }
+            InputFormat::Arrow => unreachable!("rejected above"),
+        }
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Calls `each` with the name and value in tenths of every row of `data`,
+/// read as [`convert`] reads them, and returns how many there were. For
+/// writers of formats that are not rows, such as a columnar file. An error
+/// from `each` ends the walk.
+pub fn for_each_row(
+    data: &[u8],
+    from: InputFormat,
+    columns: Columns,
+    mut each: impl FnMut(&[u8], i32) -> io::Result<()>,
+) -> io::Result<u64> {
+    check_input(from, columns)?;
+    let mut seen = 0;
+    for row in rows(data, from, columns) {
+        let (_, name, val) = row.map_err(|(n, what)| malformed(from, n, what))?;
+        each(&name, val)?;
+        seen += 1;
+    }
+    Ok(seen)
+}
+
+fn check_input(from: InputFormat, columns: Columns) -> io::Result<()> {
+    if from == InputFormat::Arrow {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "convert reads rows, not arrow columns",
+        ));
+    }
+    if from != InputFormat::Text && columns != Columns::CHALLENGE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("columns only apply to text rows, not {from}"),
+        ));
+    }
+    Ok(())
+}
+
+// Row `n` of `from` input cannot be read or written, for `what` reason.
+fn malformed(from: InputFormat, n: usize, what: &str) -> io::Error {
+    let unit = if from == InputFormat::Binary {
+        "row"
+    } else {
+        "line"
+    };
+    io::Error::new(io::ErrorKind::InvalidData, format!("{unit} {n}: {what}"))
+}
+
+fn rows(
+    data: &[u8],
+    from: InputFormat,
+    columns: Columns,
+) -> Box<dyn Iterator<Item = Result<Row<'_>, (usize, &'static str)>> + '_> {
+    let lines = data.split(|&b| b == NEWLINE).enumerate();
+    match from {
+        InputFormat::Text => Box::new(lines.filter_map(move |(idx, line)| {
+            // CRLF line endings, as the scanners take them.
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            let (name, value) = columns.split(line)?;
+            match parse_digits(value) {
+                Ok(val) if !value.is_empty() => Some(Ok((idx + 1, Cow::Borrowed(name), val))),
+                _ => Some(Err((idx + 1, "malformed value"))),
+            }
+        })),
+        InputFormat::Jsonl => Box::new(lines.filter_map(|(idx, line)| {
+            let row = serde_json::from_slice::<JsonRow>(line).ok()?;
+            let name: String = serde_json::from_str(row.station.get()).ok()?;
+            Some(Ok((
+                idx + 1,
+                Cow::Owned(name.into_bytes()),
+                to_tenths(row.temp),
+            )))
+        })),
+        InputFormat::Binary => Box::new(
+            binary::rows(data, 0..data.len())
+                .enumerate()
+                .map(|(idx, (name, val))| Ok((idx + 1, Cow::Borrowed(name), val as i32))),
+        ),
+        InputFormat::Arrow => unreachable!("rejected by `check_input`"),
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{
+    Slots, SolveError, SolveOptions, StationStats, solve_stats_with, solve_stats_with_slots,
+};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+type Solved = Result<Vec<StationStats>, SolveError>;
+
+/// A solve running on a background thread, which other threads can query
+/// while it works.
+///
+/// It builds on [`SolveOptions::preview_every`]: every `interval` the merged
+/// tables are published as an immutable `Arc` slice, and
+/// [`Engine::snapshot`] hands out the latest one without touching the
+/// workers. Once the solve finishes, the snapshot is the final result.
+pub struct Engine {
+    latest: Arc<Mutex<Arc<[StationStats]>>>,
+    handle: JoinHandle<Solved>,
+}
+
+impl Engine {
+    /// Starts solving `filename` with `opts`. Snapshots are refreshed every
+    /// `interval`; a preview sink already set on `opts` keeps receiving its
+    /// calls at that same interval.
+    pub fn start(filename: String, opts: SolveOptions, interval: Duration) -> Engine {
+        Self::spawn(filename, opts, interval, None)
+    }
+
+    /// Like [`Engine::start`], sharing `slots` with other solves: engines
+    /// over shards with the same stations skip key insertion after the
+    /// first one. See [`Slots`].
+    pub fn start_with_slots(
+        filename: String,
+        opts: SolveOptions,
+        interval: Duration,
+        slots: Arc<Slots>,
+    ) -> Engine {
+        Self::spawn(filename, opts, interval, Some(slots))
+    }
+
+    /// An [`EngineBuilder`], for engines set up beyond what
+    /// [`Engine::start`] takes, e.g. with the stations known in advance.
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
+    fn spawn(
+        filename: String,
+        opts: SolveOptions,
+        interval: Duration,
+        slots: Option<Arc<Slots>>,
+    ) -> Engine {
+        let latest: Arc<Mutex<Arc<[StationStats]>>> = Arc::default();
+        let user = opts.preview().map(|p| p.sink.clone());
+        let publish = latest.clone();
+        let opts = opts.preview_every(interval, move |stats| {
+            *publish.lock().unwrap() = stats.into();
+            if let Some(sink) = &user {
+                sink(stats);
+            }
+        });
+
+        let done = latest.clone();
+        let handle = std::thread::spawn(move || {
+            let stats = match &slots {
+                Some(slots) => solve_stats_with_slots(filename, &opts, slots),
+                None => solve_stats_with(filename, &opts),
+            }?;
+            *done.lock().unwrap() = stats.as_slice().into();
+            Ok(stats)
+        });
+        Engine { latest, handle }
+    }
+
+    /// The most recently published aggregate, sorted by name. Empty until
+    /// the first interval has passed.
+    pub fn snapshot(&self) -> Arc<[StationStats]> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the solve and returns its final result.
+    pub fn wait(self) -> Result<Vec<StationStats>, SolveError> {
+        match self.handle.join() {
+            Ok(res) => res,
+            Err(_) => Err(SolveError::Panicked),
+        }
+    }
+}
+
+/// Sets up an [`Engine`]; start from [`Engine::builder`] and chain the
+/// setters. Without them it solves with the default options and refreshes
+/// snapshots every second.
+pub struct EngineBuilder {
+    opts: SolveOptions,
+    interval: Duration,
+    slots: Option<Arc<Slots>>,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self {
+            opts: SolveOptions::default(),
+            interval: Duration::from_secs(1),
+            slots: None,
+        }
+    }
+}
+
+impl EngineBuilder {
+    pub fn options(mut self, opts: SolveOptions) -> Self {
+        self.opts = opts;
+        self
+    }
+
+    /// How often snapshots are refreshed; see [`Engine::start`].
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Shares `slots` with other solves; see [`Engine::start_with_slots`].
+    pub fn slots(mut self, slots: Arc<Slots>) -> Self {
+        self.slots = Some(slots);
+        self
+    }
+
+    /// Gives each of `names` a slot before scanning starts, so every
+    /// worker's table is sized for them and their rows skip the insert
+    /// path. Stations not listed are still counted. With [`Self::slots`]
+    /// set, the names are added to the shared slots.
+    pub fn with_stations<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.slots.get_or_insert_default().register(names);
+        self
+    }
+
+    /// Starts solving `filename`.
+    pub fn start(self, filename: String) -> Engine {
+        Engine::spawn(filename, self.opts, self.interval, self.slots)
+    }
+}
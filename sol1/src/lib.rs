@@ -1,17 +1,103 @@
 use ahash::AHashMap;
-use memmap2::MmapOptions;
-use std::fmt::Write as FmtWrite;
+use findlib::{
+    Compression, Distribution, FileError, InputFormat, Recent, Timings, Validation, Warning,
+    format_stats,
+};
+use memmap2::{Mmap, MmapOptions};
+use serde::Deserialize;
+use slots::SlotIndex;
 use std::fs::File;
-use std::ops::Range;
+use std::io::{Read, Seek};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-pub use findlib::find;
+mod admit;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod binary;
+mod buckets;
+mod chunks;
+mod compressed;
+mod convert;
+mod engine;
+mod preview;
+mod profile;
+mod slots;
+mod steal;
+mod stream;
+mod table;
+mod watermark;
+
+use admit::Admit;
+use chunks::ChunkLog;
+use table::Table;
+
+pub use binary::FRAME;
+pub use buckets::{solve_buckets, solve_buckets_bytes};
+pub use convert::{convert, for_each_row};
+pub use engine::{Engine, EngineBuilder};
+pub use findlib::{SolveError, SolveOptions, StationStats, find};
+pub use profile::{MAX_NAME_LEN, Profile, profile, profile_bytes};
+pub use slots::Slots;
 
 pub const NEWLINE: u8 = 10;
 pub const SEMICOLON: u8 = 59;
+pub const TAB: u8 = 9;
+pub const COMMA: u8 = 44;
 pub const NUM_STATIONS: usize = 413;
 pub const MINUS: u8 = 45;
 pub const PERIOD: u8 = 46;
 
+// Chunks per worker on hybrid CPUs; see `findlib::cpu::is_hybrid`.
+const HYBRID_OVERSPLIT: usize = 4;
+
+// Below this size, starting threads costs more than scanning the whole
+// input on the calling thread.
+const SMALL_INPUT: usize = 8 << 20;
+
+// Below this many stations, sorting them on one thread beats starting more.
+const PARALLEL_SORT: usize = 1 << 16;
+
+#[cfg(test)]
+thread_local! {
+    // Threads `spawn_scoped` may still start from this thread, standing in
+    // for a pids limit in tests; unlimited if `None`.
+    static SPAWN_BUDGET: std::cell::Cell<Option<usize>> = const { std::cell::Cell::new(None) };
+    // Whether inputs from this thread take the threaded block scan however
+    // small they are; see `tests::in_blocks`.
+    static IN_BLOCKS: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+// Whether `len` bytes are scanned whole on the calling thread: below
+// SMALL_INPUT, unless previews need the threaded scan.
+fn scans_whole(len: usize, opts: &SolveOptions) -> bool {
+    #[cfg(test)]
+    if IN_BLOCKS.get() {
+        return false;
+    }
+    len < SMALL_INPUT && opts.preview().is_none()
+}
+
+/// Starts a scoped thread, returning the error where
+/// [`std::thread::Scope::spawn`] would panic, so callers can make do
+/// without it.
+fn spawn_scoped<'scope, F, T>(
+    scope: &'scope std::thread::Scope<'scope, '_>,
+    f: F,
+) -> std::io::Result<std::thread::ScopedJoinHandle<'scope, T>>
+where
+    F: FnOnce() -> T + Send + 'scope,
+    T: Send + 'scope,
+{
+    #[cfg(test)]
+    match SPAWN_BUDGET.get() {
+        Some(0) => return Err(std::io::ErrorKind::WouldBlock.into()),
+        left => SPAWN_BUDGET.set(left.map(|n| n - 1)),
+    }
+    std::thread::Builder::new().spawn_scoped(scope, f)
+}
+
 #[derive(Debug)]
 struct Aggregator {
     name: String,
@@ -19,6 +105,78 @@ struct Aggregator {
     max: i32,
     sum: i64,
     count: u64,
+    // The name is a lossy copy of a key that was not UTF-8.
+    lossy: bool,
+    dist: Option<Distribution>,
+    last: Option<Recent>,
+}
+
+// What aggregates keep beyond min, max, sum and count, as the options ask.
+#[derive(Clone, Copy, Debug, Default)]
+struct Kept {
+    dist: bool,
+    last: Option<usize>,
+}
+
+impl Kept {
+    fn of(opts: &SolveOptions) -> Self {
+        Self {
+            dist: opts.collects_distribution(),
+            last: opts.kept_last(),
+        }
+    }
+}
+
+impl Aggregator {
+    fn new(kept: Kept) -> Self {
+        Self {
+            dist: kept.dist.then(Distribution::new),
+            last: kept.last.map(Recent::new),
+            ..Self::default()
+        }
+    }
+
+    // Adds `val`, from the row at `pos` (any number that grows through the
+    // input; see `scan_all`).
+    #[inline]
+    fn add(&mut self, val: i32, pos: u64) {
+        self.max = i32::max(val, self.max);
+        self.min = i32::min(val, self.min);
+        self.sum += val as i64;
+        self.count += 1;
+        if let Some(dist) = &mut self.dist {
+            dist.add(val);
+        }
+        if let Some(last) = &mut self.last {
+            last.push(pos, val);
+        }
+    }
+
+    fn merge(&mut self, other: &Aggregator) {
+        self.sum += other.sum;
+        self.count += other.count;
+        self.max = i32::max(self.max, other.max);
+        self.min = i32::min(self.min, other.min);
+        self.lossy |= other.lossy;
+        if let (Some(mine), Some(theirs)) = (&mut self.dist, &other.dist) {
+            mine.merge(theirs);
+        }
+        if let (Some(mine), Some(theirs)) = (&mut self.last, &other.last) {
+            mine.merge(theirs);
+        }
+    }
+
+    fn to_stats(&self) -> StationStats {
+        StationStats {
+            name: self.name.clone(),
+            min: self.min,
+            max: self.max,
+            sum: self.sum,
+            count: self.count,
+            dist: self.dist.clone(),
+            last: self.last.clone(),
+        }
+    }
 }
 
 impl Default for Aggregator {
@@ -29,12 +187,57 @@ impl Default for Aggregator {
             max: i32::MIN,
             sum: 0,
             count: 0,
+            lossy: false,
+            dist: None,
+            last: None,
         }
     }
 }
 
 // removed unused find_next_new_line
 
+// Small files are read into memory; for them, setting up and faulting in a
+// mapping costs more than the copy.
+enum Input {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Input::Mapped(m) => m,
+            Input::Read(v) => v,
+        }
+    }
+}
+
+impl Input {
+    fn is_mapped(&self) -> bool {
+        matches!(self, Input::Mapped(_))
+    }
+}
+
+fn open_input(mut file: File, len: u64) -> Result<Input, SolveError> {
+    if len < SMALL_INPUT as u64 {
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        return Ok(Input::Read(buf));
+    }
+    let map = unsafe { MmapOptions::new().map(&file) }.map_err(SolveError::Map)?;
+    Ok(Input::Mapped(map))
+}
+
+// Whether `file` starts with a compression magic, leaving it at the start.
+fn is_compressed(file: &mut File) -> std::io::Result<bool> {
+    let mut magic = Vec::with_capacity(4);
+    file.take(4).read_to_end(&mut magic)?;
+    file.rewind()?;
+    Ok(Compression::detect(&magic).is_some())
+}
+
 #[inline]
 fn memchr_newline(slice: &[u8]) -> Option<usize> {
     slice.iter().position(|&b| b == b'\n')
@@ -42,7 +245,7 @@ fn memchr_newline(slice: &[u8]) -> Option<usize> {
 
 fn chunk_by_newlines(data: &[u8], workers: usize) -> Vec<Range<usize>> {
     if workers == 0 {
-        return vec![0..data.len()];
+        return std::iter::once(0..data.len()).collect();
     }
     let mut ranges = Vec::new();
     let mut s = 0usize;
@@ -68,47 +271,147 @@ fn chunk_by_newlines(data: &[u8], workers: usize) -> Vec<Range<usize>> {
     ranges
 }
 
-fn parse_digits(buffer: &[u8]) -> i32 {
-    let size = buffer.len();
+// Tokens longer than the challenge's `-dd.d` saturate at ±i32::MAX rather
+// than overflowing; a symbol outside `[-.0-9]` is an error, the byte.
+fn parse_digits(buffer: &[u8]) -> Result<i32, u8> {
     let mut neg = 1;
-    let mut acc = 0;
-    let mut pos_mul = 10_i32.pow(size as u32 - 2);
-    for i in 0..size {
-        match buffer[i] {
+    let mut acc: i32 = 0;
+    for &b in buffer {
+        match b {
             MINUS => {
                 neg = -1;
-                pos_mul /= 10;
             }
             PERIOD => {
                 // Do nothing
             }
             48..=57 => {
                 // Digits
-                let d = buffer[i] as i32 - 48;
-                acc += d * pos_mul;
-                pos_mul /= 10;
+                let d = b as i32 - 48;
+                acc = acc.saturating_mul(10).saturating_add(d);
             }
-            _ => {
-                panic!("Unhandled ASCII numerical symbol: {}", buffer[i]);
+            _ => return Err(b),
+        }
+    }
+    Ok(acc * neg)
+}
+
+// Aggregates the whole lines in `range`, in the row format `admit`'s options
+// ask for. Fails on the first trusted value with a byte no number has,
+// leaving the rows before it in `res`.
+fn scan_block<'a>(
+    res: &mut Table<'a>,
+    range: Range<usize>,
+    buffer: &'a [u8],
+    admit: &Admit,
+) -> Result<(), String> {
+    #[cfg(feature = "regex")]
+    if let Some(re) = admit.opts().extractor() {
+        scan_regex_into(res, &buffer[range], re, admit);
+        return Ok(());
+    }
+    match admit.opts().format() {
+        InputFormat::Text if admit.opts().validation() != Validation::Trusted => {
+            scan_checked_into(res, range, buffer, admit)
+        }
+        InputFormat::Text => {
+            return scan_text_into(res, range, buffer, admit)
+                .map_err(|b| format!("unexpected byte {b:#04x} in a value"));
+        }
+        InputFormat::Jsonl => scan_jsonl_into(res, &buffer[range], admit),
+        InputFormat::Binary => binary::scan_binary_into(res, range, buffer, admit),
+        InputFormat::Arrow => unreachable!("arrow input is not scanned in blocks"),
+    }
+    Ok(())
+}
+
+// Decimal values from the slower input paths, in the tenths the tables keep.
+// `as` saturates, like `parse_digits` does for long tokens.
+pub(crate) fn to_tenths(val: f64) -> i32 {
+    (val * 10.0).round() as i32
+}
+
+#[derive(Deserialize)]
+pub(crate) struct JsonRow<'a> {
+    // Raw so the key can borrow the input even when the name has escapes.
+    #[serde(borrow)]
+    pub(crate) station: &'a serde_json::value::RawValue,
+    pub(crate) temp: f64,
+}
+
+fn scan_jsonl_into<'a>(res: &mut Table<'a>, lines: &'a [u8], admit: &Admit) {
+    for line in lines.split(|&b| b == NEWLINE) {
+        let Ok(row) = serde_json::from_slice::<JsonRow>(line) else {
+            continue;
+        };
+        let raw = row.station.get();
+        let Some(plain) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) else {
+            continue;
+        };
+        let val = to_tenths(row.temp);
+        if !plain.contains('\\') {
+            if admit.row(plain.as_bytes()) {
+                res.record(plain.as_bytes(), val);
             }
+            continue;
+        }
+        let Ok(name) = serde_json::from_str::<String>(raw) else {
+            continue;
+        };
+        if admit.row(name.as_bytes()) {
+            res.record_as(raw.as_bytes(), val, || name);
         }
     }
-    acc *= neg;
-    acc
 }
 
-#[inline]
-fn mean_tenths(sum_scaled: i64, count: u64) -> i64 {
-    let denom = count as i64;
-    if sum_scaled >= 0 {
-        (sum_scaled + (denom / 2)) / denom
-    } else {
-        -((-sum_scaled + (denom / 2)) / denom)
+#[cfg(feature = "regex")]
+fn scan_regex_into<'a>(res: &mut Table<'a>, lines: &'a [u8], re: &findlib::Regex, admit: &Admit) {
+    for line in lines.split(|&b| b == NEWLINE) {
+        let Some(caps) = re.captures(line) else {
+            continue;
+        };
+        let (Some(station), Some(value)) = (caps.get(1), caps.get(2)) else {
+            continue;
+        };
+        let Some(val) = std::str::from_utf8(value.as_bytes())
+            .ok()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|v| v.is_finite())
+        else {
+            continue;
+        };
+        let station = station.as_bytes();
+        if admit.row(station) {
+            res.record(station, to_tenths(val));
+        }
     }
 }
 
-fn scan_chunk(start: usize, end: usize, buffer: &[u8]) -> Vec<Aggregator> {
-    let mut res: AHashMap<&[u8], Aggregator> = AHashMap::with_capacity(NUM_STATIONS);
+// Trusted text rows, with `scan_into` specialized for the common
+// delimiters and every other layout split a line at a time.
+fn scan_text_into<'a>(
+    res: &mut Table<'a>,
+    range: Range<usize>,
+    buffer: &'a [u8],
+    admit: &Admit,
+) -> Result<(), u8> {
+    let columns = admit.opts().columns();
+    let (start, end) = (range.start, range.end);
+    match columns.delimiter {
+        _ if !columns.is_pair() => scan_columns_into(res, range, buffer, admit),
+        SEMICOLON => scan_into::<SEMICOLON>(res, start, end, buffer, admit),
+        TAB => scan_into::<TAB>(res, start, end, buffer, admit),
+        COMMA => scan_into::<COMMA>(res, start, end, buffer, admit),
+        _ => scan_columns_into(res, range, buffer, admit),
+    }
+}
+
+fn scan_into<'a, const DELIMITER: u8>(
+    res: &mut Table<'a>,
+    start: usize,
+    end: usize,
+    buffer: &'a [u8],
+    admit: &Admit,
+) -> Result<(), u8> {
     let mut pos = start;
     let mut field_start = start; // start of the current token (station or value)
     let mut current_station: &[u8] = &[]; // station slice captured at ';'
@@ -116,27 +419,14 @@ fn scan_chunk(start: usize, end: usize, buffer: &[u8]) -> Vec<Aggregator> {
 
     while pos < end {
         match buffer[pos] {
-            SEMICOLON => {
+            b if b == DELIMITER => {
                 current_station = &buffer[field_start..pos];
                 field_start = pos + 1;
                 has_station = true;
             }
             NEWLINE => {
                 if has_station {
-                    let value_slice = &buffer[field_start..pos];
-                    if !value_slice.is_empty() {
-                        let val = parse_digits(value_slice);
-                        let entry = res
-                            .entry(current_station)
-                            .or_insert_with(Aggregator::default);
-                        if entry.name.is_empty() {
-                            entry.name = String::from_utf8_lossy(current_station).to_string();
-                        }
-                        entry.max = i32::max(val, entry.max);
-                        entry.min = i32::min(val, entry.min);
-                        entry.sum += val as i64;
-                        entry.count += 1;
-                    }
+                    record_row(res, current_station, &buffer[field_start..pos], admit)?;
                 }
 
                 field_start = pos + 1; // start of next line
@@ -147,78 +437,549 @@ fn scan_chunk(start: usize, end: usize, buffer: &[u8]) -> Vec<Aggregator> {
 
         pos += 1;
     }
+    // The input's last row, if nothing ends it.
+    if has_station {
+        record_row(res, current_station, &buffer[field_start..end], admit)?;
+    }
+    Ok(())
+}
 
-    res.into_iter().map(|(_, v)| v).collect()
+// Records one row of `scan_into`; the value may still end in the `\r` of a
+// CRLF line ending.
+#[inline]
+fn record_row<'a>(
+    res: &mut Table<'a>,
+    station: &'a [u8],
+    value: &[u8],
+    admit: &Admit,
+) -> Result<(), u8> {
+    let value = value.strip_suffix(b"\r").unwrap_or(value);
+    if !value.is_empty() && admit.row(station) {
+        res.record(station, parse_digits(value)?);
+    }
+    Ok(())
 }
 
-pub fn solve(filename: String) -> Result<String, Box<dyn std::error::Error>> {
-    let file = File::open(&filename)?;
-    let mapped_file = unsafe { MmapOptions::new().map(&file)? };
-    let workers = rayon::current_num_threads().max(1);
+// `scan_into` for the layouts it is not specialized for: each line is
+// split on the delimiter, and the value column trusted as `scan_into` does.
+fn scan_columns_into<'a>(
+    res: &mut Table<'a>,
+    range: Range<usize>,
+    buffer: &'a [u8],
+    admit: &Admit,
+) -> Result<(), u8> {
+    let columns = admit.opts().columns();
+    for line in buffer[range].split(|&b| b == NEWLINE) {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if let Some((station, value)) = columns.split(line) {
+            record_row(res, station, value, admit)?;
+        }
+    }
+    Ok(())
+}
 
-    let chunks = chunk_by_newlines(&mapped_file, workers);
+// `scan_into` for `Validation::Strict` and `Lenient`: every row goes
+// through `findlib::parse_row_with`, and a malformed one is rejected or
+// counted instead of trusted. Blank lines (CRLF ones too) are neither.
+fn scan_checked_into<'a>(
+    res: &mut Table<'a>,
+    range: Range<usize>,
+    buffer: &'a [u8],
+    admit: &Admit,
+) {
+    let strict = admit.opts().validation() == Validation::Strict;
+    let columns = admit.opts().columns();
+    let (mut at, mut skipped) = (range.start, 0);
+    for line in buffer[range].split(|&b| b == NEWLINE) {
+        let start = at;
+        at += line.len() + 1;
+        if matches!(line, b"" | b"\r") {
+            continue;
+        }
+        match findlib::parse_row_with(line, columns) {
+            Ok((station, val)) => {
+                if admit.row(station) {
+                    res.record(station, val);
+                }
+            }
+            Err(reason) if strict => return admit.reject(start, reason),
+            Err(_) => skipped += 1,
+        }
+    }
+    admit.skip_rows(skipped);
+}
 
-    let mut res: Vec<Aggregator> = Vec::with_capacity(NUM_STATIONS);
+pub fn solve(filename: String) -> Result<String, SolveError> {
+    Ok(format_stats(&solve_stats(filename)?))
+}
 
-    std::thread::scope(|scope| {
-        let mut handles = Vec::with_capacity(chunks.len());
-
-        for r in chunks.iter().cloned() {
-            let buffer = &mapped_file;
-            let handle = scope.spawn(move || scan_chunk(r.start, r.end, &buffer));
-            handles.push(handle);
-        }
-
-        for handle in handles {
-            let part = handle.join().unwrap();
-            if part.is_empty() {
-                res.extend(part);
-            } else {
-                part.into_iter().for_each(|v| {
-                    if let Some(agg) = res.iter_mut().find(|a| a.name == v.name) {
-                        agg.sum += v.sum;
-                        agg.count += v.count;
-                        agg.max = i32::max(agg.max, v.max);
-                        agg.min = i32::min(agg.min, v.min);
-                    } else {
-                        res.push(v);
-                    }
-                })
+/// Aggregates measurements from a caller-provided buffer instead of a file.
+pub fn solve_bytes(data: &[u8]) -> Result<String, SolveError> {
+    Ok(format_stats(&solve_stats_bytes(data)?))
+}
+
+/// Like [`solve`], but returns the per-station aggregates sorted by name
+/// instead of the formatted summary.
+pub fn solve_stats(filename: String) -> Result<Vec<StationStats>, SolveError> {
+    solve_stats_with(filename, &SolveOptions::default())
+}
+
+/// Like [`solve_bytes`], but returns the per-station aggregates sorted by
+/// name instead of the formatted summary.
+pub fn solve_stats_bytes(data: &[u8]) -> Result<Vec<StationStats>, SolveError> {
+    solve_stats_bytes_with(data, &SolveOptions::default())
+}
+
+/// Like [`solve_stats`], configured by `opts`.
+pub fn solve_stats_with(
+    filename: String,
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
+    let start = Instant::now();
+    let mut file = File::open(&filename)?;
+    let meta = file.metadata()?;
+    if !meta.is_file() {
+        return stream::solve_stats(file, opts, None);
+    }
+    if opts.is_windowed(meta.len()) && !is_compressed(&mut file)? {
+        return stream::solve_stats_windowed(file, opts, None);
+    }
+    let data = open_input(file, meta.len())?;
+    solve_stats_inner(&data, data.is_mapped(), opts, None, start.elapsed())
+}
+
+/// Like [`solve_stats_bytes`], configured by `opts`.
+pub fn solve_stats_bytes_with(
+    data: &[u8],
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
+    solve_stats_inner(data, false, opts, None, Duration::ZERO)
+}
+
+/// Like [`solve_stats_with`], reading `reader` front to back in buffers
+/// instead of mapping a file, e.g. for stdin. [`solve_stats_with`] takes
+/// this path by itself for pipes and other files that are not regular.
+/// Previews are not supported here.
+pub fn solve_stats_reader(
+    reader: impl Read + Send,
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
+    stream::solve_stats(reader, opts, None)
+}
+
+/// Like [`solve_stats_with`], counting known stations into the slots of
+/// `slots` and adding the stations this file introduces.
+pub fn solve_stats_with_slots(
+    filename: String,
+    opts: &SolveOptions,
+    slots: &Slots,
+) -> Result<Vec<StationStats>, SolveError> {
+    let start = Instant::now();
+    let mut file = File::open(&filename)?;
+    let meta = file.metadata()?;
+    let stats = if !meta.is_file() {
+        stream::solve_stats(file, opts, Some(slots))?
+    } else if opts.is_windowed(meta.len()) && !is_compressed(&mut file)? {
+        stream::solve_stats_windowed(file, opts, Some(slots))?
+    } else {
+        let data = open_input(file, meta.len())?;
+        solve_stats_inner(&data, data.is_mapped(), opts, Some(slots), start.elapsed())?
+    };
+    slots.learn(&stats);
+    Ok(stats)
+}
+
+/// Like [`solve_stats_bytes_with`], counting known stations into the slots
+/// of `slots` and adding the stations `data` introduces.
+pub fn solve_stats_bytes_with_slots(
+    data: &[u8],
+    opts: &SolveOptions,
+    slots: &Slots,
+) -> Result<Vec<StationStats>, SolveError> {
+    let stats = solve_stats_inner(data, false, opts, Some(slots), Duration::ZERO)?;
+    slots.learn(&stats);
+    Ok(stats)
+}
+
+/// Like [`solve_stats_with`], over several files as if they were one. The
+/// files are read or mapped up front and scanned by one set of workers
+/// through one gate, so a station filter's quota counts rows across all of
+/// them and the recent values of later files come after those of earlier
+/// ones. If any file has to be streamed instead (it is not a regular file,
+/// is compressed, or is windowed by [`SolveOptions::max_resident_bytes`]),
+/// or the files are Arrow, which is read a batch at a time, they are
+/// solved one after another, counting into shared [`Slots`]; a quota
+/// cannot span them then, and is an error.
+pub fn solve_stats_files_with(
+    filenames: &[String],
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
+    let in_file = |i: usize, error: SolveError| -> SolveError {
+        FileError {
+            path: filenames[i].clone(),
+            error: Box::new(error),
+        }
+        .into()
+    };
+    let start = Instant::now();
+    let mut inputs = Vec::with_capacity(filenames.len());
+    for (i, name) in filenames.iter().enumerate() {
+        match open_whole(name, opts).map_err(|e| in_file(i, e))? {
+            Some(input) => inputs.push(input),
+            None if opts.max_rows().is_some() => {
+                return Err(in_file(
+                    i,
+                    SolveError::Unsupported(
+                        "a quota over several files needs each to be a regular, \
+                         uncompressed file of rows read whole"
+                            .into(),
+                    ),
+                ));
+            }
+            None => {
+                let slots = Slots::new();
+                return findlib::solve_in_turn(filenames, opts, |name, opts| {
+                    solve_stats_with_slots(name, opts, &slots)
+                });
             }
         }
-    });
+    }
+    solve_inputs(&inputs, opts, start.elapsed()).map_err(|(i, e)| in_file(i, e))
+}
 
-    res.sort_unstable_by(|a, b| a.name.cmp(&b.name));
-
-    let mut out = String::with_capacity(res.len().saturating_mul(32) + 3);
-    out.push('{');
-
-    for (idx, v) in res.iter().enumerate() {
-        let mean_t = mean_tenths(v.sum, v.count);
-        let _ = FmtWrite::write_fmt(
-            &mut out,
-            format_args!(
-                "{}={:.1}/{:.1}/{:.1}",
-                v.name,
-                v.min as f32 / 10.0,
-                mean_t as f32 / 10.0,
-                v.max as f32 / 10.0
-            ),
-        );
-        if idx + 1 != res.len() {
-            out.push_str(", ");
+// `name`, read or mapped whole, or `None` if it is streamed instead.
+fn open_whole(name: &str, opts: &SolveOptions) -> Result<Option<Input>, SolveError> {
+    let mut file = File::open(name)?;
+    let meta = file.metadata()?;
+    if !meta.is_file()
+        || opts.format() == InputFormat::Arrow
+        || opts.is_windowed(meta.len())
+        || is_compressed(&mut file)?
+    {
+        return Ok(None);
+    }
+    open_input(file, meta.len()).map(Some)
+}
+
+// `solve_stats_inner` for several inputs at once. An error that belongs to
+// one input comes with its index.
+fn solve_inputs(
+    inputs: &[Input],
+    opts: &SolveOptions,
+    load: Duration,
+) -> Result<Vec<StationStats>, (usize, SolveError)> {
+    check_options(opts).map_err(|e| (0, e))?;
+    if inputs.is_empty() {
+        return Ok(Vec::new());
+    }
+    let data: Vec<&[u8]> = inputs.iter().map(|input| &input[..]).collect();
+    let total: usize = data.iter().map(|d| d.len()).sum();
+    let gate = Admit::new(opts).input_len(total as u64);
+    let mut admits: Vec<Admit> = inputs[1..]
+        .iter()
+        .map(|input| gate.another().mapped(input.is_mapped()))
+        .collect();
+    admits.insert(0, gate.mapped(inputs[0].is_mapped()));
+    let logs: Vec<ChunkLog> = match opts.chunks() {
+        Some(_) => data.iter().map(|_| ChunkLog::default()).collect(),
+        None => Vec::new(),
+    };
+    let mut sources = Vec::with_capacity(data.len());
+    let mut chunks = Vec::new();
+    let workers = opts.workers();
+    // Small sets are scanned a whole input at a time on one thread.
+    let small = scans_whole(total, opts);
+    let mut start = 0;
+    for (i, d) in data.iter().enumerate() {
+        sources.push(steal::Source {
+            data: d,
+            admit: &admits[i],
+            log: logs.get(i),
+            start: start as u64,
+        });
+        // Each input gets its share of the workers' chunks, at least one.
+        let share = (workers * d.len()).div_ceil(total.max(1)).max(1);
+        let split = match opts.format() {
+            _ if small => std::iter::once(0..d.len()).collect(),
+            InputFormat::Binary => binary::chunk_by_frames(d, share),
+            _ => chunk_by_newlines(d, share),
+        };
+        chunks.extend(split.into_iter().map(|r| (i, r)));
+        start += d.len();
+    }
+    let threads = if small { 1 } else { workers };
+    let scan_start = Instant::now();
+    let (parts, spawn) = steal::scan_inputs(
+        &sources,
+        chunks,
+        threads,
+        steal::BLOCK,
+        opts.preview(),
+        None,
+    );
+    // The gates are done with once the scan is.
+    drop(sources);
+    for (i, admit) in admits.into_iter().enumerate() {
+        admit.locate_rejection(data[i], 0, 0);
+        admit.finish().map_err(|e| (i, e))?;
+    }
+    let merge_start = Instant::now();
+    let stats = merge_parts(parts, opts);
+    if let Some(sink) = opts.chunks() {
+        let all = ChunkLog::default();
+        let mut start = 0;
+        for (log, d) in logs.into_iter().zip(&data) {
+            all.append(log, start);
+            start += d.len();
         }
+        all.report(sink);
+    }
+    if let Some(sink) = opts.timings() {
+        (sink.0)(&Timings {
+            load,
+            spawn,
+            scan: merge_start - scan_start - spawn,
+            merge: merge_start.elapsed(),
+        });
+    }
+    Ok(stats)
+}
+
+/// This crate as a [`findlib::Solver`].
+pub struct Sol1;
+
+impl findlib::Solver for Sol1 {
+    fn name(&self) -> &'static str {
+        "sol1"
     }
-    out.push('}');
-    out.push('\n');
 
-    Ok(out)
+    fn solve_stats(
+        &self,
+        filename: String,
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        solve_stats_with(filename, opts)
+    }
+
+    fn solve_stats_bytes(
+        &self,
+        data: &[u8],
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        solve_stats_bytes_with(data, opts)
+    }
+
+    fn solve_stats_files(
+        &self,
+        filenames: &[String],
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        solve_stats_files_with(filenames, opts)
+    }
+}
+
+fn check_options(opts: &SolveOptions) -> Result<(), SolveError> {
+    let checked = opts.validation() != Validation::Trusted;
+    if let Some(kernel) = opts.forced_kernel() {
+        return Err(SolveError::Unsupported(format!(
+            "this solver has no scan kernels, so the {kernel} kernel cannot be forced"
+        )));
+    }
+    #[cfg(feature = "regex")]
+    if let Some(re) = opts.extractor() {
+        if re.captures_len() < 3 {
+            return Err(SolveError::Unsupported(format!(
+                "extractor {re} needs two capture groups (station, value)"
+            )));
+        }
+        if checked {
+            return Err(SolveError::Unsupported(
+                "validation does not apply to extracted rows".into(),
+            ));
+        }
+    }
+    let columns = opts.columns() != findlib::Columns::CHALLENGE;
+    #[cfg(feature = "regex")]
+    if columns && opts.extractor().is_some() {
+        return Err(SolveError::Unsupported(
+            "an extractor replaces the text columns".into(),
+        ));
+    }
+    if columns && opts.format() != InputFormat::Text {
+        return Err(SolveError::Unsupported(format!(
+            "columns only apply to text rows, not {}",
+            opts.format()
+        )));
+    }
+    if checked && opts.format() != InputFormat::Text {
+        return Err(SolveError::Unsupported(format!(
+            "validation only checks text rows, not {}",
+            opts.format()
+        )));
+    }
+    Ok(())
+}
+
+fn solve_stats_inner(
+    data: &[u8],
+    mapped: bool,
+    opts: &SolveOptions,
+    slots: Option<&Slots>,
+    load: Duration,
+) -> Result<Vec<StationStats>, SolveError> {
+    if let Some(kind) = Compression::detect(data) {
+        return compressed::solve_stats(data, kind, opts, slots);
+    }
+    check_options(opts)?;
+    if opts.format() == InputFormat::Arrow {
+        #[cfg(feature = "arrow")]
+        return arrow::solve_stats(data, opts);
+        #[cfg(not(feature = "arrow"))]
+        return Err(SolveError::Unsupported(
+            "arrow input needs sol1's `arrow` feature".into(),
+        ));
+    }
+    let admit = Admit::new(opts).mapped(mapped).input_len(data.len() as u64);
+    let known = slots.map(Slots::snapshot);
+    let log = opts.chunks().map(|_| ChunkLog::default());
+    let scan_start = Instant::now();
+    let (parts, spawn) = scan_all(data, opts, &admit, known, log.as_ref());
+    admit.locate_rejection(data, 0, 0);
+    admit.finish()?;
+    let merge_start = Instant::now();
+    let stats = merge_parts(parts, opts);
+    if let (Some(log), Some(sink)) = (log, opts.chunks()) {
+        log.report(sink);
+    }
+    if let Some(sink) = opts.timings() {
+        (sink.0)(&Timings {
+            load,
+            spawn,
+            scan: merge_start - scan_start - spawn,
+            merge: merge_start.elapsed(),
+        });
+    }
+    Ok(stats)
+}
+
+// The workers' parts merged into one aggregate per station, sorted.
+fn merge_parts(parts: Vec<Vec<Aggregator>>, opts: &SolveOptions) -> Vec<StationStats> {
+    // Keyed by name: with 100k stations and more, looking each one up in
+    // a list would take far longer than the scan.
+    let mut res: AHashMap<String, Aggregator> = AHashMap::with_capacity(NUM_STATIONS);
+    for v in parts.into_iter().flatten() {
+        match res.get_mut(&v.name) {
+            Some(agg) => agg.merge(&v),
+            None => {
+                res.insert(v.name.clone(), v);
+            }
+        }
+    }
+    into_stats(res.into_values().collect(), opts)
+}
+
+// Every worker's aggregates for `data`, and how long starting the workers
+// took. Recent values are placed by their offset in `data`.
+fn scan_all(
+    data: &[u8],
+    opts: &SolveOptions,
+    admit: &Admit,
+    known: Option<Arc<SlotIndex>>,
+    log: Option<&ChunkLog>,
+) -> (Vec<Vec<Aggregator>>, Duration) {
+    if scans_whole(data.len(), opts) {
+        let mut table = Table::new(admit.hasher(), known, Kept::of(opts));
+        table.count_from(data, 0);
+        chunks::scan_logged(log, &mut table, 0..data.len(), data, admit, 0);
+        (vec![table.into_parts()], Duration::ZERO)
+    } else {
+        scan_parallel(data, opts, admit, known, log)
+    }
+}
+
+// Merged aggregates, one per station, sorted by name. Names that are not
+// UTF-8 are warned about here, once each station is merged.
+fn into_stats(mut res: Vec<Aggregator>, opts: &SolveOptions) -> Vec<StationStats> {
+    let count = res.iter().filter(|a| a.lossy).count() as u64;
+    if count > 0 {
+        opts.warn(Warning::LossyNames { count });
+    }
+    if !opts.is_unsorted() {
+        sort_by_name(&mut res, opts.workers());
+    }
+    res.into_iter()
+        .map(|v| StationStats {
+            name: v.name,
+            min: v.min,
+            max: v.max,
+            sum: v.sum,
+            count: v.count,
+            dist: v.dist,
+            last: v.last,
+        })
+        .collect()
+}
+
+// Sorts `res` by name, on `workers` threads once there are enough stations
+// for the sort to show next to the scan: each thread sorts a run, and the
+// stable sort then only has to merge the sorted runs it finds.
+fn sort_by_name(res: &mut [Aggregator], workers: usize) {
+    let by_name = |a: &Aggregator, b: &Aggregator| a.name.cmp(&b.name);
+    if workers < 2 || res.len() < PARALLEL_SORT {
+        res.sort_unstable_by(by_name);
+        return;
+    }
+    std::thread::scope(|scope| {
+        for run in res.chunks_mut(res.len().div_ceil(workers)) {
+            // A run whose thread cannot start is left to the merge below.
+            let _ = spawn_scoped(scope, move || run.sort_unstable_by(by_name));
+        }
+    });
+    res.sort_by(by_name);
+}
+
+fn scan_parallel(
+    data: &[u8],
+    opts: &SolveOptions,
+    admit: &Admit,
+    known: Option<Arc<SlotIndex>>,
+    log: Option<&ChunkLog>,
+) -> (Vec<Vec<Aggregator>>, Duration) {
+    let workers = opts.workers();
+
+    // With mixed P/E cores, oversplit so that the workers on fast cores
+    // take the chunks nobody started while the slow ones finish theirs.
+    let split = if findlib::cpu::is_hybrid() {
+        HYBRID_OVERSPLIT
+    } else {
+        1
+    };
+    let chunks = match opts.format() {
+        InputFormat::Binary => binary::chunk_by_frames(data, workers * split),
+        _ => chunk_by_newlines(data, workers * split),
+    };
+    steal::scan(
+        data,
+        chunks,
+        workers,
+        steal::BLOCK,
+        admit,
+        opts.preview(),
+        known,
+        log,
+    )
+}
+
+/// Runs [`solve_bytes`] over the dataset embedded in the crate.
+pub fn solve_builtin_sample() -> Result<String, SolveError> {
+    solve_bytes(findlib::SAMPLE)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use findlib::read_file;
+    use findlib::{Columns, read_file};
+    use std::io;
     use std::path::Path;
 
     #[test]
@@ -234,4 +995,819 @@ mod tests {
             assert_eq!(want, got, "mismatch for {}", name.display())
         }
     }
+
+    #[test]
+    fn test_solve_bytes() {
+        let got = solve_bytes(b"Hamburg;12.0\nBulawayo;8.9\nHamburg;-3.4\n")
+            .unwrap_or_else(|e| panic!("solve_bytes failed: {e}"));
+        assert_eq!("{Bulawayo=8.9/8.9/8.9, Hamburg=-3.4/4.3/12.0}\n", got);
+    }
+
+    #[test]
+    fn test_solve_bytes_crlf() {
+        let want = "{Bulawayo=8.9/8.9/8.9, Hamburg=-3.4/4.3/12.0}\n";
+        let strict = SolveOptions::default().validate(Validation::Strict);
+        // With and without the last line's ending, scanned as a whole and
+        // streamed in buffers.
+        for data in [
+            &b"Hamburg;12.0\r\nBulawayo;8.9\r\nHamburg;-3.4\r\n"[..],
+            b"Hamburg;12.0\r\nBulawayo;8.9\r\nHamburg;-3.4",
+            b"Hamburg;12.0\nBulawayo;8.9\nHamburg;-3.4",
+        ] {
+            for opts in [&SolveOptions::default(), &strict] {
+                let got = solve_stats_bytes_with(data, opts).unwrap();
+                assert_eq!(want, format_stats(&got), "{data:?}");
+                let got = solve_stats_reader(data, opts).unwrap();
+                assert_eq!(want, format_stats(&got), "{data:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_digits_bounds() {
+        assert_eq!(Ok(999), parse_digits(b"99.9"));
+        assert_eq!(Ok(-999), parse_digits(b"-99.9"));
+        assert_eq!(Ok(32767), parse_digits(b"3276.7"));
+        assert_eq!(Ok(-32768), parse_digits(b"-3276.8"));
+        assert_eq!(Ok(i32::MAX), parse_digits(b"99999999999.9"));
+        assert_eq!(Ok(-i32::MAX), parse_digits(b"-99999999999.9"));
+        assert_eq!(Ok(5), parse_digits(b"5"));
+        assert_eq!(Err(b'x'), parse_digits(b"1x.0"));
+    }
+
+    #[test]
+    fn test_steal_rebalances() {
+        let totals = |(parts, _): (Vec<Vec<Aggregator>>, Duration)| {
+            let mut t = std::collections::BTreeMap::new();
+            for a in parts.into_iter().flatten() {
+                let e = t.entry(a.name).or_insert((i32::MAX, i32::MIN, 0, 0));
+                *e = (e.0.min(a.min), e.1.max(a.max), e.2 + a.sum, e.3 + a.count);
+            }
+            t
+        };
+        let data = findlib::SAMPLE;
+        let want = totals(steal::scan(
+            data,
+            chunk_by_newlines(data, 1),
+            1,
+            steal::BLOCK,
+            &Admit::new(&SolveOptions::default()),
+            None,
+            None,
+            None,
+        ));
+
+        // All work starts on the first worker; the idle ones have to steal
+        // small blocks off it for every line to be counted exactly once.
+        let mut chunks = chunk_by_newlines(data, 1);
+        chunks.extend(std::iter::repeat_n(data.len()..data.len(), 3));
+        assert_eq!(
+            want,
+            totals(steal::scan(
+                data,
+                chunks,
+                4,
+                256,
+                &Admit::new(&SolveOptions::default()),
+                None,
+                None,
+                None,
+            ))
+        );
+    }
+
+    #[test]
+    fn test_oversplit_keeps_thread_count() {
+        // Sixteen chunks on two threads: the chunks nobody started are
+        // picked up by the threads there are, not by threads of their own.
+        let data = findlib::SAMPLE;
+        let log = ChunkLog::default();
+        let opts = SolveOptions::default();
+        let admit = Admit::new(&opts);
+        let (parts, _) = steal::scan(
+            data,
+            chunk_by_newlines(data, 16),
+            2,
+            4096,
+            &admit,
+            None,
+            None,
+            Some(&log),
+        );
+        // One table per thread, and one for what nobody took.
+        assert_eq!(3, parts.len());
+        let rows: u64 = parts.iter().flatten().map(|a| a.count).sum();
+        assert_eq!(data.iter().filter(|&&b| b == NEWLINE).count() as u64, rows);
+        let workers = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = workers.clone();
+        log.report(&findlib::ChunksSink(Arc::new(move |c| {
+            sink.lock().unwrap().extend(c.iter().map(|c| c.worker))
+        })));
+        assert!(workers.lock().unwrap().iter().all(|&w| w < 2));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_memory_watermark() {
+        let totals = |(parts, _): (Vec<Vec<Aggregator>>, Duration)| {
+            let mut t = std::collections::BTreeMap::new();
+            for a in parts.into_iter().flatten() {
+                let e = t.entry(a.name).or_insert((i32::MAX, i32::MIN, 0, 0));
+                *e = (e.0.min(a.min), e.1.max(a.max), e.2 + a.sum, e.3 + a.count);
+            }
+            t
+        };
+        let data = findlib::SAMPLE;
+        let scan = |opts: &SolveOptions| {
+            totals(steal::scan(
+                data,
+                chunk_by_newlines(data, 4),
+                4,
+                256,
+                &Admit::new(opts),
+                None,
+                None,
+                None,
+            ))
+        };
+        let want = scan(&SolveOptions::default());
+
+        // Past the mark from the first poll on, so it is crossed once: two
+        // workers park, their tables flushed and their ranges taken over.
+        let warnings = findlib::Warnings::new();
+        let opts = SolveOptions::default()
+            .memory_watermark(1)
+            .collect_warnings(&warnings);
+        assert_eq!(want, scan(&opts));
+        let warnings = warnings.take();
+        assert!(
+            matches!(
+                warnings[..],
+                [Warning::Watermark {
+                    watermark: 1,
+                    workers: 2,
+                    ..
+                }]
+            ),
+            "{warnings:?}"
+        );
+    }
+
+    #[test]
+    fn test_include_stations() {
+        let opts = SolveOptions::default().include_stations(["Oslo", "Lima"]);
+        let got = solve_stats_bytes_with(b"Oslo;1.0\nRome;2.0\nLima;3.0\nOslo;-1.0\n", &opts)
+            .unwrap_or_else(|e| panic!("solve failed: {e}"));
+        assert_eq!(
+            "{Lima=3.0/3.0/3.0, Oslo=-1.0/0.0/1.0}\n",
+            format_stats(&got)
+        );
+    }
+
+    #[test]
+    fn test_max_rows_per_station() {
+        let opts = SolveOptions::default()
+            .include_stations(["Oslo", "Hamburg"])
+            .max_rows_per_station(2);
+        let got = solve_stats_bytes_with(findlib::SAMPLE, &opts)
+            .unwrap_or_else(|e| panic!("solve failed: {e}"));
+        let counts: Vec<_> = got.iter().map(|s| (s.name.as_str(), s.count)).collect();
+        assert_eq!(vec![("Hamburg", 2), ("Oslo", 2)], counts);
+    }
+
+    #[test]
+    fn test_files_share_quota() {
+        use findlib::Solver;
+
+        let dir = std::env::temp_dir();
+        let shards = [
+            ("a", &b"Oslo;1.0\nRome;9.0\nOslo;2.0\n"[..]),
+            ("b", b"Oslo;3.0\nOslo;4.0\nOslo;5.0\n"),
+        ];
+        let mut names = Vec::new();
+        let mut whole = Vec::new();
+        for (shard, rows) in shards {
+            let path = dir.join(format!("quota-{}-{shard}.txt", std::process::id()));
+            std::fs::write(&path, rows).unwrap();
+            names.push(path.to_string_lossy().into_owned());
+            whole.extend_from_slice(rows);
+        }
+        let opts = SolveOptions::default()
+            .include_stations(["Oslo"])
+            .max_rows_per_station(2)
+            .keep_last(1);
+        let got = Sol1.solve_stats_files(&names, &opts);
+        for name in &names {
+            std::fs::remove_file(name).unwrap();
+        }
+        // The quota is met in the first file, as in the files joined.
+        let want = solve_stats_bytes_with(&whole, &opts).unwrap();
+        assert_eq!("{Oslo=1.0/1.5/2.0}\n", format_stats(&want));
+        assert_eq!(want, got.unwrap());
+    }
+
+    #[test]
+    fn test_preview() {
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let opts = SolveOptions::default().preview_every(Duration::from_millis(1), move |s| {
+            sink.lock()
+                .unwrap()
+                .push(s.iter().map(|s| s.count).sum::<u64>());
+        });
+        let data = findlib::SAMPLE.repeat(16);
+        let got = solve_stats_bytes_with(&data, &opts).unwrap_or_else(|e| panic!("{e}"));
+        let total: u64 = got.iter().map(|s| s.count).sum();
+        let seen = seen.lock().unwrap();
+        assert!(!seen.is_empty(), "no preview was emitted");
+        assert!(seen.iter().all(|&n| n <= total));
+    }
+
+    #[test]
+    fn test_engine_snapshot() {
+        use std::time::Duration;
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_cases/measurements-10.txt");
+        let engine = Engine::start(
+            path.display().to_string(),
+            SolveOptions::default(),
+            Duration::from_millis(1),
+        );
+        while !engine.is_finished() {
+            let snap = engine.snapshot();
+            assert!(snap.windows(2).all(|w| w[0].name < w[1].name));
+        }
+        let last = engine.snapshot();
+        let got = engine
+            .wait()
+            .unwrap_or_else(|e| panic!("solve failed: {e}"));
+        assert_eq!(&got[..], &last[..]);
+        assert_eq!(10, got.iter().map(|s| s.count).sum::<u64>());
+    }
+
+    #[test]
+    fn test_engine_with_stations() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_cases/measurements-10.txt");
+        let want = solve_stats(path.display().to_string()).unwrap();
+        // Two of the file's stations, and one it never mentions.
+        let names = [&want[0].name, &want[1].name, "Atlantis"];
+        let slots = Arc::new(Slots::new());
+        let builder = Engine::builder()
+            .slots(slots.clone())
+            .with_stations(names)
+            .interval(Duration::from_secs(3600));
+        assert_eq!(3, slots.len());
+        let engine = builder.start(path.display().to_string());
+        assert_eq!(want, engine.wait().unwrap());
+        assert_eq!(want.len() + 1, slots.len());
+    }
+
+    #[test]
+    fn test_hash_seed() {
+        let want = solve_stats_bytes(findlib::SAMPLE).unwrap();
+        for opts in [
+            SolveOptions::default().hash_seed(7),
+            SolveOptions::default().hash_seed(7).untrusted_input(true),
+        ] {
+            let got = solve_stats_bytes_with(findlib::SAMPLE, &opts)
+                .unwrap_or_else(|e| panic!("{opts:?} failed: {e}"));
+            assert_eq!(
+                format_stats(&want),
+                format_stats(&got),
+                "{opts:?} disagrees"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_forced_kernel() {
+        let opts = SolveOptions::default().force_kernel(findlib::Kernel::Scalar);
+        let err = solve_stats_bytes_with(findlib::SAMPLE, &opts).unwrap_err();
+        assert!(matches!(err, SolveError::Unsupported(_)), "{err}");
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_extract_with() {
+        let re = findlib::Regex::new(r"station=(\w+) temp=(\S+)").unwrap();
+        let log = b"t=1 station=Oslo temp=1.04\nnoise\nt=2 station=Rome temp=-2\nt=3 station=Oslo temp=x\n";
+        let got = solve_stats_bytes_with(log, &SolveOptions::default().extract_with(re))
+            .unwrap_or_else(|e| panic!("solve failed: {e}"));
+        assert_eq!(
+            "{Oslo=1.0/1.0/1.0, Rome=-2.0/-2.0/-2.0}\n",
+            format_stats(&got)
+        );
+
+        let one_group = findlib::Regex::new(r"station=(\w+)").unwrap();
+        let opts = SolveOptions::default().extract_with(one_group);
+        assert!(solve_stats_bytes_with(log, &opts).is_err());
+    }
+
+    #[test]
+    fn test_jsonl() {
+        let rows = br#"{"station": "Oslo", "temp": 1.04}
+{"station": "Z\u00fcrich", "temp": -2}
+not json
+{"temp": 3.0, "station": "Z\u00fcrich", "extra": true}
+{"station": "Oslo"}
+"#;
+        let opts = SolveOptions::default().input_format(InputFormat::Jsonl);
+        let got =
+            solve_stats_bytes_with(rows, &opts).unwrap_or_else(|e| panic!("solve failed: {e}"));
+        assert_eq!(
+            "{Oslo=1.0/1.0/1.0, Zürich=-2.0/0.5/3.0}\n",
+            format_stats(&got)
+        );
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let mut bin = Vec::new();
+        let rows = convert(
+            findlib::SAMPLE,
+            InputFormat::Text,
+            Columns::CHALLENGE,
+            InputFormat::Binary,
+            &mut bin,
+        )
+        .unwrap();
+        assert_eq!(
+            findlib::SAMPLE.iter().filter(|&&b| b == NEWLINE).count() as u64,
+            rows
+        );
+        // The sample spans several frames, so rows get padded at least once.
+        assert!(bin.len() > 2 * FRAME);
+
+        let opts = SolveOptions::default().input_format(InputFormat::Binary);
+        let got =
+            solve_stats_bytes_with(&bin, &opts).unwrap_or_else(|e| panic!("solve failed: {e}"));
+        assert_eq!(solve_builtin_sample().unwrap(), format_stats(&got));
+
+        let err = convert(
+            b"Oslo;1.0\nRome;4000.0\n",
+            InputFormat::Text,
+            Columns::CHALLENGE,
+            InputFormat::Binary,
+            Vec::new(),
+        )
+        .unwrap_err();
+        assert_eq!("line 2: value out of range", err.to_string());
+
+        // Back to text through jsonl, rows keep their order and values.
+        let mut jsonl = Vec::new();
+        let (binary, jsonl_in) = (InputFormat::Binary, InputFormat::Jsonl);
+        convert(&bin, binary, Columns::CHALLENGE, jsonl_in, &mut jsonl).unwrap();
+        let mut text = Vec::new();
+        convert(
+            &jsonl,
+            jsonl_in,
+            Columns::CHALLENGE,
+            InputFormat::Text,
+            &mut text,
+        )
+        .unwrap();
+        assert_eq!(findlib::SAMPLE, &text[..]);
+    }
+
+    #[test]
+    fn test_convert_crlf() {
+        let mut text = Vec::new();
+        let rows = convert(
+            b"Oslo;1.0\r\nRome;-4.5\r\nLima;2.0",
+            InputFormat::Text,
+            Columns::CHALLENGE,
+            InputFormat::Text,
+            &mut text,
+        )
+        .unwrap();
+        assert_eq!(3, rows);
+        assert_eq!(&b"Oslo;1.0\nRome;-4.5\nLima;2.0\n"[..], &text[..]);
+    }
+
+    #[test]
+    fn test_convert_columns() {
+        let tsv = Columns {
+            delimiter: TAB,
+            value: 3,
+        };
+        let mut text = Vec::new();
+        let rows = convert(
+            b"Oslo\t2024-01-01\t1.0\nRome\t2024-01-02\t-4.5\t!\n",
+            InputFormat::Text,
+            tsv,
+            InputFormat::Text,
+            &mut text,
+        )
+        .unwrap();
+        assert_eq!(2, rows);
+        assert_eq!(&b"Oslo;1.0\nRome;-4.5\n"[..], &text[..]);
+
+        let err = convert(b"", InputFormat::Jsonl, tsv, InputFormat::Text, Vec::new());
+        assert_eq!(io::ErrorKind::InvalidInput, err.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_slots() {
+        let want = solve_stats_bytes(findlib::SAMPLE).unwrap();
+        let slots = Slots::new();
+        for _ in 0..2 {
+            let got =
+                solve_stats_bytes_with_slots(findlib::SAMPLE, &SolveOptions::default(), &slots)
+                    .unwrap_or_else(|e| panic!("solve failed: {e}"));
+            assert_eq!(format_stats(&want), format_stats(&got));
+            assert_eq!(want.len(), slots.len());
+        }
+
+        // A shard with one known and one new station.
+        let got = solve_stats_bytes_with_slots(
+            b"Oslo;1.0\nAtlantis;-2.0\n",
+            &SolveOptions::default(),
+            &slots,
+        )
+        .unwrap_or_else(|e| panic!("solve failed: {e}"));
+        assert_eq!(
+            "{Atlantis=-2.0/-2.0/-2.0, Oslo=1.0/1.0/1.0}\n",
+            format_stats(&got)
+        );
+        assert_eq!(want.len() + 1, slots.len());
+    }
+
+    // Runs `f` with the threaded block scan for inputs of any size, as
+    // large ones get.
+    fn in_blocks<T>(f: impl FnOnce() -> T) -> T {
+        IN_BLOCKS.set(true);
+        let out = f();
+        IN_BLOCKS.set(false);
+        out
+    }
+
+    #[test]
+    fn test_small_input_path() {
+        let want = in_blocks(|| solve_stats_bytes(findlib::SAMPLE)).unwrap();
+        assert!(findlib::SAMPLE.len() < SMALL_INPUT);
+        let got = solve_stats_bytes(findlib::SAMPLE).unwrap();
+        assert_eq!(want, got);
+    }
+
+    #[test]
+    fn test_spawn_failures_degrade() {
+        let want = solve_stats_bytes(findlib::SAMPLE).unwrap();
+        // One spawn goes to the preview thread, so a budget of three leaves
+        // two workers and none leaves only this thread.
+        for (budget, started, previews) in [(3, 2, true), (0, 0, false)] {
+            let warnings = findlib::Warnings::new();
+            let opts = SolveOptions::default()
+                .threads(4)
+                .preview_every(Duration::from_secs(3600), |_| {})
+                .collect_warnings(&warnings);
+            SPAWN_BUDGET.set(Some(budget));
+            let got = solve_stats_bytes_with(findlib::SAMPLE, &opts);
+            SPAWN_BUDGET.set(None);
+            assert_eq!(want, got.unwrap(), "budget {budget}");
+
+            let warnings = warnings.take();
+            let workers: Vec<_> = warnings
+                .iter()
+                .filter_map(|w| match w {
+                    findlib::Warning::FewerWorkers { started, .. } => Some(*started),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(vec![started], workers, "budget {budget}");
+            let no_previews = warnings
+                .iter()
+                .any(|w| matches!(w, findlib::Warning::NoPreviews { .. }));
+            assert_eq!(!previews, no_previews, "budget {budget}");
+        }
+    }
+
+    #[test]
+    fn test_windowed_file() {
+        let path = std::env::temp_dir().join(format!("windowed-{}.txt", std::process::id()));
+        let name = path.to_string_lossy().into_owned();
+        std::fs::write(&path, findlib::SAMPLE).unwrap();
+        let want = solve_stats_bytes(findlib::SAMPLE).unwrap();
+        let opts = SolveOptions::default().max_resident_bytes(100_000);
+        let got = solve_stats_with(name.clone(), &opts);
+        // Streamed, so the file cannot be previewed.
+        let previews = opts.preview_every(Duration::from_secs(3600), |_| {});
+        let err = solve_stats_with(name, &previews).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(want, got.unwrap());
+        assert!(matches!(err, SolveError::Unsupported(_)), "{err}");
+    }
+
+    #[test]
+    fn test_unsorted() {
+        let sorted = solve_stats_bytes(findlib::SAMPLE).unwrap();
+        let opts = SolveOptions::default().threads(4).unsorted(true);
+        let mut got = solve_stats_bytes_with(findlib::SAMPLE, &opts).unwrap();
+        got.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(sorted, got);
+    }
+
+    #[test]
+    fn test_sort_by_name() {
+        let names: Vec<String> = (0..PARALLEL_SORT as u64 + 3)
+            .map(|i| format!("S{}", i.wrapping_mul(0x9e37_79b9_7f4a_7c15)))
+            .collect();
+        let mut want = names.clone();
+        want.sort_unstable();
+        // Every run on its own thread, then only some of them.
+        for budget in [None, Some(2)] {
+            let mut res: Vec<Aggregator> = names
+                .iter()
+                .map(|name| Aggregator {
+                    name: name.clone(),
+                    min: 0,
+                    max: 0,
+                    sum: 0,
+                    count: 1,
+                    lossy: false,
+                    dist: None,
+                    last: None,
+                })
+                .collect();
+            SPAWN_BUDGET.set(budget);
+            sort_by_name(&mut res, 5);
+            SPAWN_BUDGET.set(None);
+            let got: Vec<String> = res.into_iter().map(|a| a.name).collect();
+            assert_eq!(want, got, "budget {budget:?}");
+        }
+    }
+
+    #[test]
+    fn test_chunk_panics() {
+        use findlib::{PanicPolicy, Warning};
+
+        // A value parse_digits has no case for, halfway in.
+        let mut data = findlib::SAMPLE.repeat(4);
+        let bad = data.len() / 2;
+        let bad = bad + data[bad..].iter().position(|&b| b == NEWLINE).unwrap() + 1;
+        data.splice(bad..bad, b"Oslo;1x.0\n".iter().copied());
+        let lines = |d: &[u8]| d.iter().filter(|&&b| b == NEWLINE).count() as u64;
+        let opts = SolveOptions::default().threads(4);
+
+        let err = in_blocks(|| solve_stats_bytes_with(&data, &opts));
+        let SolveError::Chunk(err) = err.unwrap_err() else {
+            panic!("not a chunk error");
+        };
+        assert!(err.range.contains(&bad), "{err}");
+        assert_eq!("unexpected byte 0x78 in a value", err.message);
+
+        let warnings = findlib::Warnings::new();
+        let opts = opts
+            .on_chunk_panic(PanicPolicy::Skip)
+            .collect_warnings(&warnings);
+        let got = in_blocks(|| solve_stats_bytes_with(&data, &opts)).unwrap();
+        let warnings = warnings.take();
+        let [Warning::SkippedChunk(err)] = warnings.as_slice() else {
+            panic!("{warnings:?}");
+        };
+        assert!(err.range.contains(&bad), "{err}");
+        let rows: u64 = got.iter().map(|s| s.count).sum();
+        assert_eq!(lines(&data) - lines(&data[err.range.clone()]), rows);
+    }
+
+    #[test]
+    fn test_validation() {
+        use findlib::{ParseError, Validation, Warning};
+
+        let clean = findlib::SAMPLE.repeat(4);
+        let want = solve_stats_bytes(&clean).unwrap();
+        let mut data = clean.clone();
+        let bad = data.len() / 2;
+        let bad = bad + data[bad..].iter().position(|&b| b == NEWLINE).unwrap() + 1;
+        data.splice(bad..bad, b"Oslo;1x.0\n".iter().copied());
+        let line = data[..bad].iter().filter(|&&b| b == NEWLINE).count() as u64 + 1;
+        let opts = SolveOptions::default().threads(4);
+
+        let strict = opts.clone().validate(Validation::Strict);
+        let err = in_blocks(|| solve_stats_bytes_with(&data, &strict));
+        let SolveError::Parse(err) = err.unwrap_err() else {
+            panic!("not a parse error");
+        };
+        assert_eq!(
+            ParseError {
+                line,
+                byte_offset: bad,
+                reason: "value is not -?d?d.d",
+            },
+            err
+        );
+
+        data.extend_from_slice(b"noise\n\n");
+        let warnings = findlib::Warnings::new();
+        let opts = opts
+            .validate(Validation::Lenient)
+            .collect_warnings(&warnings);
+        let got = in_blocks(|| solve_stats_bytes_with(&data, &opts));
+        assert_eq!(want, got.unwrap());
+        assert_eq!(vec![Warning::SkippedRows { count: 2 }], warnings.take());
+    }
+
+    #[test]
+    fn test_solve_report() {
+        use findlib::Solver;
+
+        let path = std::env::temp_dir().join(format!("report-{}.txt", std::process::id()));
+        let name = path.to_string_lossy().into_owned();
+        std::fs::write(&path, b"Oslo;1.0\nK\xf8benhavn;2.0\nK\xf8benhavn;3.0\n").unwrap();
+        let seen = Arc::new(std::sync::Mutex::new(0));
+        let sink = seen.clone();
+        let opts = SolveOptions::default().report_warnings(move |_| *sink.lock().unwrap() += 1);
+        // Mapped, then streamed.
+        for opts in [opts.clone(), opts.max_resident_bytes(1)] {
+            let report = Sol1
+                .solve_report(std::slice::from_ref(&name), &opts)
+                .unwrap();
+            assert_eq!(vec![Warning::LossyNames { count: 1 }], report.warnings);
+            assert_eq!(
+                ("K\u{FFFD}benhavn", 2),
+                (&report.stats[0].name[..], report.stats[0].count)
+            );
+        }
+        std::fs::remove_file(&path).unwrap();
+        // The sink set beforehand still saw them.
+        assert_eq!(2, *seen.lock().unwrap());
+    }
+
+    #[test]
+    fn test_distribution() {
+        let mut want: std::collections::BTreeMap<&[u8], StationStats> = Default::default();
+        for line in findlib::SAMPLE
+            .split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty())
+        {
+            let (name, temp) = findlib::parse_row(line).unwrap();
+            let s = want.entry(name).or_insert_with(|| StationStats {
+                dist: Some(Distribution::new()),
+                ..StationStats::new(String::from_utf8_lossy(name), temp, temp, 0, 0)
+            });
+            s.min = s.min.min(temp);
+            s.max = s.max.max(temp);
+            s.sum += i64::from(temp);
+            s.count += 1;
+            s.dist.as_mut().unwrap().add(temp);
+        }
+        let want: Vec<_> = want.into_values().collect();
+        let opts = SolveOptions::default()
+            .threads(3)
+            .collect_distribution(true);
+        // Small input on one thread, then stolen blocks.
+        let got = solve_stats_bytes_with(findlib::SAMPLE, &opts).unwrap();
+        assert_eq!(want, got);
+        let got = in_blocks(|| solve_stats_bytes_with(findlib::SAMPLE, &opts)).unwrap();
+        assert_eq!(want, got);
+    }
+
+    #[test]
+    fn test_keep_last() {
+        use findlib::Solver;
+
+        let mut data = findlib::SAMPLE.to_vec();
+        data.extend_from_slice(b"Oslo;1.0\nOslo;2.0\nOslo;3.0\n");
+        let dir = std::env::temp_dir();
+        let long = dir.join(format!("last-{}.txt", std::process::id()));
+        let short = dir.join(format!("last-{}-short.txt", std::process::id()));
+        std::fs::write(&long, &data).unwrap();
+        std::fs::write(&short, b"Oslo;-5.0\n").unwrap();
+        let names = [&long, &short].map(|p| p.to_string_lossy().into_owned());
+        let last = |stats: Vec<StationStats>| -> Vec<i32> {
+            let oslo = stats.iter().find(|s| s.name == "Oslo").unwrap();
+            oslo.last.as_ref().unwrap().values().collect()
+        };
+
+        let opts = SolveOptions::default().threads(3).keep_last(2);
+        let streamed = opts.clone().max_resident_bytes(100_000);
+        // One table, stolen blocks, then buffers of a stream.
+        for got in [
+            solve_stats_bytes_with(&data, &opts),
+            in_blocks(|| solve_stats_bytes_with(&data, &opts)),
+            solve_stats_bytes_with(&data, &streamed),
+        ] {
+            assert_eq!(vec![20, 30], last(got.unwrap()));
+        }
+        let got = solve_stats_with(names[0].clone(), &streamed).unwrap();
+        assert_eq!(vec![20, 30], last(got));
+        // The second file's rows come after the first's.
+        let got = Sol1.solve_stats_files(&names, &opts);
+        std::fs::remove_file(&long).unwrap();
+        std::fs::remove_file(&short).unwrap();
+        assert_eq!(vec![30, -50], last(got.unwrap()));
+    }
+
+    #[test]
+    fn test_columns() {
+        use findlib::Columns;
+
+        let want = solve_stats_bytes(findlib::SAMPLE).unwrap();
+        let swap = |to: u8| -> Vec<u8> {
+            let sample = findlib::SAMPLE.iter();
+            sample
+                .map(|&b| if b == SEMICOLON { to } else { b })
+                .collect()
+        };
+        // The value third of four `|` columns.
+        let mut fields = Vec::new();
+        for line in findlib::SAMPLE
+            .split(|&b| b == NEWLINE)
+            .filter(|l| !l.is_empty())
+        {
+            let at = line.iter().position(|&b| b == SEMICOLON).unwrap();
+            fields.extend_from_slice(&line[..at]);
+            fields.extend_from_slice(b"|x|");
+            fields.extend_from_slice(&line[at + 1..]);
+            fields.extend_from_slice(b"|y\r\n");
+        }
+        for (delimiter, value, data) in [
+            (TAB, 2, swap(TAB)),
+            (b'|', 2, swap(b'|')),
+            (b'|', 3, fields),
+        ] {
+            let columns = Columns { delimiter, value };
+            let opts = SolveOptions::default().threads(2).text_columns(columns);
+            for opts in [opts.clone(), opts.validate(Validation::Strict)] {
+                let got = solve_stats_bytes_with(&data, &opts);
+                assert_eq!(want, got.unwrap(), "{columns:?} {:?}", opts.validation());
+            }
+        }
+
+        let jsonl = SolveOptions::default()
+            .input_format(InputFormat::Jsonl)
+            .text_columns(Columns {
+                delimiter: TAB,
+                value: 2,
+            });
+        let err = solve_stats_bytes_with(b"", &jsonl).unwrap_err();
+        assert!(matches!(err, SolveError::Unsupported(_)), "{err}");
+    }
+
+    #[test]
+    fn test_report_timings() {
+        let got = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = got.clone();
+        let opts = SolveOptions::default()
+            .threads(2)
+            .report_timings(move |t| sink.lock().unwrap().push(*t));
+        solve_stats_bytes_with(findlib::SAMPLE, &opts).unwrap();
+        let got = got.lock().unwrap();
+        assert_eq!(1, got.len());
+        // In-memory and small: nothing to load, no threads to start.
+        assert_eq!(
+            (Duration::ZERO, Duration::ZERO),
+            (got[0].load, got[0].spawn)
+        );
+    }
+
+    #[test]
+    fn test_report_chunks() {
+        let got = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = got.clone();
+        let opts = SolveOptions::default()
+            .report_chunks(move |c| sink.lock().unwrap().extend_from_slice(c));
+        let data = findlib::SAMPLE;
+        let stats = solve_stats_bytes_with(data, &opts).unwrap();
+        let rows: u64 = stats.iter().map(|s| s.count).sum();
+
+        let log = ChunkLog::default();
+        let admit = Admit::new(&opts);
+        steal::scan(
+            data,
+            chunk_by_newlines(data, 4),
+            4,
+            4096,
+            &admit,
+            None,
+            None,
+            Some(&log),
+        );
+        log.report(opts.chunks().unwrap());
+
+        let got = got.lock().unwrap();
+        // The small-input path scans everything as one block.
+        assert_eq!(0..data.len(), got[0].range);
+        assert_eq!((rows, stats.len()), (got[0].rows, got[0].keys));
+        let blocks = &got[1..];
+        assert!(
+            blocks
+                .windows(2)
+                .all(|w| w[0].range.end == w[1].range.start)
+        );
+        assert_eq!(0, blocks[0].range.start);
+        assert_eq!(data.len(), blocks[blocks.len() - 1].range.end);
+        assert_eq!(rows, blocks.iter().map(|c| c.rows).sum::<u64>());
+        assert!(blocks.iter().all(|c| c.worker < 4 && c.keys <= stats.len()));
+    }
+
+    #[test]
+    fn test_builtin_sample() {
+        let got = solve_builtin_sample().unwrap_or_else(|e| panic!("solve failed: {e}"));
+        for (name, _) in findlib::STATIONS {
+            assert!(got.contains(&format!("{name}=")), "missing {name}");
+        }
+    }
 }
@@ -0,0 +1,89 @@
+use super::Table;
+use findlib::{Preview, StationStats};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// Per-worker copies of the tables, refreshed on request. The ticker bumps
+/// `epoch`; each worker notices between two blocks and publishes once, so
+/// the scan pays for a copy per interval rather than per block.
+pub(crate) struct Snapshots {
+    epoch: AtomicU64,
+    parts: Vec<Mutex<Vec<StationStats>>>,
+    // What workers flushed into the watermark's shared table.
+    flushed: Mutex<Vec<StationStats>>,
+    done: Mutex<bool>,
+    wake: Condvar,
+}
+
+impl Snapshots {
+    pub(crate) fn new(workers: usize) -> Self {
+        Self {
+            epoch: AtomicU64::new(0),
+            parts: (0..workers).map(|_| Mutex::default()).collect(),
+            flushed: Mutex::default(),
+            done: Mutex::new(false),
+            wake: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn publish_if_asked(&self, me: usize, seen: &mut u64, res: &Table) {
+        let epoch = self.epoch.load(Ordering::Relaxed);
+        if epoch != *seen {
+            *seen = epoch;
+            *self.parts[me].lock().unwrap() = res.to_stats();
+        }
+    }
+
+    /// Records that worker `me` flushed its table, which now holds nothing,
+    /// into the shared one, which holds `flushed`.
+    pub(crate) fn flushed(&self, me: usize, flushed: Vec<StationStats>) {
+        // Both change under the `flushed` lock, which `merge` holds
+        // throughout, so no preview counts a row twice or not at all.
+        let mut all = self.flushed.lock().unwrap();
+        self.parts[me].lock().unwrap().clear();
+        *all = flushed;
+    }
+
+    /// Emits a merged preview every interval until [`Self::finish`].
+    pub(crate) fn run(&self, preview: &Preview) {
+        // A zero interval would spin on the lock and starve `finish`.
+        let interval = preview.interval.max(Duration::from_millis(1));
+        loop {
+            self.epoch.fetch_add(1, Ordering::Relaxed);
+            let done = self.done.lock().unwrap();
+            let (done, _) = self
+                .wake
+                .wait_timeout_while(done, interval, |d| !*d)
+                .unwrap();
+            if *done {
+                return;
+            }
+            // Unlocked while the sink runs, so `finish` never waits on it.
+            drop(done);
+            (preview.sink)(&self.merge());
+        }
+    }
+
+    pub(crate) fn finish(&self) {
+        *self.done.lock().unwrap() = true;
+        self.wake.notify_all();
+    }
+
+    fn merge(&self) -> Vec<StationStats> {
+        let mut all: BTreeMap<String, StationStats> = BTreeMap::new();
+        let mut add = |s: &StationStats| match all.get_mut(&s.name) {
+            Some(acc) => acc.merge(s),
+            None => {
+                all.insert(s.name.clone(), s.clone());
+            }
+        };
+        let flushed = self.flushed.lock().unwrap();
+        flushed.iter().for_each(&mut add);
+        for part in &self.parts {
+            part.lock().unwrap().iter().for_each(&mut add);
+        }
+        all.into_values().collect()
+    }
+}
@@ -0,0 +1,152 @@
+use super::{NEWLINE, SEMICOLON, SolveError, chunk_by_newlines};
+use memmap2::MmapOptions;
+use std::fs::File;
+
+/// Longest station name the rules allow, in bytes; longer names are
+/// counted in the last bucket of [`Profile::name_lengths`].
+pub const MAX_NAME_LEN: usize = 100;
+
+/// Shape of a measurements file, gathered in one pass without aggregating
+/// per station.
+#[derive(Debug, Default)]
+pub struct Profile {
+    /// Lines in the file, valid or not.
+    pub rows: u64,
+    /// Lines that are not exactly `<name>;<-?d?d.d>`; see
+    /// [`findlib::parse_row`].
+    pub invalid_rows: u64,
+    /// Smallest valid value, in tenths of a degree.
+    pub value_min: i32,
+    /// Largest valid value, in tenths of a degree.
+    pub value_max: i32,
+    /// Sum of all valid values, in tenths of a degree.
+    pub value_sum: i64,
+    /// Number of lines per worker chunk, in file order.
+    pub rows_per_chunk: Vec<u64>,
+    /// `name_lengths[n]` counts names of `n` bytes.
+    pub name_lengths: Vec<u64>,
+    /// `delimiters[n]` counts lines with `n` semicolons; the last entry
+    /// counts lines with two or more.
+    pub delimiters: [u64; 3],
+}
+
+impl Profile {
+    /// Mean of all valid values, in degrees.
+    pub fn value_mean(&self) -> f64 {
+        let valid = self.rows - self.invalid_rows;
+        if valid == 0 {
+            return 0.0;
+        }
+        self.value_sum as f64 / valid as f64 / 10.0
+    }
+
+    /// Mean of all valid values in tenths of a degree, rounded like the
+    /// stations' means, so it never prints as `-0.0`.
+    pub fn value_mean_tenths(&self) -> i64 {
+        match self.rows - self.invalid_rows {
+            0 => 0,
+            valid => findlib::mean_tenths(self.value_sum, valid),
+        }
+    }
+
+    fn merge(&mut self, other: Profile) {
+        self.rows += other.rows;
+        self.invalid_rows += other.invalid_rows;
+        self.value_min = self.value_min.min(other.value_min);
+        self.value_max = self.value_max.max(other.value_max);
+        self.value_sum += other.value_sum;
+        self.rows_per_chunk.extend(other.rows_per_chunk);
+        for (a, b) in self.name_lengths.iter_mut().zip(other.name_lengths) {
+            *a += b;
+        }
+        for (a, b) in self.delimiters.iter_mut().zip(other.delimiters) {
+            *a += b;
+        }
+    }
+
+    fn empty() -> Self {
+        Self {
+            value_min: i32::MAX,
+            value_max: i32::MIN,
+            name_lengths: vec![0; MAX_NAME_LEN + 2],
+            ..Default::default()
+        }
+    }
+}
+
+fn scan_chunk(chunk: &[u8]) -> Profile {
+    let mut p = Profile::empty();
+    for line in chunk.split(|&b| b == NEWLINE) {
+        if line.is_empty() {
+            continue;
+        }
+        p.rows += 1;
+        let semis = line.iter().filter(|&&b| b == SEMICOLON).count();
+        p.delimiters[semis.min(2)] += 1;
+
+        let Some(at) = line.iter().position(|&b| b == SEMICOLON) else {
+            p.invalid_rows += 1;
+            continue;
+        };
+        p.name_lengths[at.min(MAX_NAME_LEN + 1)] += 1;
+        match findlib::parse_row(line) {
+            Ok((_, v)) => {
+                p.value_min = p.value_min.min(v);
+                p.value_max = p.value_max.max(v);
+                p.value_sum += v as i64;
+            }
+            Err(_) => p.invalid_rows += 1,
+        }
+    }
+    p.rows_per_chunk.push(p.rows);
+    p
+}
+
+/// Profiles `filename`, splitting it across workers the same way
+/// [`crate::solve`] does.
+pub fn profile(filename: String) -> Result<Profile, SolveError> {
+    let file = File::open(&filename)?;
+    let mapped_file = unsafe { MmapOptions::new().map(&file) }.map_err(SolveError::Map)?;
+    Ok(profile_bytes(&mapped_file))
+}
+
+/// Like [`profile`], but over a caller-provided buffer.
+pub fn profile_bytes(data: &[u8]) -> Profile {
+    let workers = findlib::cpu::default_threads();
+    let chunks = chunk_by_newlines(data, workers);
+
+    let mut res = Profile::empty();
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|r| scope.spawn(move || scan_chunk(&data[r])))
+            .collect();
+        for handle in handles {
+            res.merge(handle.join().unwrap());
+        }
+    });
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_bytes() {
+        let p = profile_bytes(b"Hamburg;12.0\nOslo;-3.4\nbad line\nA;B;1.0\nRome;1.23\n");
+        assert_eq!(5, p.rows);
+        assert_eq!(3, p.invalid_rows);
+        assert_eq!((-34, 120), (p.value_min, p.value_max));
+        assert_eq!(4.3, p.value_mean());
+        assert_eq!(43, p.value_mean_tenths());
+        assert_eq!([1, 3, 1], p.delimiters);
+        assert_eq!(1, p.name_lengths[7]);
+        assert_eq!(2, p.name_lengths[4]);
+        assert_eq!(5, p.rows_per_chunk.iter().sum::<u64>());
+
+        // A mean of -0.03 is zero, not negative zero.
+        let p = profile_bytes(b"A;-0.1\nB;0.0\nC;-0.0\n");
+        assert_eq!(0, p.value_mean_tenths());
+    }
+}
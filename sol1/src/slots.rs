@@ -0,0 +1,98 @@
+use ahash::AHashMap;
+use findlib::StationStats;
+use std::sync::{Arc, RwLock};
+
+/// Station names learned by earlier solves, each with a fixed slot.
+///
+/// Solves over many files with the same stations (the shards of one
+/// dataset, say) can share one `Slots`: rows for a known station are counted
+/// straight into a slot array, so only the first file pays for inserting
+/// keys and allocating names. Stations first seen in a file are added once
+/// its solve finishes.
+#[derive(Default)]
+pub struct Slots {
+    index: RwLock<Arc<SlotIndex>>,
+}
+
+impl Slots {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of stations with a slot.
+    pub fn len(&self) -> usize {
+        self.index.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The index as it is now; a solve works from one snapshot throughout.
+    pub(crate) fn snapshot(&self) -> Arc<SlotIndex> {
+        self.index.read().unwrap().clone()
+    }
+
+    /// Slots for `names` up front, for when the stations are known before
+    /// any file is solved: even the first solve then counts every row
+    /// straight into its slot. Stations without rows are left out of the
+    /// results as usual.
+    pub fn with_stations<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let slots = Self::new();
+        slots.register(names);
+        slots
+    }
+
+    /// Gives a slot to each of `names` that has none yet. Solves already
+    /// running keep the slots they started with.
+    pub fn register<I, S>(&self, names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut index = self.index.write().unwrap();
+        let mut names = names.into_iter();
+        let Some(new) = names.find(|n| index.get(n.as_ref().as_bytes()).is_none()) else {
+            return;
+        };
+        // Solves running now keep their snapshot; later ones see the copy.
+        let next = Arc::make_mut(&mut index);
+        for name in std::iter::once(new).chain(names) {
+            let name = name.as_ref();
+            if next.get(name.as_bytes()).is_none() {
+                next.ids.insert(name.as_bytes().into(), next.names.len());
+                next.names.push(name.to_string());
+            }
+        }
+    }
+
+    /// Gives a slot to each station in `stats` that has none yet.
+    pub(crate) fn learn(&self, stats: &[StationStats]) {
+        self.register(stats.iter().map(|s| &s.name));
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct SlotIndex {
+    ids: AHashMap<Box<[u8]>, usize>,
+    names: Vec<String>,
+}
+
+impl SlotIndex {
+    pub(crate) fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, station: &[u8]) -> Option<usize> {
+        self.ids.get(station).copied()
+    }
+
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+}
@@ -0,0 +1,462 @@
+use super::binary::FRAME;
+use super::chunks::{ChunkLog, scan_logged};
+use super::preview::Snapshots;
+use super::slots::SlotIndex;
+use super::watermark::Watermark;
+use super::{Admit, Aggregator, Kept, NEWLINE, Table, spawn_scoped};
+use findlib::{InputFormat, Preview, Warning};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Bytes a worker claims from its range at a time. The range's lock is taken
+/// once per block, so a thief never waits for more than one block's work.
+pub(crate) const BLOCK: usize = 1 << 20;
+
+// What is left of one worker's range, in one of the inputs. The owner
+// takes blocks off the front; a thief cuts the back half off at a newline
+// and makes it its own range.
+struct Slot {
+    left: Mutex<(usize, Range<usize>)>,
+    // The owner stopped claiming blocks (see `Watermark`); thieves take
+    // all that is left.
+    parked: AtomicBool,
+}
+
+impl Slot {
+    fn new(input: usize, range: Range<usize>) -> Self {
+        Self {
+            left: Mutex::new((input, range)),
+            parked: AtomicBool::new(false),
+        }
+    }
+
+    fn next_block(
+        &self,
+        inputs: &[&[u8]],
+        block: usize,
+        format: InputFormat,
+    ) -> Option<(usize, Range<usize>)> {
+        let mut guard = self.left.lock().unwrap();
+        let (input, left) = &mut *guard;
+        if (*left).is_empty() {
+            return None;
+        }
+        let end = row_start(inputs[*input], left.start + block, left.end, format);
+        let claimed = left.start..end;
+        left.start = end;
+        Some((*input, claimed))
+    }
+}
+
+// First row start at or after `pos`, capped at `limit`: the next line for
+// line-based formats, the next frame for binary rows.
+fn row_start(data: &[u8], pos: usize, limit: usize, format: InputFormat) -> usize {
+    if pos >= limit {
+        return limit;
+    }
+    if format == InputFormat::Binary {
+        return pos.next_multiple_of(FRAME).min(limit);
+    }
+    match data[pos..limit].iter().position(|&b| b == NEWLINE) {
+        Some(i) => pos + i + 1,
+        None => limit,
+    }
+}
+
+// Splits the largest remaining range in two at a row start and hands the
+// back half to `me`. Only one lock is held at a time, so thieves cannot
+// deadlock. The victim is picked from lengths read one lock at a time, and
+// may have claimed blocks or been robbed by the time it is locked again;
+// the split is worked out afresh under that lock, so a stale pick only
+// costs balance (a smaller share, or none), never a row.
+fn steal(me: usize, slots: &[Slot], inputs: &[&[u8]], block: usize, format: InputFormat) -> bool {
+    let parked = |i: usize| slots[i].parked.load(Ordering::Relaxed);
+    let victim = (0..slots.len()).filter(|&i| i != me).max_by_key(|&i| {
+        let len = slots[i].left.lock().unwrap().1.len();
+        (parked(i) && len > 0, len)
+    });
+    let Some(victim) = victim else {
+        return false;
+    };
+
+    let stolen = {
+        let mut guard = slots[victim].left.lock().unwrap();
+        let (input, left) = &mut *guard;
+        let split = if parked(victim) {
+            left.start
+        } else if left.len() < 2 * block {
+            // Not worth splitting: the owner finishes it within two blocks.
+            return false;
+        } else {
+            row_start(
+                inputs[*input],
+                left.start + left.len() / 2,
+                left.end,
+                format,
+            )
+        };
+        if split >= left.end {
+            return false;
+        }
+        let stolen = split..left.end;
+        left.end = split;
+        (*input, stolen)
+    };
+    *slots[me].left.lock().unwrap() = stolen;
+    true
+}
+
+// Drops the pages wholly inside `range` from this process's memory. Only
+// for a read-only file mapping: a page touched again is reread from the
+// file, where anonymous memory would come back zeroed.
+#[cfg(unix)]
+fn release_pages(data: &[u8], range: Range<usize>) {
+    // SAFETY: sysconf has no preconditions.
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let base = data.as_ptr() as usize;
+    let start = (base + range.start).next_multiple_of(page);
+    let end = (base + range.end) / page * page;
+    if start < end {
+        // SAFETY: the pages lie within `data`, which the caller guarantees is
+        // mapped from a file; its bytes read the same afterwards. The
+        // advice is only a hint, so a failure is ignored.
+        unsafe { libc::madvise(start as *mut libc::c_void, end - start, libc::MADV_DONTNEED) };
+    }
+}
+
+#[cfg(not(unix))]
+fn release_pages(_: &[u8], _: Range<usize>) {}
+
+/// One of the inputs [`scan_inputs`] covers, with the gate and chunk log
+/// kept for it alone. The gates of a solve's inputs share their quotas and
+/// failure flag (see [`Admit::another`]).
+pub(crate) struct Source<'a, 's> {
+    pub(crate) data: &'a [u8],
+    pub(crate) admit: &'s Admit<'s>,
+    pub(crate) log: Option<&'s ChunkLog>,
+    // Where `data` starts if the inputs followed each other, which is
+    // where the positions of its rows start.
+    pub(crate) start: u64,
+}
+
+/// Scans `chunks` on `threads` threads, one chunk each to start with. A
+/// thread that runs out of work takes a chunk nobody started, or else half
+/// of the largest range still pending, so one slow core (a throttled or
+/// efficiency core) no longer sets the run's wall time.
+///
+/// Also returns how long it took until the last thread was running.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn scan(
+    data: &[u8],
+    chunks: Vec<Range<usize>>,
+    threads: usize,
+    block: usize,
+    admit: &Admit,
+    preview: Option<&Preview>,
+    known: Option<Arc<SlotIndex>>,
+    log: Option<&ChunkLog>,
+) -> (Vec<Vec<Aggregator>>, Duration) {
+    let source = Source {
+        data,
+        admit,
+        log,
+        start: 0,
+    };
+    let chunks = chunks.into_iter().map(|r| (0, r)).collect();
+    scan_inputs(&[source], chunks, threads, block, preview, known)
+}
+
+/// Like [`scan`], over chunks of several inputs, each chunk naming the
+/// input it is in, on at most `threads` threads. The threads and their
+/// tables are shared by all inputs. Thread `i` starts on chunk `i`; the
+/// chunks past the last thread are left for whoever runs dry first, and a
+/// thread steals from any input.
+pub(crate) fn scan_inputs(
+    sources: &[Source],
+    chunks: Vec<(usize, Range<usize>)>,
+    threads: usize,
+    block: usize,
+    preview: Option<&Preview>,
+    known: Option<Arc<SlotIndex>>,
+) -> (Vec<Vec<Aggregator>>, Duration) {
+    let inputs: Vec<&[u8]> = sources.iter().map(|s| s.data).collect();
+    let inputs = &inputs;
+    // Quotas and failures are shared, so any input's gate says when to stop.
+    let admit = sources[0].admit;
+    let slots: Vec<Slot> = chunks.into_iter().map(|(i, r)| Slot::new(i, r)).collect();
+    let slots = &slots;
+    let threads = threads.min(slots.len());
+    // Nobody owns these, so thieves take them whole, like a parked range.
+    for slot in slots.iter().skip(threads) {
+        slot.parked.store(true, Ordering::Relaxed);
+    }
+    let shared = Watermark::new(admit.opts(), threads);
+    let watermark = shared.as_ref();
+    let snapshots = preview.map(|_| Snapshots::new(threads));
+    let snapshots = snapshots.as_ref();
+    let hasher = admit.hasher();
+    let kept = Kept::of(admit.opts());
+    let hasher = &hasher;
+    let known = &known;
+    let format = admit.opts().format();
+    let begun = Instant::now();
+    let spawned = AtomicU64::new(0);
+    let spawned = &spawned;
+
+    // Copied into each thread, or run on this one if a thread cannot start.
+    let work = move |me: usize| {
+        let since = begun.elapsed().as_nanos() as u64;
+        spawned.fetch_max(since, Ordering::Relaxed);
+        let mut res = Table::new(hasher.clone(), known.clone(), kept);
+        let mut seen = 0;
+        let mut shedding = Shedding::default();
+        let keeps = |me| watermark.is_none_or(|w| w.keeps(me));
+        // Quotas are checked per block, so a met quota stops every worker
+        // within one block's work.
+        loop {
+            while !admit.is_done()
+                && keeps(me)
+                && let Some((i, r)) = slots[me].next_block(inputs, block, format)
+            {
+                let source = &sources[i];
+                res.count_from(source.data, source.start);
+                scan_logged(
+                    source.log,
+                    &mut res,
+                    r.clone(),
+                    source.data,
+                    source.admit,
+                    me,
+                );
+                if source.admit.releases_pages() {
+                    release_pages(source.data, r);
+                } else if let Some(w) = watermark {
+                    shedding.after_block(w, me, &mut res, (i, r), sources, snapshots);
+                }
+                if let Some(s) = snapshots {
+                    s.publish_if_asked(me, &mut seen, &res);
+                }
+            }
+            if !keeps(me) {
+                // Parked: what is left of the range goes to the others.
+                slots[me].parked.store(true, Ordering::Relaxed);
+                if let Some(w) = watermark {
+                    shedding.flush(w, me, &mut res, snapshots);
+                }
+                break;
+            }
+            if admit.is_done() || !steal(me, slots, inputs, block, format) {
+                break;
+            }
+        }
+        res.into_parts()
+    };
+
+    let scanned = std::thread::scope(|scope| {
+        if let (Some(s), Some(p)) = (snapshots, preview)
+            && let Err(e) = spawn_scoped(scope, move || s.run(p))
+        {
+            let error = e.to_string();
+            admit.opts().warn(Warning::NoPreviews { error });
+        }
+        let mut handles = Vec::with_capacity(threads);
+        let mut failed = None;
+        for me in 0..threads {
+            match spawn_scoped(scope, move || work(me)) {
+                Ok(h) => handles.push(h),
+                Err(e) => {
+                    failed = Some(e);
+                    break;
+                }
+            }
+        }
+        // Once a spawn fails the next ones would too. This thread runs the
+        // workers that did not start, one after another; each drains its
+        // own range and steals like the rest, so nothing is left unscanned.
+        let mut inline = Vec::new();
+        if let Some(e) = failed {
+            admit.opts().warn(Warning::FewerWorkers {
+                wanted: threads,
+                started: handles.len(),
+                error: e.to_string(),
+            });
+            inline = (handles.len()..threads).map(work).collect();
+        }
+        let mut parts: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        parts.append(&mut inline);
+        // Whatever parked workers left and nobody stole.
+        let mut rest = Table::new(hasher.clone(), known.clone(), kept);
+        for slot in slots {
+            while !admit.is_done()
+                && let Some((i, r)) = slot.next_block(inputs, block, format)
+            {
+                let source = &sources[i];
+                rest.count_from(source.data, source.start);
+                scan_logged(source.log, &mut rest, r, source.data, source.admit, 0);
+            }
+        }
+        parts.push(rest.into_parts());
+        if let Some(s) = snapshots {
+            s.finish();
+        }
+        let spawn = Duration::from_nanos(spawned.load(Ordering::Relaxed));
+        (parts, spawn)
+    });
+    let (mut parts, spawn) = scanned;
+    if let Some(flushed) = shared.and_then(Watermark::into_flushed) {
+        parts.push(flushed.into_parts());
+    }
+    (parts, spawn)
+}
+
+// One worker's part in keeping under the watermark.
+#[derive(Default)]
+struct Shedding {
+    // Crossings of the watermark this worker has shed for.
+    crossings: u64,
+    // Scanned blocks of mapped inputs whose pages are still held, with the
+    // input each is in.
+    held: Vec<(usize, Range<usize>)>,
+}
+
+impl Shedding {
+    // Polls the watermark after scanning `block` of one of `sources`. Past
+    // it, the pages of every scanned block are released; on each new
+    // crossing, the table is flushed too.
+    fn after_block<'a>(
+        &mut self,
+        w: &Watermark<'a>,
+        me: usize,
+        res: &mut Table<'a>,
+        block: (usize, Range<usize>),
+        sources: &[Source],
+        snapshots: Option<&Snapshots>,
+    ) {
+        let admit = sources[block.0].admit;
+        let crossings = w.poll(admit.opts());
+        if admit.is_mapped() {
+            match self.held.last_mut() {
+                Some((i, last)) if *i == block.0 && last.end == block.1.start => {
+                    last.end = block.1.end;
+                }
+                _ => self.held.push(block),
+            }
+            if w.is_over() {
+                for (i, r) in self.held.drain(..) {
+                    release_pages(sources[i].data, r);
+                }
+            }
+        }
+        if crossings != self.crossings {
+            self.crossings = crossings;
+            self.flush(w, me, res, snapshots);
+        }
+    }
+
+    fn flush<'a>(
+        &mut self,
+        w: &Watermark<'a>,
+        me: usize,
+        res: &mut Table<'a>,
+        snapshots: Option<&Snapshots>,
+    ) {
+        w.flush(res);
+        if let Some(s) = snapshots {
+            s.flushed(me, w.flushed_stats());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steal_splits_at_newline() {
+        let data = b"Oslo;1.0\nRome;2.0\nLima;3.0\nKyiv;4.0\n";
+        let slots = [Slot::new(0, 9..data.len()), Slot::new(0, 0..0)];
+        assert!(steal(1, &slots, &[data], 8, InputFormat::Text));
+        assert_eq!((0, 9..27), *slots[0].left.lock().unwrap());
+        assert_eq!((0, 27..36), *slots[1].left.lock().unwrap());
+        // Slot 0 now holds two lines; its middle falls inside the last one.
+        assert!(!steal(1, &slots, &[data], 8, InputFormat::Text));
+    }
+
+    #[test]
+    fn test_claims_and_steals_cover_each_row_once() {
+        // Owners claim tiny blocks while every thread steals as soon as it
+        // runs dry, so picks go stale all the time: the rows still come
+        // out exactly once each.
+        let data: Vec<u8> = b"Oslo;1.0\n".repeat(4096);
+        let inputs: &[&[u8]] = &[&data];
+        let slots: Vec<Slot> = (0..4)
+            .map(|i| match i {
+                0 => Slot::new(0, 0..data.len()),
+                _ => Slot::new(0, data.len()..data.len()),
+            })
+            .collect();
+        let claimed = Mutex::new(Vec::new());
+        std::thread::scope(|s| {
+            for me in 0..slots.len() {
+                let (slots, claimed) = (&slots, &claimed);
+                s.spawn(move || {
+                    loop {
+                        while let Some((_, r)) = slots[me].next_block(inputs, 9, InputFormat::Text)
+                        {
+                            claimed.lock().unwrap().push(r);
+                        }
+                        if !steal(me, slots, inputs, 9, InputFormat::Text) {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        let mut claimed = claimed.into_inner().unwrap();
+        claimed.sort_unstable_by_key(|r| r.start);
+        // Nothing is left unclaimed: a range too short to split is one its
+        // owner is still draining.
+        assert!(slots.iter().all(|s| s.left.lock().unwrap().1.is_empty()));
+        assert!(claimed.windows(2).all(|w| w[0].end == w[1].start));
+        assert_eq!(
+            (0, data.len()),
+            (claimed[0].start, claimed.last().unwrap().end)
+        );
+    }
+
+    #[test]
+    fn test_steal_takes_parked_range() {
+        let data = b"Oslo;1.0\nRome;2.0\nLima;3.0\nKyiv;4.0\n";
+        let slots = [
+            Slot::new(0, 9..18),
+            Slot::new(0, 18..data.len()),
+            Slot::new(0, 0..0),
+        ];
+        // Too short to split, but its owner is not coming back for it.
+        slots[0].parked.store(true, Ordering::Relaxed);
+        assert!(steal(2, &slots, &[data], 8, InputFormat::Text));
+        assert!(slots[0].left.lock().unwrap().1.is_empty());
+        assert_eq!((0, 9..18), *slots[2].left.lock().unwrap());
+    }
+
+    #[test]
+    fn test_released_pages_read_back() {
+        let path = std::env::temp_dir().join(format!("release-pages-{}.txt", std::process::id()));
+        let want: Vec<u8> = findlib::SAMPLE
+            .iter()
+            .copied()
+            .cycle()
+            .take(1 << 20)
+            .collect();
+        std::fs::write(&path, &want).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let map = unsafe { memmap2::Mmap::map(&file).unwrap() };
+        std::fs::remove_file(&path).unwrap();
+        // Unaligned ends keep their partial pages; the rest is reread.
+        release_pages(&map, 5..map.len() - 7);
+        release_pages(&map, 0..map.len());
+        assert!(want == map[..]);
+    }
+}
@@ -0,0 +1,278 @@
+//! Input that can only be read front to back once: pipes, terminals,
+//! sockets, and files too large to keep in memory (see
+//! `SolveOptions::max_resident_bytes`). A reader thread fills buffers while
+//! the workers scan the previous one, and each buffer ends at a row
+//! boundary; the partial row after it is carried over to the next.
+
+use super::binary::FRAME;
+use super::chunks::ChunkLog;
+use super::{Admit, Aggregator, NEWLINE, Slots, check_options, into_stats, scan_all, spawn_scoped};
+use ahash::AHashMap;
+use findlib::{InputFormat, SolveError, StationStats, Timings, Validation, Warning};
+use std::fs::File;
+use std::io::{self, Read};
+use std::sync::{Mutex, mpsc};
+use std::time::Instant;
+
+// Bytes read before a buffer is handed to the workers. A multiple of
+// `FRAME`, so binary buffers end on a frame boundary.
+const BUFFER: usize = 64 << 20;
+
+pub(crate) fn solve_stats(
+    reader: impl Read + Send,
+    opts: &crate::SolveOptions,
+    slots: Option<&Slots>,
+) -> Result<Vec<StationStats>, SolveError> {
+    solve_stats_buffered(reader, opts, slots, BUFFER, 0)
+}
+
+// Like `solve_stats`, for a file larger than `opts.max_resident()`: its
+// two buffers together stay within that.
+pub(crate) fn solve_stats_windowed(
+    file: File,
+    opts: &crate::SolveOptions,
+    slots: Option<&Slots>,
+) -> Result<Vec<StationStats>, SolveError> {
+    let buffer = opts.max_resident().map_or(BUFFER, |max| (max / 2).max(1));
+    let len = file.metadata()?.len();
+    solve_stats_buffered(file, opts, slots, buffer, len)
+}
+
+// `len` is the input's size if known, else 0, for progress reports.
+fn solve_stats_buffered(
+    reader: impl Read + Send,
+    opts: &crate::SolveOptions,
+    slots: Option<&Slots>,
+    buffer: usize,
+    len: u64,
+) -> Result<Vec<StationStats>, SolveError> {
+    check_options(opts)?;
+    if opts.format() == InputFormat::Arrow {
+        return Err(SolveError::Unsupported(
+            "arrow input needs a regular, uncompressed file read whole".into(),
+        ));
+    }
+    if opts.preview().is_some() {
+        return Err(SolveError::Unsupported(
+            "previews are not supported for streamed input".into(),
+        ));
+    }
+    let admit = Admit::new(opts).input_len(len);
+    let known = slots.map(Slots::snapshot);
+    let log = opts.chunks().map(|_| ChunkLog::default());
+    let format = opts.format();
+
+    // Locked by the reader thread, or by this one if that cannot start.
+    let reader = Mutex::new(reader);
+    let mut res: AHashMap<String, Aggregator> = AHashMap::new();
+    let mut timings = Timings::default();
+    let (mut offset, mut lines) = (0, 0);
+    // Scans one buffer into `res`; true once every quota is met.
+    let mut consume = |buf: &[u8], timings: &mut Timings| {
+        let logged = log.as_ref().map_or(0, ChunkLog::len);
+        let failed = admit.failures();
+        let scan_start = Instant::now();
+        let (parts, spawn) = scan_all(buf, opts, &admit, known.clone(), log.as_ref());
+        let merge_start = Instant::now();
+        timings.spawn += spawn;
+        timings.scan += merge_start - scan_start - spawn;
+        for mut a in parts.into_iter().flatten() {
+            if let Some(last) = &mut a.last {
+                last.rebase(0, offset as u64);
+            }
+            match res.get_mut(&a.name) {
+                Some(mine) => mine.merge(&a),
+                None => {
+                    res.insert(a.name.clone(), a);
+                }
+            }
+        }
+        timings.merge += merge_start.elapsed();
+        if let Some(log) = &log {
+            log.rebase(logged, offset);
+        }
+        admit.rebase_failures(failed, offset);
+        admit.locate_rejection(buf, offset, lines);
+        offset += buf.len();
+        // Only a rejected row needs line numbers.
+        if opts.validation() == Validation::Strict {
+            lines += buf.iter().filter(|&&b| b == NEWLINE).count() as u64;
+        }
+        admit.is_done()
+    };
+
+    let read = std::thread::scope(|scope| {
+        // Two buffers: one being filled while the other is scanned.
+        let (full_tx, full_rx) = mpsc::sync_channel::<Vec<u8>>(1);
+        let (free_tx, free_rx) = mpsc::channel::<Vec<u8>>();
+        for _ in 0..2 {
+            free_tx.send(Vec::with_capacity(buffer)).unwrap();
+        }
+        let ahead = spawn_scoped(scope, {
+            let reader = &reader;
+            move || -> io::Result<()> {
+                let mut reader = reader.lock().unwrap();
+                let mut carry = Vec::new();
+                // The workers stopped early (every quota met) once the free
+                // buffers stop coming back; that is not an error.
+                while let Ok(mut buf) = free_rx.recv() {
+                    let eof = fill(&mut *reader, &mut buf, &mut carry, buffer, format)?;
+                    if !buf.is_empty() && full_tx.send(buf).is_err() {
+                        break;
+                    }
+                    if eof {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+        });
+        let ahead = match ahead {
+            Ok(ahead) => ahead,
+            Err(e) => {
+                opts.warn(Warning::NoReadAhead {
+                    error: e.to_string(),
+                });
+                let mut reader = reader.lock().unwrap();
+                return read_in_turn(&mut *reader, buffer, format, &mut timings, consume);
+            }
+        };
+
+        let mut waited = Instant::now();
+        for buf in full_rx {
+            timings.load += waited.elapsed();
+            if consume(&buf, &mut timings) {
+                break;
+            }
+            // Fails once the reader is past the end; whatever it sent
+            // before that is still drained.
+            let _ = free_tx.send(buf);
+            waited = Instant::now();
+        }
+        drop(free_tx);
+        ahead.join().unwrap()
+    });
+    read?;
+    admit.finish()?;
+
+    let merge_start = Instant::now();
+    let stats = into_stats(res.into_values().collect(), opts);
+    timings.merge += merge_start.elapsed();
+    if let (Some(log), Some(sink)) = (log, opts.chunks()) {
+        log.report(sink);
+    }
+    if let Some(sink) = opts.timings() {
+        (sink.0)(&timings);
+    }
+    Ok(stats)
+}
+
+// Without a reader thread: fills one buffer, scans it, and so on.
+fn read_in_turn(
+    reader: &mut impl Read,
+    buffer: usize,
+    format: InputFormat,
+    timings: &mut Timings,
+    mut consume: impl FnMut(&[u8], &mut Timings) -> bool,
+) -> io::Result<()> {
+    let (mut buf, mut carry) = (Vec::with_capacity(buffer), Vec::new());
+    loop {
+        let load_start = Instant::now();
+        let eof = fill(reader, &mut buf, &mut carry, buffer, format)?;
+        timings.load += load_start.elapsed();
+        if (!buf.is_empty() && consume(&buf, timings)) || eof {
+            return Ok(());
+        }
+    }
+}
+
+// Refills `buf` with the next rows: the partial row carried over from the
+// last call, then reads up to about `buffer` bytes, cut after the last
+// whole row. The rest moves to `carry`. Returns whether the input ended.
+fn fill(
+    reader: &mut impl Read,
+    buf: &mut Vec<u8>,
+    carry: &mut Vec<u8>,
+    buffer: usize,
+    format: InputFormat,
+) -> io::Result<bool> {
+    buf.clear();
+    buf.append(carry);
+    // Keep reading until the buffer holds a whole row: a row longer than
+    // the buffer just makes it grow.
+    let (mut cut, mut eof) = (0, false);
+    while cut == 0 && !eof {
+        let want = buffer.saturating_sub(buf.len()).max(buffer / 2).max(1);
+        eof = reader.take(want as u64).read_to_end(buf)? < want;
+        cut = if eof { buf.len() } else { row_end(buf, format) };
+    }
+    carry.extend_from_slice(&buf[cut..]);
+    buf.truncate(cut);
+    Ok(eof)
+}
+
+// End of the last whole row in `buf`, or 0 if it holds none yet.
+fn row_end(buf: &[u8], format: InputFormat) -> usize {
+    match format {
+        InputFormat::Binary => buf.len() / FRAME * FRAME,
+        _ => buf.iter().rposition(|&b| b == NEWLINE).map_or(0, |i| i + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SolveOptions, solve_stats_bytes};
+
+    #[test]
+    fn test_carries_rows_across_buffers() {
+        let data = findlib::SAMPLE;
+        let want = solve_stats_bytes(data).unwrap();
+        // Reads of a few bytes at a time, as from a slow pipe.
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = buf.len().min(self.0.len()).min(7);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+        let opts = SolveOptions::default().threads(2);
+        for buffer in [10, 4096, 1 << 20] {
+            let got = solve_stats_buffered(Trickle(data), &opts, None, buffer, 0).unwrap();
+            assert_eq!(want, got, "buffer {buffer}");
+        }
+    }
+
+    #[test]
+    fn test_numbers_rejected_line_across_buffers() {
+        let data = b"Oslo;1.0\nBern;2.0\nRome;3.0\nRome;3.0\nA;1;2.0\nOslo;1.0\n";
+        let opts = SolveOptions::default().validate(findlib::Validation::Strict);
+        for buffer in [10, 4096] {
+            let err = solve_stats_buffered(&data[..], &opts, None, buffer, 0).unwrap_err();
+            assert_eq!(
+                "line 5 (byte 36): more than one ';'",
+                err.to_string(),
+                "buffer {buffer}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_reads_in_turn_without_reader_thread() {
+        let data = findlib::SAMPLE;
+        let want = solve_stats_bytes(data).unwrap();
+        let warnings = findlib::Warnings::new();
+        let opts = SolveOptions::default().collect_warnings(&warnings);
+        crate::SPAWN_BUDGET.set(Some(0));
+        let got = solve_stats_buffered(data, &opts, None, 4096, 0);
+        crate::SPAWN_BUDGET.set(None);
+        assert_eq!(want, got.unwrap());
+        let warnings = warnings.take();
+        assert!(!warnings.is_empty(), "no warning");
+        for w in &warnings {
+            assert!(matches!(w, Warning::NoReadAhead { .. }), "{w}");
+        }
+    }
+}
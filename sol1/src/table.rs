@@ -0,0 +1,183 @@
+use super::slots::SlotIndex;
+use super::{Aggregator, Kept, NUM_STATIONS};
+use ahash::{AHashMap, RandomState};
+use findlib::StationStats;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::sync::Arc;
+
+/// One worker's aggregates. Rows for stations a [`crate::Slots`] already
+/// knows go straight to their slot; the rest are keyed by the name bytes in
+/// the input.
+pub(crate) struct Table<'a> {
+    slots: Option<Arc<SlotIndex>>,
+    known: Vec<Aggregator>,
+    fresh: AHashMap<&'a [u8], Aggregator>,
+    kept: Kept,
+    // Added to a row's address to give its position; see `count_from`.
+    shift: u64,
+}
+
+impl<'a> Table<'a> {
+    pub(crate) fn new(hasher: RandomState, slots: Option<Arc<SlotIndex>>, kept: Kept) -> Self {
+        let known = slots.as_ref().map_or(0, |s| s.len());
+        Self {
+            slots,
+            known: (0..known).map(|_| Aggregator::new(kept)).collect(),
+            fresh: AHashMap::with_capacity_and_hasher(NUM_STATIONS, hasher),
+            kept,
+            shift: 0,
+        }
+    }
+
+    /// Makes the positions of rows recorded from now on count from `start`
+    /// at the first byte of `data`, rather than be addresses, so that rows
+    /// of several inputs order as if the inputs followed each other.
+    pub(crate) fn count_from(&mut self, data: &[u8], start: u64) {
+        self.shift = start.wrapping_sub(data.as_ptr() as u64);
+    }
+
+    #[inline]
+    pub(crate) fn record(&mut self, station: &'a [u8], val: i32) {
+        // Keys borrow their row from the input, so where they are orders
+        // the rows; `count_from` makes that an offset.
+        let pos = (station.as_ptr() as u64).wrapping_add(self.shift);
+        if let Some(slots) = &self.slots
+            && let Some(slot) = slots.get(station)
+        {
+            self.known[slot].add(val, pos);
+            return;
+        }
+        // Unnamed until `into_parts` or `to_stats`: the row loop only ever
+        // borrows the key from the input.
+        let kept = self.kept;
+        self.fresh
+            .entry(station)
+            .or_insert_with(|| Aggregator::new(kept))
+            .add(val, pos);
+    }
+
+    /// Like [`Self::record`], for keys that are not the name itself (e.g. an
+    /// escaped JSON string): `name` is only called for a key's first row.
+    /// Entries that share a name are merged with the other workers' parts.
+    #[inline]
+    pub(crate) fn record_as(&mut self, key: &'a [u8], val: i32, name: impl FnOnce() -> String) {
+        let kept = self.kept;
+        let entry = self
+            .fresh
+            .entry(key)
+            .or_insert_with(|| Aggregator::new(kept));
+        if entry.name.is_empty() {
+            entry.name = name();
+        }
+        entry.add(val, (key.as_ptr() as u64).wrapping_add(self.shift));
+    }
+
+    /// An empty table with the same hasher and slots.
+    pub(crate) fn empty_like(&self) -> Self {
+        let mut table = Self::new(self.fresh.hasher().clone(), self.slots.clone(), self.kept);
+        table.shift = self.shift;
+        table
+    }
+
+    /// Folds `other`, built with [`Self::empty_like`], into this table.
+    pub(crate) fn absorb(&mut self, other: Table<'a>) {
+        for (mine, theirs) in self.known.iter_mut().zip(&other.known) {
+            mine.merge(theirs);
+        }
+        for (key, theirs) in other.fresh {
+            match self.fresh.entry(key) {
+                Entry::Occupied(mut e) => e.get_mut().merge(&theirs),
+                Entry::Vacant(e) => {
+                    e.insert(theirs);
+                }
+            }
+        }
+    }
+
+    /// Rows recorded so far, and how many keys they fell on.
+    pub(crate) fn rows_and_keys(&self) -> (u64, usize) {
+        let counts = self.known.iter().chain(self.fresh.values());
+        counts
+            .filter(|a| a.count > 0)
+            .fold((0, 0), |(rows, keys), a| (rows + a.count, keys + 1))
+    }
+
+    fn known(&self) -> impl Iterator<Item = (&str, &Aggregator)> {
+        let names = self.slots.iter().flat_map(|s| s.names());
+        names.zip(&self.known).filter(|(_, a)| a.count > 0)
+    }
+
+    pub(crate) fn to_stats(&self) -> Vec<StationStats> {
+        let known = self.known().map(|(name, a)| StationStats {
+            name: name.to_string(),
+            ..a.to_stats()
+        });
+        let fresh = self.fresh.iter().map(|(key, a)| StationStats {
+            name: fresh_name(key, a),
+            ..a.to_stats()
+        });
+        known.chain(fresh).collect()
+    }
+
+    pub(crate) fn into_parts(self) -> Vec<Aggregator> {
+        let mut parts: Vec<Aggregator> = self
+            .known()
+            .map(|(name, a)| Aggregator {
+                name: name.to_string(),
+                dist: a.dist.clone(),
+                last: a.last.clone(),
+                ..*a
+            })
+            .collect();
+        parts.extend(self.fresh.into_iter().map(|(key, mut a)| {
+            if a.name.is_empty() {
+                let name = String::from_utf8_lossy(key);
+                a.lossy = matches!(name, Cow::Owned(_));
+                a.name = name.into_owned();
+            }
+            a
+        }));
+        parts
+    }
+}
+
+// The name of a fresh entry: the one `record_as` gave it, or else its key.
+fn fresh_name(key: &[u8], a: &Aggregator) -> String {
+    if a.name.is_empty() {
+        String::from_utf8_lossy(key).into_owned()
+    } else {
+        a.name.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_fresh_keys_on_the_way_out() {
+        let data = b"Oslo\"\\u00d8\"";
+        let mut table = Table::new(RandomState::new(), None, Kept::default());
+        table.record(&data[..4], 10);
+        table.record(&data[..4], -10);
+        table.record_as(&data[4..], 5, || "Ø".to_string());
+        // Plain keys stay unnamed while rows come in.
+        assert!(table.fresh[&data[..4]].name.is_empty());
+
+        let mut names: Vec<_> = table
+            .to_stats()
+            .into_iter()
+            .map(|s| (s.name, s.count))
+            .collect();
+        names.sort();
+        assert_eq!(vec![("Oslo".to_string(), 2), ("Ø".to_string(), 1)], names);
+        let mut parts: Vec<_> = table
+            .into_parts()
+            .into_iter()
+            .map(|a| (a.name, a.sum))
+            .collect();
+        parts.sort();
+        assert_eq!(vec![("Oslo".to_string(), 0), ("Ø".to_string(), 5)], parts);
+    }
+}
@@ -0,0 +1,132 @@
+//! The soft memory watermark (see `SolveOptions::memory_watermark`).
+//! Workers poll the process's resident set between blocks. Each time it
+//! passes the mark they shed what they can without losing progress: the
+//! pages of blocks already scanned go back to the kernel, every table is
+//! flushed into one shared table, and half the workers stop claiming
+//! blocks, leaving their ranges to the others.
+
+use super::Table;
+use findlib::{SolveOptions, Warning};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+pub(crate) struct Watermark<'a> {
+    limit: u64,
+    over: AtomicBool,
+    // Times the mark was passed; a worker sheds once per crossing.
+    crossings: AtomicU64,
+    // Workers below this index keep claiming blocks.
+    active: AtomicUsize,
+    flushed: Mutex<Option<Table<'a>>>,
+}
+
+impl<'a> Watermark<'a> {
+    /// The watermark `opts` sets for `workers` workers, if any, and if this
+    /// platform reports the resident set; warns if it does not.
+    pub(crate) fn new(opts: &SolveOptions, workers: usize) -> Option<Self> {
+        let limit = opts.watermark()?;
+        if let Err(error) = resident_bytes() {
+            opts.warn(Warning::NoWatermark { error });
+            return None;
+        }
+        Some(Self {
+            limit,
+            over: AtomicBool::new(false),
+            crossings: AtomicU64::new(0),
+            active: AtomicUsize::new(workers),
+            flushed: Mutex::default(),
+        })
+    }
+
+    /// Checks the resident set, and reports a crossing the first worker to
+    /// see it. Returns the number of crossings so far.
+    pub(crate) fn poll(&self, opts: &SolveOptions) -> u64 {
+        let resident = resident_bytes().unwrap_or(0);
+        let over = resident >= self.limit;
+        if over != self.over.load(Ordering::Relaxed)
+            && self
+                .over
+                .compare_exchange(!over, over, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            && over
+        {
+            let workers = (self.active.load(Ordering::Relaxed) / 2).max(1);
+            self.active.store(workers, Ordering::Relaxed);
+            self.crossings.fetch_add(1, Ordering::Relaxed);
+            opts.warn(Warning::Watermark {
+                resident,
+                watermark: self.limit,
+                workers,
+            });
+        }
+        self.crossings.load(Ordering::Relaxed)
+    }
+
+    /// Whether the resident set was past the mark when last polled.
+    pub(crate) fn is_over(&self) -> bool {
+        self.over.load(Ordering::Relaxed)
+    }
+
+    /// Whether worker `me` still claims blocks.
+    pub(crate) fn keeps(&self, me: usize) -> bool {
+        me < self.active.load(Ordering::Relaxed)
+    }
+
+    /// Moves everything in `table` into the shared table, leaving it empty.
+    pub(crate) fn flush(&self, table: &mut Table<'a>) {
+        let mine = std::mem::replace(table, table.empty_like());
+        let mut flushed = self.flushed.lock().unwrap();
+        flushed
+            .get_or_insert_with(|| mine.empty_like())
+            .absorb(mine);
+    }
+
+    /// The shared table, for previews.
+    pub(crate) fn flushed_stats(&self) -> Vec<findlib::StationStats> {
+        let flushed = self.flushed.lock().unwrap();
+        flushed.as_ref().map_or_else(Vec::new, Table::to_stats)
+    }
+
+    pub(crate) fn into_flushed(self) -> Option<Table<'a>> {
+        self.flushed.into_inner().unwrap()
+    }
+}
+
+// This process's resident set in bytes, from the second field of
+// /proc/self/statm (in pages).
+#[cfg(target_os = "linux")]
+fn resident_bytes() -> Result<u64, String> {
+    let statm = std::fs::read_to_string("/proc/self/statm")
+        .map_err(|e| format!("reading /proc/self/statm: {e}"))?;
+    let pages: u64 = statm
+        .split_whitespace()
+        .nth(1)
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| format!("unexpected /proc/self/statm: {}", statm.trim_end()))?;
+    // SAFETY: sysconf has no preconditions.
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    let page = u64::try_from(page).map_err(|_| "no page size".to_string())?;
+    Ok(pages * page)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_bytes() -> Result<u64, String> {
+    Err("the resident set is only read on Linux".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_resident_bytes() {
+        let before = resident_bytes().unwrap();
+        assert!(before > 0);
+        // Touching 64 MiB shows up.
+        let big = vec![1u8; 64 << 20];
+        let after = resident_bytes().unwrap();
+        assert!(after >= before + (32 << 20), "{before} -> {after}");
+        drop(std::hint::black_box(big));
+    }
+}
@@ -1,4 +1,6 @@
 use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use findlib::{FlamegraphProfiler, Stat, StationStats};
+use sol2::{Kernel, SolveOptions};
 use std::fs;
 use std::hint::black_box;
 use std::path::PathBuf;
@@ -11,24 +13,98 @@ fn criterion_benchmark(c: &mut Criterion) {
     group.sample_size(100);
     group.throughput(Throughput::Bytes(size));
 
-    group.bench_function(BenchmarkId::from_parameter("measurements.txt"), |b| {
-        b.iter_batched(
-            || path.to_string_lossy().to_string(),
-            |filename| {
-                let out = sol2::solve(filename).unwrap();
-                black_box(out);
+    // One run per kernel the CPU supports, so numbers from different
+    // machines can be compared on the same code path.
+    for kernel in Kernel::ALL {
+        if !kernel.is_supported() {
+            continue;
+        }
+        let opts = SolveOptions::default().force_kernel(kernel);
+        group.bench_function(
+            BenchmarkId::new(kernel.to_string(), "measurements.txt"),
+            |b| {
+                b.iter_batched(
+                    || path.to_string_lossy().to_string(),
+                    |filename| {
+                        let out = sol2::solve_with(filename, &opts).unwrap();
+                        black_box(out);
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
             },
-            criterion::BatchSize::SmallInput,
-        )
+        );
+    }
+
+    group.finish();
+}
+
+// The built-in sample from memory, so there is a number to compare without
+// generating `data/measurements.txt` and without disk reads in the loop.
+fn sample_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sol2-bytes");
+    group.throughput(Throughput::Bytes(findlib::SAMPLE.len() as u64));
+    group.bench_function(BenchmarkId::from_parameter("sample"), |b| {
+        b.iter(|| black_box(sol2::solve_bytes(black_box(findlib::SAMPLE)).unwrap()))
     });
+    group.finish();
+}
+
+// Far more stations than the 10K the rules allow, so the table merge and
+// the final sort by name weigh against the scan.
+fn cardinality_benchmark(c: &mut Criterion) {
+    let stations = 200_000u64;
+    let mut data = Vec::new();
+    for i in 0..stations * 4 {
+        let name = (i % stations).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 40;
+        let value = (i % 1999) as i64 - 999;
+        let sign = if value < 0 { "-" } else { "" };
+        let value = value.abs();
+        let row = format!("S{name:07};{sign}{}.{}\n", value / 10, value % 10);
+        data.extend_from_slice(row.as_bytes());
+    }
 
+    let mut group = c.benchmark_group("sol2-cardinality");
+    group.sample_size(20);
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function(BenchmarkId::from_parameter("200k-stations"), |b| {
+        b.iter(|| black_box(sol2::solve_stats_bytes(black_box(&data)).unwrap()))
+    });
+    group.finish();
+}
+
+// Output phase alone, at the 10K-station upper bound where it stops being
+// noise next to parsing.
+fn format_benchmark(c: &mut Criterion) {
+    let stats: Vec<StationStats> = (0..10_000)
+        .map(|i| {
+            StationStats::new(
+                format!("Station {i:05}"),
+                -999 + (i % 50),
+                999 - (i % 70),
+                123_456 * (i as i64 % 7 - 3),
+                1_000 + i as u64,
+            )
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("format");
+    group.throughput(Throughput::Elements(stats.len() as u64));
+    let mut out = String::new();
+    group.bench_function(BenchmarkId::from_parameter("10k-stations"), |b| {
+        b.iter(|| {
+            out.clear();
+            findlib::write_selected(&mut out, black_box(&stats), Stat::DEFAULT);
+            black_box(out.len());
+        })
+    });
     group.finish();
 }
 
 criterion_group!(
     name = benches;
-    config = Criterion::default();
-    targets = criterion_benchmark,
+    // `cargo bench -- --profile-time 10` writes a flamegraph per benchmark.
+    config = Criterion::default().with_profiler(FlamegraphProfiler::new(100));
+    targets = criterion_benchmark, sample_benchmark, cardinality_benchmark, format_benchmark,
 );
 
 criterion_main!(benches);
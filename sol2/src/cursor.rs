@@ -0,0 +1,83 @@
+//! The solver's row scanning on its own, for tools (samplers, validators,
+//! converters) that want rows rather than aggregates.
+
+use crate::TailReader;
+use findlib::{Kernel, SolveError};
+
+/// Iterates over the `name;value` rows of `data` as `(name, value, offset)`,
+/// where `offset` is where the row starts, using the same kernels and the
+/// same trust in the input as the solver: a row is everything up to the
+/// next `;`, and its value runs to the newline after it. So a blank line or
+/// one without `;` is not reported but ends up at the front of the next
+/// row's name, and a trailing fragment without `;` is dropped. Check rows
+/// with [`findlib::parse_row`] where the input may be malformed.
+///
+/// Nothing is copied: `data` can be a memory map of the whole input.
+pub struct LineCursor<'a> {
+    reader: TailReader<'a>,
+    pos: usize,
+}
+
+impl<'a> LineCursor<'a> {
+    /// A cursor using the best kernel for this CPU.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            reader: TailReader::new(data, Kernel::detect()),
+            pos: 0,
+        }
+    }
+
+    /// A cursor using `kernel`, which must be supported on this CPU.
+    pub fn with_kernel(data: &'a [u8], kernel: Kernel) -> Result<Self, SolveError> {
+        if !kernel.is_supported() {
+            let e = format!("the {kernel} kernel is not supported on this CPU");
+            return Err(SolveError::Unsupported(e));
+        }
+        Ok(Self {
+            reader: TailReader::new(data, kernel),
+            pos: 0,
+        })
+    }
+}
+
+impl<'a> Iterator for LineCursor<'a> {
+    type Item = (&'a [u8], &'a [u8], usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.pos;
+        let semi = self.reader.find_delimiter(start)?;
+        let (_, len, next) = self.reader.value(semi);
+        self.pos = next;
+        let data = self.reader.data;
+        Some((&data[start..semi], &data[semi + 1..semi + 1 + len], start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_cursor() {
+        let data = b"Oslo;-3.2\nLas Palmas de Gran Canaria;12.0\nRome;1.0";
+        for kernel in Kernel::ALL {
+            let Ok(cursor) = LineCursor::with_kernel(data, kernel) else {
+                assert!(!kernel.is_supported());
+                continue;
+            };
+            let rows: Vec<_> = cursor.collect();
+            assert_eq!(
+                vec![
+                    (&b"Oslo"[..], &b"-3.2"[..], 0),
+                    (b"Las Palmas de Gran Canaria", b"12.0", 10),
+                    (b"Rome", b"1.0", 42),
+                ],
+                rows,
+                "{kernel}"
+            );
+        }
+        // Trusted like the solver: the blank line joins the next name.
+        let rows: Vec<_> = LineCursor::new(b"A;1.0\n\nB;2.0\nC").collect();
+        assert_eq!(vec![(&b"A"[..], &b"1.0"[..], 0), (b"\nB", b"2.0", 6)], rows);
+    }
+}
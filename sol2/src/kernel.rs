@@ -0,0 +1,166 @@
+//! Searching and counting single bytes, one code path per [`Kernel`]. The
+//! vector loops only cover whole vectors and report how far they got; the
+//! dispatchers finish the tail a byte at a time.
+
+use findlib::Kernel;
+
+const LOW7: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+
+/// Offset of the first `needle` in `data`, if any. Callers must have checked
+/// `kernel.is_supported()`.
+pub(crate) fn find_byte(kernel: Kernel, data: &[u8], needle: u8) -> Option<usize> {
+    let done = match kernel {
+        Kernel::Scalar => 0,
+        Kernel::Swar => match find_byte_swar(data, needle) {
+            Ok(i) => return Some(i),
+            Err(done) => done,
+        },
+        // SAFETY: only constructed with Avx2 when the CPU has it.
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => match unsafe { find_byte_avx2(data, needle) } {
+            Ok(i) => return Some(i),
+            Err(done) => done,
+        },
+        #[cfg(not(target_arch = "x86_64"))]
+        Kernel::Avx2 => unreachable!("avx2 kernel on a non-x86_64 target"),
+    };
+    data[done..]
+        .iter()
+        .position(|&b| b == needle)
+        .map(|i| done + i)
+}
+
+/// How often `needle` occurs in `data`. Callers must have checked
+/// `kernel.is_supported()`.
+pub(crate) fn count_byte(kernel: Kernel, data: &[u8], needle: u8) -> usize {
+    let (count, done) = match kernel {
+        Kernel::Scalar => (0, 0),
+        Kernel::Swar => count_byte_swar(data, needle),
+        // SAFETY: only constructed with Avx2 when the CPU has it.
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe { count_byte_avx2(data, needle) },
+        #[cfg(not(target_arch = "x86_64"))]
+        Kernel::Avx2 => unreachable!("avx2 kernel on a non-x86_64 target"),
+    };
+    count + data[done..].iter().filter(|&&b| b == needle).count()
+}
+
+// The high bit of every byte of `word` that equals `needle`, and no others.
+// Unlike the borrow-based test in `find_delimiter`, a match cannot set the
+// bit of the byte above it, so the result can be counted as well as searched.
+#[inline]
+fn matches_swar(word: u64, needle: u8) -> u64 {
+    let x = word ^ (u64::from(needle) * 0x0101_0101_0101_0101);
+    !(((x & LOW7) + LOW7) | x | LOW7)
+}
+
+fn find_byte_swar(data: &[u8], needle: u8) -> Result<usize, usize> {
+    let words = data.chunks_exact(8);
+    let done = data.len() - words.remainder().len();
+    for (i, word) in words.enumerate() {
+        let m = matches_swar(u64::from_le_bytes(word.try_into().unwrap()), needle);
+        if m != 0 {
+            return Ok(i * 8 + (m.trailing_zeros() / 8) as usize);
+        }
+    }
+    Err(done)
+}
+
+fn count_byte_swar(data: &[u8], needle: u8) -> (usize, usize) {
+    let words = data.chunks_exact(8);
+    let done = data.len() - words.remainder().len();
+    let count = words
+        .map(|w| matches_swar(u64::from_le_bytes(w.try_into().unwrap()), needle).count_ones())
+        .sum::<u32>();
+    (count as usize, done)
+}
+
+/// Offset of the first `needle` in `data`, 32 bytes per step, or how far
+/// the whole vectors reached.
+///
+/// # Safety
+///
+/// The CPU must support AVX2.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn find_byte_avx2(data: &[u8], needle: u8) -> Result<usize, usize> {
+    use std::arch::x86_64::*;
+
+    let needle = _mm256_set1_epi8(needle as i8);
+    let mut pos = 0;
+    while pos + 32 <= data.len() {
+        // SAFETY: pos + 32 <= data.len(), and loadu has no alignment needs.
+        let v = unsafe { _mm256_loadu_si256(data.as_ptr().add(pos) as *const __m256i) };
+        let mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, needle)) as u32;
+        if mask != 0 {
+            return Ok(pos + mask.trailing_zeros() as usize);
+        }
+        pos += 32;
+    }
+    Err(pos)
+}
+
+/// Occurrences of `needle` in the whole 32-byte vectors of `data`, and how
+/// far those reached.
+///
+/// # Safety
+///
+/// The CPU must support AVX2.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn count_byte_avx2(data: &[u8], needle: u8) -> (usize, usize) {
+    use std::arch::x86_64::*;
+
+    let needle = _mm256_set1_epi8(needle as i8);
+    let (mut count, mut pos) = (0, 0);
+    while pos + 32 <= data.len() {
+        // A match is -1, so subtracting counts it. The byte counters are
+        // folded into 64-bit sums before they can wrap, every 255 vectors.
+        let mut acc = _mm256_setzero_si256();
+        let mut round = 0;
+        while round < 255 && pos + 32 <= data.len() {
+            // SAFETY: pos + 32 <= data.len(), and loadu has no alignment needs.
+            let v = unsafe { _mm256_loadu_si256(data.as_ptr().add(pos) as *const __m256i) };
+            acc = _mm256_sub_epi8(acc, _mm256_cmpeq_epi8(v, needle));
+            pos += 32;
+            round += 1;
+        }
+        let sums = _mm256_sad_epu8(acc, _mm256_setzero_si256());
+        count += (_mm256_extract_epi64::<0>(sums)
+            + _mm256_extract_epi64::<1>(sums)
+            + _mm256_extract_epi64::<2>(sums)
+            + _mm256_extract_epi64::<3>(sums)) as usize;
+    }
+    (count, pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernels_find_and_count() {
+        // Long enough for several vectors and a 255-vector fold, with the
+        // needle at the edges and in the tail.
+        let mut data: Vec<u8> = (0..20_000u32)
+            .map(|i| b"abc;def\n"[i as usize % 8])
+            .collect();
+        data.extend_from_slice(b"xyz;");
+        let want = data.iter().filter(|&&b| b == b'\n').count();
+        for kernel in Kernel::ALL {
+            if !kernel.is_supported() {
+                continue;
+            }
+            assert_eq!(want, count_byte(kernel, &data, b'\n'), "{kernel}");
+            assert_eq!(2501, count_byte(kernel, &data, b';'), "{kernel}");
+            assert_eq!(0, count_byte(kernel, &data, b'!'), "{kernel}");
+            for start in [0, 1, 7, 8, 33, 19_990] {
+                let rest = &data[start..];
+                let want = rest.iter().position(|&b| b == b';');
+                assert_eq!(want, find_byte(kernel, rest, b';'), "{kernel} from {start}");
+            }
+            assert_eq!(None, find_byte(kernel, &data, b'!'), "{kernel}");
+            assert_eq!(None, find_byte(kernel, &[], b'\n'), "{kernel}");
+        }
+    }
+}
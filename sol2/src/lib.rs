@@ -1,180 +1,304 @@
+use findlib::{Progress, format_stats};
 use memmap2::MmapOptions;
 use rayon::prelude::*;
+use std::borrow::Cow;
 use std::fs::File;
+use std::hash::{BuildHasher, RandomState};
 use std::ops::Range;
 
-const OFFSET64: u64 = 14695981039346656037;
-const PRIME64: u64 = 1099511628211;
-const BUCKET_SIZE: usize = 1 << 25; // must be power of two
+mod cursor;
+mod kernel;
+mod window;
+
+pub use cursor::LineCursor;
+
+pub use findlib::{
+    Columns, Compression, FileError, InputFormat, Kernel, ParseError, SolveError, SolveOptions,
+    StationStats, Validation, Warning,
+};
+
+// Odd multiplier for folding the words of long names into their hash.
+const FOLD: u64 = 0x517c_c1b7_2722_0a95;
+// Slots a table starts with; must be a power of two. Enough for the usual
+// few hundred stations without growing. Miri and sanitizer runs start tiny
+// instead (`small-table`), so that growing and probes that wrap around the
+// end of the table get exercised too.
+#[cfg(not(any(miri, feature = "small-table")))]
+const INITIAL_SLOTS: usize = 1 << 12;
+#[cfg(any(miri, feature = "small-table"))]
+const INITIAL_SLOTS: usize = 1 << 2;
+// A probe this long means crafted names or very bad luck; past it the table
+// switches to the keyed hash rather than walk ever longer runs per row.
+const MAX_CHAIN: usize = 64;
 
 // Shifts/masks for number parsing
-const SHIFT1: u64 = 8 * 1;
+const SHIFT1: u64 = 8;
 const SHIFT2: u64 = 8 * 2;
 const SHIFT3: u64 = 8 * 3;
 const SHIFT4: u64 = 8 * 4;
 
 const CHAR_MASK0: u64 = 255;
-const CHAR_MASK1: u64 = (255u64) << SHIFT1;
-const CHAR_MASK2: u64 = (255u64) << SHIFT2;
-const CHAR_MASK3: u64 = (255u64) << SHIFT3;
-const CHAR_MASK4: u64 = (255u64) << SHIFT4;
 
-const DOT1: u64 = (b'.' as u64) << 8;
-const DOT2: u64 = (b'.' as u64) << 16;
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Hash(u64);
 
-fn round1(x: f64) -> f64 {
-    (x * 10.0).round() / 10.0
+/// Picks a key's slot. `Hash` (see `key_hash`) still decides equality;
+/// only the slot index depends on the mode and seed.
+#[derive(Clone)]
+enum Slotter {
+    /// The `Hash` with the seed mixed in, through a 64-bit finalizer.
+    Seeded(u64),
+    /// SipHash with random keys over the whole name, for untrusted input:
+    /// without the keys nobody can pick names that pile up in one run.
+    Keyed(RandomState),
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-struct Hash(u64);
+impl Slotter {
+    fn from_options(opts: &SolveOptions) -> Self {
+        if opts.is_untrusted() {
+            Slotter::Keyed(RandomState::new())
+        } else {
+            Slotter::Seeded(
+                opts.seed()
+                    .unwrap_or_else(|| RandomState::new().hash_one(0u64)),
+            )
+        }
+    }
 
-impl Hash {
+    /// Where the probe for a key starts, before masking to the table size.
     #[inline]
-    fn index(self) -> usize {
-        // FNV-like step then mask by table size
-        let mut h = OFFSET64;
-        h ^= self.0;
-        h = h.wrapping_mul(PRIME64);
-        (h & ((BUCKET_SIZE as u64) - 1)) as usize
+    fn index(&self, h: Hash, key: &[u8]) -> usize {
+        let mixed = match self {
+            Slotter::Seeded(seed) => fmix64(seed ^ h.0),
+            Slotter::Keyed(state) => state.hash_one(key),
+        };
+        mixed as usize
+    }
+}
+
+// MurmurHash3's finalizer: every input bit reaches every output bit, so
+// names that differ in a few bits of a digit (`S0000001`, `S0000002`, ...)
+// land far apart rather than in one run, and the mask can take the low
+// bits.
+#[inline]
+fn fmix64(mut h: u64) -> u64 {
+    h = (h ^ (h >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h = (h ^ (h >> 33)).wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// A key's `Hash`. A key of up to 8 bytes is its own hash, zero-padded;
+/// a longer one folds each further 8-byte word into its prefix, so names
+/// that share a prefix get apart in the table and a probe seldom has to
+/// compare names that turn out to differ.
+#[inline]
+fn key_hash(key: &[u8]) -> Hash {
+    let mut h = city_hash8_prefix(key);
+    if key.len() > 8 {
+        for word in key[8..].chunks(8) {
+            h = fold_word(h, city_hash8_prefix(word));
+        }
     }
+    Hash(h)
 }
 
 #[inline]
-fn create_hash(u: u64, p: usize) -> Hash {
-    if p >= 8 {
-        Hash(u)
+fn fold_word(h: u64, word: u64) -> u64 {
+    (h.rotate_left(5) ^ word).wrapping_mul(FOLD)
+}
+
+// The first `n` bytes of `word`, the rest zeroed.
+#[inline]
+fn low_bytes(word: u64, n: usize) -> u64 {
+    if n >= 8 {
+        word
     } else {
-        let m = (1u64 << (p << 3)) - 1;
-        Hash(u & m)
+        word & ((1u64 << (n << 3)) - 1)
     }
 }
 
-#[derive(Clone, Debug)]
-struct Node {
-    key: String,
+/// One station's aggregates, held inline in the table. The name is borrowed
+/// from the input rather than copied.
+#[derive(Clone, Copy, Debug)]
+struct Entry<'a> {
+    // `None` marks an empty slot.
+    key: Option<&'a [u8]>,
     hash: Hash,
-    next: Option<Box<Node>>,
     sum: i64,
-    count: i64,
-    min: i16,
-    max: i16,
+    count: u64,
+    // i32 rather than i16: the slot survives values past ±3276.7 and, next
+    // to the slice and the 64-bit fields, costs no space (Entry stays 48
+    // bytes).
+    min: i32,
+    max: i32,
 }
 
-impl Node {
-    fn new(key: String, hash: Hash) -> Self {
-        Self {
-            key,
-            hash,
-            next: None,
-            sum: 0,
-            count: 0,
-            min: i16::MAX,
-            max: i16::MIN,
-        }
+impl Entry<'_> {
+    const EMPTY: Self = Self {
+        key: None,
+        hash: Hash(0),
+        sum: 0,
+        count: 0,
+        min: i32::MAX,
+        max: i32::MIN,
+    };
+
+    #[inline]
+    fn add(&mut self, temp: i32) {
+        self.min = self.min.min(temp);
+        self.max = self.max.max(temp);
+        self.sum += temp as i64;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Entry) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
     }
 }
 
-struct Bucket {
-    keys: Vec<String>,
-    bucket: Vec<Option<Box<Node>>>,
+/// One worker's aggregates: a flat table of inline entries with linear
+/// probing, doubled whenever it is half full.
+struct Bucket<'a> {
+    slotter: Slotter,
+    slots: Vec<Entry<'a>>,
+    len: usize,
+    // Measurements folded into the table vs. lines in the partition; see
+    // `check_counts`.
+    records: u64,
+    lines: u64,
+    // Checked scans only: malformed rows left out, and the earliest one
+    // rejected (its input, where it starts there, and why).
+    skipped: u64,
+    rejected: Option<(usize, usize, &'static str)>,
+    // How often this table and those merged into it switched to the keyed
+    // slotter.
+    rekeyed: u64,
 }
 
-impl Bucket {
-    fn new() -> Self {
-        let mut bucket = Vec::with_capacity(BUCKET_SIZE);
-        bucket.resize_with(BUCKET_SIZE, || None);
+impl<'a> Bucket<'a> {
+    fn new(slotter: Slotter) -> Self {
         Bucket {
-            keys: Vec::new(),
-            bucket,
+            slotter,
+            slots: vec![Entry::EMPTY; INITIAL_SLOTS],
+            len: 0,
+            records: 0,
+            lines: 0,
+            skipped: 0,
+            rejected: None,
+            rekeyed: 0,
         }
     }
 
-    fn keys(&self) -> &[String] {
-        &self.keys
+    /// The occupied slots, with their keys, in slot order. Each slot holds
+    /// its key next to its aggregates, so the output phase walks the table
+    /// once without hashing or probing anything.
+    fn entries(&self) -> impl Iterator<Item = (&'a [u8], &Entry<'a>)> {
+        self.slots.iter().filter_map(|e| Some((e.key?, e)))
     }
 
-    fn find(&self, h: Hash, key: &str) -> Option<&Node> {
-        let mut curr = self.bucket[h.index()].as_deref();
-        while let Some(node) = curr {
-            if node.hash == h && (key.len() <= 8 || key == node.key) {
-                return Some(node);
+    /// The slot holding `key`, or the empty one it belongs in, and how many
+    /// other keys the probe passed on the way. The table is never more than
+    /// half full, so there always is an empty slot to stop at.
+    #[inline]
+    fn probe(&self, h: Hash, key: &[u8]) -> (usize, usize) {
+        let mask = self.slots.len() - 1;
+        let mut idx = self.slotter.index(h, key) & mask;
+        let mut passed = 0;
+        while let Some(k) = self.slots[idx].key {
+            // A key of up to 8 bytes is all in its hash; longer ones are
+            // only compared once their hashes match.
+            if self.slots[idx].hash == h && k.len() == key.len() && (key.len() <= 8 || k == key) {
+                break;
             }
-            curr = node.next.as_deref();
+            passed += 1;
+            idx = (idx + 1) & mask;
         }
-        None
+        (idx, passed)
     }
 
-    fn find_mut(&mut self, h: Hash, key: &[u8]) -> Option<&mut Node> {
-        let mut link = &mut self.bucket[h.index()];
-        while let Some(node) = link {
-            if node.hash == h && (key.len() <= 8 || key == node.key.as_bytes()) {
-                return Some(node.as_mut());
+    #[inline]
+    fn insert(&mut self, h: Hash, key: &'a [u8]) -> &mut Entry<'a> {
+        let (mut idx, passed) = self.probe(h, key);
+        if self.slots[idx].key.is_none() {
+            let rekey = passed >= MAX_CHAIN && matches!(self.slotter, Slotter::Seeded(_));
+            let grow = 2 * (self.len + 1) > self.slots.len();
+            if rekey || grow {
+                let slotter = if rekey {
+                    self.rekeyed += 1;
+                    Slotter::Keyed(RandomState::new())
+                } else {
+                    self.slotter.clone()
+                };
+                let n = self.slots.len() << usize::from(grow);
+                self.rebuild(slotter, n);
+                idx = self.probe(h, key).0;
             }
-            link = &mut node.next;
+            self.slots[idx] = Entry {
+                key: Some(key),
+                hash: h,
+                ..Entry::EMPTY
+            };
+            self.len += 1;
         }
-        None
+        &mut self.slots[idx]
     }
 
-    fn insert(&mut self, h: Hash, key: &[u8]) -> &mut Node {
-        let idx = h.index();
-
-        // Try to find existing node without holding a mutable borrow across this function
-        let found_ptr: *mut Node = {
-            let mut link = &mut self.bucket[idx];
-            loop {
-                match link {
-                    Some(node) => {
-                        if node.hash == h && (key.len() <= 8 || key == node.key.as_bytes()) {
-                            break node.as_mut() as *mut Node;
-                        }
-                        link = &mut node.next;
-                    }
-                    None => break std::ptr::null_mut(),
-                }
-            }
-        };
-        if !found_ptr.is_null() {
-            // SAFETY: `found_ptr` points into `self.bucket[idx]` and we have exclusive access via `&mut self`.
-            return unsafe { &mut *found_ptr };
+    /// Folds `other` into this table. Merging the partitions' tables
+    /// pairwise, as rayon's reduction tree does, keeps every level of the
+    /// merge as parallel as the scan.
+    fn merge(mut self, other: Bucket<'a>) -> Self {
+        for (key, e) in other.entries() {
+            self.insert(e.hash, key).merge(e);
         }
+        self.records += other.records;
+        self.lines += other.lines;
+        self.skipped += other.skipped;
+        self.rekeyed += other.rekeyed;
+        self.rejected = match (self.rejected, other.rejected) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self
+    }
 
-        // Not found: insert new node and record the key once
-        let key_string = String::from_utf8(key.to_vec()).unwrap();
-        self.keys.push(key_string.clone());
-
-        let new_node = Box::new(Node::new(String::from_utf8(key.to_vec()).unwrap(), h));
-
-        let head = &mut self.bucket[idx];
-        match head {
-            None => {
-                *head = Some(new_node);
-                return head.as_deref_mut().unwrap();
-            }
-            Some(head_node) => {
-                let mut tail = head_node.as_mut();
-                while tail.next.is_some() {
-                    tail = tail.next.as_mut().unwrap().as_mut();
-                }
-                tail.next = Some(new_node);
-                return tail.next.as_deref_mut().unwrap();
+    /// Moves every entry into a fresh table of `n` slots indexed by
+    /// `slotter`.
+    fn rebuild(&mut self, slotter: Slotter, n: usize) {
+        let old = std::mem::replace(&mut self.slots, vec![Entry::EMPTY; n]);
+        self.slotter = slotter;
+        for e in old {
+            if let Some(key) = e.key {
+                let idx = self.probe(e.hash, key).0;
+                self.slots[idx] = e;
             }
         }
     }
 }
 
-#[inline]
-fn memchr_newline(slice: &[u8]) -> Option<usize> {
-    slice.iter().position(|&b| b == b'\n')
-}
+// Input is cut into chunks of about this size, however many workers there
+// are, so one that finishes early takes chunks off the others' share
+// instead of idling while they scan their last.
+const CHUNK: usize = 16 << 20;
 
-fn chunk_by_newlines(data: &[u8], workers: usize) -> Vec<Range<usize>> {
+// How many chunks `len` bytes are cut into for `workers` workers: at least
+// one each, or a single one with no pool.
+fn chunk_count(len: usize, workers: usize) -> usize {
     if workers == 0 {
-        return vec![0..data.len()];
+        return 0;
+    }
+    workers.max(len.div_ceil(CHUNK))
+}
+
+fn chunk_by_newlines(data: &[u8], count: usize, kernel: Kernel) -> Vec<Range<usize>> {
+    if count == 0 {
+        return std::iter::once(0..data.len()).collect();
     }
     let mut ranges = Vec::new();
     let mut s = 0usize;
-    let base = data.len() / workers.max(1);
+    let base = data.len() / count;
     let chunk_size = if base == 0 { data.len() } else { base };
 
     while s < data.len() {
@@ -183,7 +307,7 @@ fn chunk_by_newlines(data: &[u8], workers: usize) -> Vec<Range<usize>> {
             ranges.push(s..data.len());
             break;
         }
-        if let Some(nl_off) = memchr_newline(&data[e..]) {
+        if let Some(nl_off) = kernel::find_byte(kernel, &data[e..], b'\n') {
             e += nl_off + 1;
             ranges.push(s..e);
             s = e;
@@ -196,130 +320,245 @@ fn chunk_by_newlines(data: &[u8], workers: usize) -> Vec<Range<usize>> {
     ranges
 }
 
-fn process_partition(data: &[u8], range: Range<usize>) -> Bucket {
-    let mut b = Bucket::new();
-    let mut start = range.start;
-    let end = range.end;
+/// Word-sized reads over a single chunk. Every access stays inside the
+/// chunk: a word that would cross its end is zero-padded instead, and a
+/// value runs at most to the end, so a short or truncated last line can
+/// neither read out of bounds nor skip into the next chunk.
+struct TailReader<'a> {
+    data: &'a [u8],
+    kernel: Kernel,
+    delimiter: u8,
+    // `delimiter` in every byte, for `find_in_word`.
+    delimiters: u64,
+}
 
-    while start < end {
-        if start + 8 > end {
-            let (city_bytes, after_city) = scan_city_slow(&data[start..end]);
-            start += after_city;
+impl<'a> TailReader<'a> {
+    // Callers must have checked `kernel.is_supported()`.
+    fn new(data: &'a [u8], kernel: Kernel) -> Self {
+        Self::with_delimiter(data, kernel, b';')
+    }
 
-            let uhash = city_hash8_prefix(city_bytes);
-            let h = create_hash(uhash, city_bytes.len());
+    fn with_delimiter(data: &'a [u8], kernel: Kernel, delimiter: u8) -> Self {
+        Self {
+            data,
+            kernel,
+            delimiter,
+            delimiters: u64::from(delimiter) * 0x0101_0101_0101_0101,
+        }
+    }
 
-            let mut tmp = [0u8; 8];
-            let avail = end - start;
-            tmp[..avail].copy_from_slice(&data[start..end]);
-            let u = u64::from_le_bytes(tmp);
-            let (temp, adv) = parse_number(u);
-            let node = b.insert(h, city_bytes);
-            node.min = node.min.min(temp);
-            node.max = node.max.max(temp);
-            node.sum += temp as i64;
-            node.count += 1;
-            start += adv.min(avail);
+    #[inline]
+    fn load(&self, pos: usize) -> u64 {
+        if pos + 8 <= self.data.len() {
+            load_u64_le(&self.data[pos..pos + 8])
         } else {
-            let w = load_u64_le(&data[start..start + 8]);
-
-            // Try find semicolon in first 8 bytes
-            let mut idx = find_semicolon(w);
-            let city_bytes: &[u8] = if idx >= 0 {
-                let uidx = idx as usize;
-                let slice = &data[start..start + uidx];
-                start += uidx + 1; // skip ';'
-                slice
-            } else {
-                let mut i = start + 8;
-                let mut maybe: Option<&[u8]> = None;
-
-                while i + 8 <= end {
-                    let u = load_u64_le(&data[i..i + 8]);
-                    idx = find_semicolon(u);
-                    if idx >= 0 {
-                        let uidx = idx as usize;
-                        let slice = &data[start..i + uidx];
-                        start = i + uidx + 1;
-                        maybe = Some(slice);
-                        break;
-                    }
-                    i += 8;
-                }
+            let rest = self.data.get(pos..).unwrap_or(&[]);
+            let mut tmp = [0u8; 8];
+            tmp[..rest.len()].copy_from_slice(rest);
+            u64::from_le_bytes(tmp)
+        }
+    }
 
-                if let Some(bytes) = maybe {
-                    bytes
-                } else {
-                    let (c, consumed) = scan_city_slow(&data[start..end]);
-                    start += consumed;
-                    c
+    /// `key_hash` of the `len` bytes at `pos`, loading whole words and
+    /// masking the last rather than copying it out.
+    #[inline]
+    fn key_hash(&self, pos: usize, len: usize) -> Hash {
+        let mut h = low_bytes(self.load(pos), len);
+        let mut off = 8;
+        while off < len {
+            h = fold_word(h, low_bytes(self.load(pos + off), len - off));
+            off += 8;
+        }
+        Hash(h)
+    }
+
+    #[inline]
+    fn find_delimiter(&self, pos: usize) -> Option<usize> {
+        match self.kernel {
+            Kernel::Scalar => {
+                let rest = self.data.get(pos..)?;
+                rest.iter()
+                    .position(|&b| b == self.delimiter)
+                    .map(|i| pos + i)
+            }
+            Kernel::Swar => self.find_delimiter_swar(pos),
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => {
+                let rest = self.data.get(pos..)?;
+                // SAFETY: only constructed with Avx2 when the CPU has it.
+                match unsafe { kernel::find_byte_avx2(rest, self.delimiter) } {
+                    Ok(i) => Some(pos + i),
+                    Err(done) => self.find_delimiter_swar(pos + done),
                 }
-            };
+            }
+            #[cfg(not(target_arch = "x86_64"))]
+            Kernel::Avx2 => unreachable!("avx2 kernel on a non-x86_64 target"),
+        }
+    }
 
-            let uhash = city_hash8_prefix(city_bytes);
-            let h = create_hash(uhash, city_bytes.len());
-
-            if start + 8 > end {
-                let mut tmp = [0u8; 8];
-                let avail = end - start;
-                tmp[..avail].copy_from_slice(&data[start..end]);
-                let u = u64::from_le_bytes(tmp);
-                let (temp, adv) = parse_number(u);
-                let node = b.insert(h, city_bytes);
-                node.min = node.min.min(temp);
-                node.max = node.max.max(temp);
-                node.sum += temp as i64;
-                node.count += 1;
-                start += adv.min(avail);
-            } else {
-                let u = load_u64_le(&data[start..start + 8]);
-                let (temp, adv) = parse_number(u);
-                let node = b.insert(h, city_bytes);
-                node.min = node.min.min(temp);
-                node.max = node.max.max(temp);
-                node.sum += temp as i64;
-                node.count += 1;
-                start += adv;
+    // Padding bytes are zero and never match, so a hit is always in bounds.
+    #[inline]
+    fn find_delimiter_swar(&self, mut pos: usize) -> Option<usize> {
+        while pos < self.data.len() {
+            let idx = find_in_word(self.load(pos), self.delimiters);
+            if idx >= 0 {
+                return Some(pos + idx as usize);
+            }
+            pos += 8;
+        }
+        None
+    }
+
+    /// The value after the delimiter at `semi`: the word it starts, its length up
+    /// to the newline (and a `\r` before it, for CRLF line endings), and
+    /// where the next line starts. A well-formed value and its newline fit
+    /// in that one word; otherwise (the chunk's unterminated last line, or a
+    /// malformed row) the newline is searched for, and the end of the chunk
+    /// stands in if there is none.
+    #[inline]
+    fn value(&self, semi: usize) -> (u64, usize, usize) {
+        let pos = semi + 1;
+        let word = self.load(pos);
+        let idx = find_in_word(word, NEWLINES);
+        if idx > 0 {
+            let len = idx as usize;
+            let cr = (word >> ((len - 1) << 3)) as u8 == b'\r';
+            return (word, len - usize::from(cr), pos + len + 1);
+        }
+        let rest = self.data.get(pos..).unwrap_or(&[]);
+        let (len, next) = match kernel::find_byte(self.kernel, rest, b'\n') {
+            Some(i) => (i, pos + i + 1),
+            None => (rest.len(), self.data.len()),
+        };
+        let cr = len > 0 && rest[len - 1] == b'\r';
+        (word, len - usize::from(cr), next)
+    }
+}
+
+fn process_partition<'a>(
+    b: &mut Bucket<'a>,
+    data: &'a [u8],
+    range: Range<usize>,
+    kernel: Kernel,
+    delimiter: u8,
+) {
+    let reader = TailReader::with_delimiter(&data[..range.end], kernel, delimiter);
+    let mut start = range.start;
+    let mut blank = 0;
+
+    // A trailing fragment without a delimiter carries no value and is
+    // dropped.
+    loop {
+        // Blank lines (CRLF ones too) are skipped, not counted, as in
+        // checked scans.
+        while let Some(len) = blank_line(&data[start..range.end]) {
+            start += len;
+            blank += 1;
+        }
+        let Some(semi) = reader.find_delimiter(start) else {
+            break;
+        };
+        let city_bytes = &data[start..semi];
+        let h = reader.key_hash(start, semi - start);
+
+        let (word, len, next) = reader.value(semi);
+        b.insert(h, city_bytes).add(parse_number(word, len) as i32);
+        b.records += 1;
+        start = next;
+    }
+
+    let chunk = &data[range];
+    let mut lines = kernel::count_byte(kernel, chunk, b'\n') as u64;
+    if chunk.last().is_some_and(|&c| c != b'\n') {
+        lines += 1;
+    }
+    b.lines += lines - blank;
+}
+
+// The length of the blank line `rest` starts with, its line ending
+// included, if it starts with one. A lone `\r` at the end of a chunk is
+// the unterminated last line of a CRLF file.
+#[inline]
+fn blank_line(rest: &[u8]) -> Option<usize> {
+    match rest {
+        [b'\n', ..] => Some(1),
+        [b'\r', b'\n', ..] => Some(2),
+        [b'\r'] => Some(1),
+        _ => None,
+    }
+}
+
+// `process_partition` for `Validation::Strict` and `Lenient`, and for rows
+// with more than two columns: splits the partition into lines and checks
+// each with `findlib::parse_row_with` instead of trusting the fixed-width
+// layout. Blank lines (CRLF ones too) are skipped, not counted. A rejected
+// row is recorded as in input `input`.
+fn process_partition_checked<'a>(
+    b: &mut Bucket<'a>,
+    (input, data): (usize, &'a [u8]),
+    range: Range<usize>,
+    kernel: Kernel,
+    (columns, strict): (Columns, bool),
+) {
+    let mut start = range.start;
+    while start < range.end {
+        let end = kernel::find_byte(kernel, &data[start..range.end], b'\n')
+            .map_or(range.end, |i| start + i);
+        let line = &data[start..end];
+        if !matches!(line, b"" | b"\r") {
+            b.lines += 1;
+            match findlib::parse_row_with(line, columns) {
+                Ok((city, temp)) => {
+                    b.insert(key_hash(city), city).add(temp);
+                    b.records += 1;
+                }
+                Err(reason) if strict => {
+                    let row = (input, start, reason);
+                    b.rejected = Some(b.rejected.map_or(row, |r| r.min(row)));
+                    break;
+                }
+                Err(_) => b.skipped += 1,
             }
         }
+        start = end + 1;
     }
+}
 
-    b
+// The SWAR scan takes a line that is not blank to be one name, one ';' and
+// one value. One without a ';' therefore does not fail loudly but gets
+// glued onto the next station's name. Comparing the measurements taken
+// against the lines seen catches any such drift.
+fn check_counts(all: &Bucket) -> Result<(), SolveError> {
+    let (records, lines) = (all.records + all.skipped, all.lines);
+    if records != lines {
+        return Err(SolveError::Miscounted { records, lines });
+    }
+    Ok(())
 }
 
+// Overflow semantics of the fast path: every digit position is clamped to
+// 0..=9 (bytes below '0' read as 0, above '9' as 9), so the magnitude never
+// exceeds 999 and malformed input saturates instead of panicking or
+// wrapping. Only the framing (the value's length) is trusted.
 #[inline]
-fn parse_number(u: u64) -> (i16, usize) {
-    // Formats:
-    //  0.0      -> 4 bytes
-    //  00.0     or -0.0 -> 5 bytes
-    // -00.0     -> 6 bytes
+fn digit(u: u64, shift: u64) -> i16 {
+    (((u >> shift) & CHAR_MASK0) as u8)
+        .saturating_sub(b'0')
+        .min(9) as i16
+}
 
-    if (u & CHAR_MASK1) == DOT1 {
+/// The value of `len` bytes at the start of `u`, in tenths. The length
+/// alone picks the format; longer values read as `-dd.d`.
+#[inline]
+fn parse_number(u: u64, len: usize) -> i16 {
+    match len {
         // 0.0
-        let ones = ((u & CHAR_MASK0) - b'0' as u64) * 10;
-        let tenths = ((u & CHAR_MASK2) >> SHIFT2) - b'0' as u64;
-        return (i16::try_from(ones + tenths).unwrap(), 4);
-    } else if (u & CHAR_MASK2) == DOT2 {
+        ..=3 => digit(u, 0) * 10 + digit(u, SHIFT2),
         // 00.0 or -0.0
-        let v0 = u & CHAR_MASK0;
-        // If leading byte is '-', do not compute tens to avoid overflow on multiply
-        let neg = v0 == b'-' as u64;
-        let tens = if neg { 0 } else { (v0 - b'0' as u64) * 100 };
-        let ones = (((u & CHAR_MASK1) >> SHIFT1) - b'0' as u64) * 10;
-        let tenths = ((u & CHAR_MASK3) >> SHIFT3) - b'0' as u64;
-
-        let temp_u = ones + tenths + tens;
-        let val = i16::try_from(temp_u).unwrap();
-        let val = if neg { -val } else { val };
-        return (val, 5);
-    } else {
+        4 if (u & CHAR_MASK0) == b'-' as u64 => -(digit(u, SHIFT1) * 10 + digit(u, SHIFT3)),
+        4 => digit(u, 0) * 100 + digit(u, SHIFT1) * 10 + digit(u, SHIFT3),
         // -00.0
-        let tens = (((u & CHAR_MASK1) >> SHIFT1) - b'0' as u64) * 100;
-        let ones = (((u & CHAR_MASK2) >> SHIFT2) - b'0' as u64) * 10;
-        let tenths = ((u & CHAR_MASK4) >> SHIFT4) - b'0' as u64;
-
-        let t = i16::try_from(tens + ones + tenths).unwrap();
-        return (t.saturating_neg(), 6);
+        _ => -(digit(u, SHIFT1) * 100 + digit(u, SHIFT2) * 10 + digit(u, SHIFT4)),
     }
 }
 
@@ -331,13 +570,15 @@ fn city_hash8_prefix(bytes: &[u8]) -> u64 {
     u64::from_le_bytes(buf)
 }
 
-// Find semicolon within the next 8 bytes.
+const NEWLINES: u64 = 0x0A0A0A0A0A0A0A0A;
+
+// Find the byte repeated in `pattern` within the next 8 bytes.
 // Returns byte index [0..7] if found, else -1.
-// Implements hasvalue(x, ';') via haszero((x) ^ repeat_byte(';')) trick and trailing_zeros.
+// Implements hasvalue(x, b) via haszero((x) ^ repeat_byte(b)) trick and trailing_zeros.
 #[inline]
-fn find_semicolon(word: u64) -> i32 {
-    // maskedInput = (word ^ 0x3B*8) => bytes equal to ';' become 0x00
-    let mut masked = word ^ 0x3B3B3B3B3B3B3B3B;
+fn find_in_word(word: u64, pattern: u64) -> i32 {
+    // maskedInput = (word ^ b*8) => bytes equal to b become 0x00
+    let mut masked = word ^ pattern;
     // haszero(v) = ((v - 0x0101..) & ~v & 0x8080..)
     masked = (masked.wrapping_sub(0x0101010101010101)) & (!masked) & 0x8080_8080_8080_8080u64;
     if masked == 0 {
@@ -354,72 +595,336 @@ fn load_u64_le(bytes: &[u8]) -> u64 {
     u64::from_le_bytes(arr)
 }
 
-#[inline]
-fn scan_city_slow(data: &[u8]) -> (&[u8], usize) {
-    if let Some(pos) = data.iter().position(|&b| b == b';') {
-        (&data[..pos], pos + 1)
-    } else {
-        (data, data.len())
-    }
+pub fn solve(filename: String) -> Result<String, SolveError> {
+    Ok(format_stats(&solve_stats(filename)?))
+}
+
+/// Same as [`solve`], but over measurements already held in memory.
+pub fn solve_bytes(data: &[u8]) -> Result<String, SolveError> {
+    Ok(format_stats(&solve_stats_bytes(data)?))
+}
+
+/// Like [`solve`], but returns the per-station aggregates sorted by name
+/// instead of the formatted summary.
+pub fn solve_stats(filename: String) -> Result<Vec<StationStats>, SolveError> {
+    solve_stats_with(filename, &SolveOptions::default())
+}
+
+/// Like [`solve_bytes`], but returns the per-station aggregates sorted by
+/// name instead of the formatted summary.
+pub fn solve_stats_bytes(data: &[u8]) -> Result<Vec<StationStats>, SolveError> {
+    solve_stats_bytes_with(data, &SolveOptions::default())
+}
+
+/// Like [`solve`], but honours the kernel, hashing and thread settings in
+/// `opts`. Only text input is accepted; station filters, quotas and
+/// previews are not supported here yet, and timings are never reported.
+pub fn solve_with(filename: String, opts: &SolveOptions) -> Result<String, SolveError> {
+    Ok(format_stats(&solve_stats_with(filename, opts)?))
+}
+
+/// Like [`solve_bytes`], but honours the kernel, hashing and thread
+/// settings in `opts`.
+pub fn solve_bytes_with(data: &[u8], opts: &SolveOptions) -> Result<String, SolveError> {
+    Ok(format_stats(&solve_stats_bytes_with(data, opts)?))
 }
 
-pub fn solve(filename: String) -> Result<String, Box<dyn std::error::Error>> {
+/// Like [`solve_with`], but returns the per-station aggregates sorted by
+/// name instead of the formatted summary.
+pub fn solve_stats_with(
+    filename: String,
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
     let file = File::open(&filename)?;
-    let mapped_file = unsafe { MmapOptions::new().map(&file)? };
+    if let Some(max) = opts.max_resident()
+        && opts.is_windowed(file.metadata()?.len())
+    {
+        return window::solve_stats(&[filename], opts, max, |_, e| e);
+    }
+    let mapped_file = unsafe { MmapOptions::new().map(&file) }.map_err(SolveError::Map)?;
+    solve_stats_bytes_with(&mapped_file, opts)
+}
 
-    let workers = rayon::current_num_threads().max(1);
-    let chunks = chunk_by_newlines(&mapped_file, workers);
+/// Like [`solve_stats_with`], over several files as if they were one. All
+/// of them are mapped up front and scanned on one pool, their partitions
+/// interleaved, and merged into a single table; with
+/// [`SolveOptions::max_resident_bytes`] they are scanned one after another
+/// instead, a window at a time.
+pub fn solve_stats_files_with(
+    filenames: &[String],
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
+    let in_file = |i: usize, error| -> SolveError {
+        FileError {
+            path: filenames[i].clone(),
+            error: Box::new(error),
+        }
+        .into()
+    };
+    if let Some(max) = opts.max_resident() {
+        return window::solve_stats(filenames, opts, max, in_file);
+    }
+    let maps = filenames
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let file = File::open(name).map_err(|e| in_file(i, e.into()))?;
+            unsafe { MmapOptions::new().map(&file) }.map_err(|e| in_file(i, SolveError::Map(e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let inputs: Vec<&[u8]> = maps.iter().map(|m| &m[..]).collect();
+    solve_inputs(&inputs, opts, in_file)
+}
 
-    let groups: Vec<Bucket> = (0..chunks.len())
-        .into_par_iter()
-        .map(|i| process_partition(&mapped_file, chunks[i].clone()))
-        .collect();
+/// Like [`solve_bytes_with`], but returns the per-station aggregates sorted
+/// by name instead of the formatted summary.
+pub fn solve_stats_bytes_with(
+    data: &[u8],
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
+    solve_inputs(&[data], opts, |_, e| e)
+}
 
-    let total_keys = groups.iter().map(|b| b.keys.len()).sum();
-    let mut cities = Vec::with_capacity(total_keys);
-    for b in groups.iter() {
-        cities.extend_from_slice(b.keys());
+// Aggregates `inputs` into one result. `in_input` wraps an error that
+// belongs to one of them, to say which.
+fn solve_inputs(
+    inputs: &[&[u8]],
+    opts: &SolveOptions,
+    in_input: impl Fn(usize, SolveError) -> SolveError,
+) -> Result<Vec<StationStats>, SolveError> {
+    let kernel = check_options(opts)?;
+    let pool = build_pool(opts.workers(), opts);
+    let total = inputs.iter().map(|data| data.len() as u64).sum();
+    let progress = Progress::new(opts, total);
+    let Aggregated {
+        mut stats,
+        skipped,
+        lossy,
+        rekeyed,
+    } = aggregate(
+        inputs,
+        opts,
+        kernel,
+        pool.as_ref(),
+        &in_input,
+        progress.as_ref(),
+    )?;
+    if skipped > 0 {
+        opts.warn(Warning::SkippedRows { count: skipped });
+    }
+    if !lossy.is_empty() {
+        opts.warn(Warning::LossyNames {
+            count: lossy.len() as u64,
+        });
+    }
+    if rekeyed > 0 {
+        opts.warn(Warning::Rekeyed { tables: rekeyed });
     }
-    cities.sort();
-    cities.dedup();
+    // With hundreds of thousands of stations the sort is no longer noise
+    // next to the scan, so it runs on the pool too.
+    match &pool {
+        _ if opts.is_unsorted() => {}
+        Some(pool) => pool.install(|| stats.par_sort_unstable_by(|a, b| a.name.cmp(&b.name))),
+        None => stats.sort_unstable_by(|a, b| a.name.cmp(&b.name)),
+    }
+    Ok(stats)
+}
 
-    // Build output: {city=min/avg/max, ...}
-    let mut out = String::with_capacity(cities.len() * 32);
-    out.push('{');
+// The kernel to scan with, or why `opts` asks for more than this solver
+// does.
+fn check_options(opts: &SolveOptions) -> Result<Kernel, SolveError> {
+    opts.check_plain_scan()?;
+    let kernel = opts.kernel();
+    if !kernel.is_supported() {
+        let e = format!("the {kernel} kernel is not supported on this CPU");
+        return Err(SolveError::Unsupported(e));
+    }
+    Ok(kernel)
+}
 
-    for (i, city) in cities.iter().enumerate() {
-        let h = create_hash(city_hash8_prefix(city.as_bytes()), city.len());
+// What `aggregate` found in its inputs.
+struct Aggregated {
+    // Unsorted.
+    stats: Vec<StationStats>,
+    // Malformed rows left out.
+    skipped: u64,
+    // The stations whose names were not UTF-8, as converted.
+    lossy: Vec<String>,
+    // Tables that switched to the keyed slotter.
+    rekeyed: u64,
+}
 
-        let mut minv: i16 = i16::MAX;
-        let mut maxv: i16 = i16::MIN;
-        let mut sum: i64 = 0;
-        let mut cnt: i64 = 0;
+// The aggregates of `inputs`, scanned on `pool`.
+fn aggregate<'a>(
+    inputs: &[&'a [u8]],
+    opts: &SolveOptions,
+    kernel: Kernel,
+    pool: Option<&rayon::ThreadPool>,
+    in_input: &impl Fn(usize, SolveError) -> SolveError,
+    progress: Option<&Progress>,
+) -> Result<Aggregated, SolveError> {
+    for (i, data) in inputs.iter().enumerate() {
+        if let Some(kind) = Compression::detect(data) {
+            let e = format!("{kind}-compressed input is not supported by this solver");
+            return Err(in_input(i, SolveError::Unsupported(e)));
+        }
+    }
+    let workers = opts.workers();
+    let chunks: Vec<(usize, Range<usize>)> = inputs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, data)| {
+            chunk_by_newlines(data, chunk_count(data.len(), workers), kernel)
+                .into_iter()
+                .map(move |r| (i, r))
+        })
+        .collect();
+    let slotter = Slotter::from_options(opts);
 
-        for g in groups.iter() {
-            if let Some(node) = g.find(h, city) {
-                minv = minv.min(node.min);
-                maxv = maxv.max(node.max);
-                sum += node.sum;
-                cnt += node.count;
+    let validation = opts.validation();
+    let columns = opts.columns();
+    let scan = |mut b: Bucket<'a>, c: usize| {
+        let (i, range) = chunks[c].clone();
+        let len = range.len() as u64;
+        match validation {
+            Validation::Trusted if columns.is_pair() => {
+                process_partition(&mut b, inputs[i], range, kernel, columns.delimiter)
             }
+            // Other columns are split a line at a time; under `Trusted`,
+            // rows that do not parse are left out as under `Lenient`.
+            _ => process_partition_checked(
+                &mut b,
+                (i, inputs[i]),
+                range,
+                kernel,
+                (columns, validation == Validation::Strict),
+            ),
         }
+        if let Some(progress) = progress {
+            progress.add(len);
+        }
+        b
+    };
+    let empty = || Bucket::new(slotter.clone());
+    // Each of the pool's jobs folds the chunks it runs, stolen ones too,
+    // into its own table. Tables are merged as soon as both halves are
+    // done, so the merge of early chunks overlaps the scan of late ones.
+    let all = match pool {
+        Some(pool) => pool.install(|| {
+            (0..chunks.len())
+                .into_par_iter()
+                .fold(empty, scan)
+                .reduce_with(Bucket::merge)
+        }),
+        None => Some((0..chunks.len()).fold(empty(), scan)),
+    };
+    let all = all.unwrap_or_else(empty);
+    if let Some((i, byte_offset, reason)) = all.rejected {
+        let before = kernel::count_byte(kernel, &inputs[i][..byte_offset], b'\n');
+        let e = ParseError {
+            line: before as u64 + 1,
+            byte_offset,
+            reason,
+        };
+        return Err(in_input(i, e.into()));
+    }
+    check_counts(&all)?;
+
+    let mut lossy = Vec::new();
+    let stats: Vec<StationStats> = all
+        .entries()
+        .map(|(key, e)| {
+            let name = String::from_utf8_lossy(key);
+            if let Cow::Owned(name) = &name {
+                lossy.push(name.clone());
+            }
+            StationStats::new(name.into_owned(), e.min, e.max, e.sum, e.count)
+        })
+        .collect();
+    Ok(Aggregated {
+        stats,
+        skipped: all.skipped,
+        lossy,
+        rekeyed: all.rekeyed,
+    })
+}
 
-        if i > 0 {
-            out.push_str(", ");
+// A pool of exactly `workers` threads, built here rather than at startup so
+// its cost lands in the solve that asked for it. Where threads are rationed
+// (a container's pids limit, say) it makes do with half as many, and so on
+// down to none: the caller then scans on its own thread.
+fn build_pool(workers: usize, opts: &SolveOptions) -> Option<rayon::ThreadPool> {
+    let mut threads = workers;
+    let mut error = None;
+    let pool = loop {
+        if threads == 0 {
+            break None;
+        }
+        match try_pool(threads) {
+            Ok(pool) => break Some(pool),
+            Err(e) => {
+                error.get_or_insert(e);
+                threads /= 2;
+            }
         }
+    };
+    if let Some(error) = error {
+        opts.warn(Warning::FewerWorkers {
+            wanted: workers,
+            started: threads,
+            error,
+        });
+    }
+    pool
+}
+
+fn try_pool(threads: usize) -> Result<rayon::ThreadPool, String> {
+    #[cfg(test)]
+    if tests::THREAD_LIMIT.get().is_some_and(|n| threads > n) {
+        return Err("thread limit reached".to_string());
+    }
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| e.to_string())
+}
 
-        let min_f = round1(minv as f64 / 10.0);
-        let avg_f = round1((sum as f64) / 10.0 / (cnt as f64));
-        let max_f = round1(maxv as f64 / 10.0);
+/// This crate as a [`findlib::Solver`].
+pub struct Sol2;
 
-        // city=%.1f/%.1f/%.1f
-        use std::fmt::Write as _;
-        write!(out, "{}={:.1}/{:.1}/{:.1}", city, min_f, avg_f, max_f).unwrap();
+impl findlib::Solver for Sol2 {
+    fn name(&self) -> &'static str {
+        "sol2"
     }
 
-    out.push_str("}\n");
+    fn solve_stats(
+        &self,
+        filename: String,
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        solve_stats_with(filename, opts)
+    }
 
-    Ok(out)
+    fn solve_stats_bytes(
+        &self,
+        data: &[u8],
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        solve_stats_bytes_with(data, opts)
+    }
+
+    fn solve_stats_files(
+        &self,
+        filenames: &[String],
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        solve_stats_files_with(filenames, opts)
+    }
+}
+
+/// Runs [`solve_bytes`] over the dataset embedded in the crate.
+pub fn solve_builtin_sample() -> Result<String, SolveError> {
+    solve_bytes(findlib::SAMPLE)
 }
 
 #[cfg(test)]
@@ -428,7 +933,15 @@ mod tests {
     use findlib::{find, read_file};
     use std::path::Path;
 
+    thread_local! {
+        // Largest pool `try_pool` may build on this thread, standing in for
+        // a pids limit; unlimited if `None`.
+        pub(super) static THREAD_LIMIT: std::cell::Cell<Option<usize>> =
+            const { std::cell::Cell::new(None) };
+    }
+
     #[test]
+    #[cfg_attr(miri, ignore = "Miri cannot mmap")]
     fn test_solve() {
         let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_cases");
         let files = find(&root, ".txt").unwrap_or_else(|e| panic!("walking test_cases: {e}"));
@@ -440,4 +953,548 @@ mod tests {
             assert_eq!(want, got, "mismatch for {}", name.display())
         }
     }
+
+    #[test]
+    fn test_solve_bytes() {
+        let got = solve_bytes(b"Hamburg;12.0\nBulawayo;8.9\nHamburg;-3.4\n")
+            .unwrap_or_else(|e| panic!("solve_bytes failed: {e}"));
+        assert_eq!("{Bulawayo=8.9/8.9/8.9, Hamburg=-3.4/4.3/12.0}\n", got);
+    }
+
+    #[test]
+    fn test_solve_bytes_rounds_mean_like_sol1() {
+        // Both means are exactly ±0.55, ties that integer tenths round
+        // half up, as sol1 does.
+        let data = b"A;0.5\nA;0.5\nA;0.5\nA;0.6\nA;0.6\nA;0.6\n\
+                     B;-0.5\nB;-0.5\nB;-0.5\nB;-0.6\nB;-0.6\nB;-0.6\n";
+        let got = solve_bytes(data).unwrap_or_else(|e| panic!("solve_bytes failed: {e}"));
+        assert_eq!("{A=0.5/0.6/0.6, B=-0.6/-0.5/-0.5}\n", got);
+    }
+
+    #[test]
+    fn test_unsorted() {
+        let sorted = solve_stats_bytes(findlib::SAMPLE).unwrap();
+        let opts = SolveOptions::default().threads(4).unsorted(true);
+        let mut got = solve_stats_bytes_with(findlib::SAMPLE, &opts).unwrap();
+        got.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(sorted, got);
+    }
+
+    #[test]
+    fn test_solve_stats_bytes() {
+        let got = solve_stats_bytes(b"Hamburg;12.0\nBulawayo;8.9\nHamburg;-3.4\n").unwrap();
+        let hamburg = StationStats::new("Hamburg", -34, 120, 86, 2);
+        assert_eq!(
+            (2, "Bulawayo", &hamburg),
+            (got.len(), got[0].name.as_str(), &got[1])
+        );
+        assert_eq!(43, got[1].mean());
+    }
+
+    #[test]
+    fn test_fewer_threads_when_pool_fails() {
+        let want = solve_builtin_sample().unwrap();
+        for (limit, started) in [(2, 2), (0, 0)] {
+            let warnings = findlib::Warnings::new();
+            let opts = SolveOptions::default()
+                .threads(5)
+                .collect_warnings(&warnings);
+            THREAD_LIMIT.set(Some(limit));
+            let got = solve_bytes_with(findlib::SAMPLE, &opts);
+            THREAD_LIMIT.set(None);
+            assert_eq!(want, got.unwrap(), "limit {limit}");
+            assert_eq!(
+                vec![Warning::FewerWorkers {
+                    wanted: 5,
+                    started,
+                    error: "thread limit reached".to_string(),
+                }],
+                warnings.take()
+            );
+        }
+    }
+
+    #[test]
+    fn test_solver_trait() {
+        use findlib::Solver;
+
+        let data = b"Hamburg;12.0\nBulawayo;8.9\nHamburg;-3.4\n";
+        let stats = Sol2
+            .solve_stats_bytes(data, &SolveOptions::default())
+            .unwrap();
+        assert_eq!(solve_bytes(data).unwrap(), format_stats(&stats));
+        assert_eq!((2, 86), (stats[1].count, stats[1].sum));
+
+        let filtered = SolveOptions::default().include_stations(["Hamburg"]);
+        assert!(Sol2.solve_stats_bytes(data, &filtered).is_err());
+        for opts in [
+            SolveOptions::default().report_chunks(|_| {}),
+            SolveOptions::default().on_chunk_panic(findlib::PanicPolicy::Skip),
+            SolveOptions::default().release_pages(true),
+            SolveOptions::default().memory_watermark(1 << 30),
+        ] {
+            let err = Sol2.solve_stats_bytes(data, &opts).unwrap_err();
+            assert!(matches!(err, SolveError::Unsupported(_)), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_tail_reader_pads_last_word() {
+        let reader = TailReader::new(b"Oslo;-3.2", Kernel::Swar);
+        assert_eq!(u64::from_le_bytes(*b"Oslo;-3."), reader.load(0));
+        assert_eq!(u64::from_le_bytes(*b"-3.2\0\0\0\0"), reader.load(5));
+        assert_eq!(0, reader.load(9));
+    }
+
+    #[test]
+    fn test_tail_reader_find_delimiter() {
+        let data = b"Las Palmas de Gran Canaria;1.0\nab;Llanfairpwllgwyngyllgogerychwyrndrobwll;";
+        for kernel in Kernel::ALL {
+            if !kernel.is_supported() {
+                continue;
+            }
+            let reader = TailReader::new(&data[..33], kernel);
+            assert_eq!(Some(26), reader.find_delimiter(0), "{kernel}");
+            assert_eq!(None, reader.find_delimiter(27), "{kernel}");
+            assert_eq!(None, reader.find_delimiter(40), "{kernel}");
+            let reader = TailReader::new(data, kernel);
+            assert_eq!(Some(33), reader.find_delimiter(27), "{kernel}");
+            assert_eq!(Some(data.len() - 1), reader.find_delimiter(34), "{kernel}");
+        }
+    }
+
+    #[test]
+    fn test_tail_reader_value() {
+        let data = b"Oslo;1.2\nAbha;-12.3\nRome;12.345678\nBad;1.0";
+        for kernel in Kernel::ALL {
+            if !kernel.is_supported() {
+                continue;
+            }
+            // Each value parsed, with its length and the next line's start.
+            let value = |reader: &TailReader, semi| {
+                let (word, len, next) = reader.value(semi);
+                (parse_number(word, len), len, next)
+            };
+            let reader = TailReader::new(data, kernel);
+            assert_eq!((12, 3, 9), value(&reader, 4), "{kernel}");
+            assert_eq!((-123, 5, 20), value(&reader, 13), "{kernel}");
+            // Too long for one word: the newline is searched for.
+            let (_, len, next) = value(&reader, 24);
+            assert_eq!((9, 35), (len, next), "{kernel}");
+            // The unterminated last line ends with the chunk.
+            assert_eq!((10, 3, data.len()), value(&reader, 38), "{kernel}");
+            let reader = TailReader::new(&data[..7], kernel);
+            assert_eq!((10, 2, 7), value(&reader, 4), "{kernel}");
+        }
+    }
+
+    #[test]
+    fn test_solve_bytes_unterminated_tail() {
+        let got = solve_bytes(b"Hamburg;12.0\nBulawayo;8.9\nOslo;-3.2")
+            .unwrap_or_else(|e| panic!("solve_bytes failed: {e}"));
+        assert_eq!(
+            "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/12.0/12.0, Oslo=-3.2/-3.2/-3.2}\n",
+            got
+        );
+    }
+
+    #[test]
+    fn test_solve_bytes_crlf() {
+        let want = "{Bulawayo=8.9/8.9/8.9, Hamburg=12.0/12.0/12.0, Oslo=-3.2/-3.2/-3.2}\n";
+        // With and without the last line's ending.
+        for data in [
+            &b"Hamburg;12.0\r\nBulawayo;8.9\r\nOslo;-3.2\r\n"[..],
+            b"Hamburg;12.0\r\nBulawayo;8.9\r\nOslo;-3.2",
+        ] {
+            for kernel in Kernel::ALL.into_iter().filter(|k| k.is_supported()) {
+                let opts = SolveOptions::default().force_kernel(kernel);
+                let got = solve_bytes_with(data, &opts).unwrap();
+                assert_eq!(want, got, "{kernel} {data:?}");
+            }
+        }
+        // Checked scans also skip blank CRLF lines.
+        let data = b"Hamburg;12.0\r\n\r\nBulawayo;8.9\r\nOslo;-3.2";
+        let strict = SolveOptions::default().validate(Validation::Strict);
+        assert_eq!(want, solve_bytes_with(data, &strict).unwrap());
+    }
+
+    #[test]
+    fn test_parse_number_bounds() {
+        let parse = |s: &[u8]| {
+            let mut buf = [0u8; 8];
+            buf[..s.len()].copy_from_slice(s);
+            parse_number(u64::from_le_bytes(buf), s.len())
+        };
+        assert_eq!(999, parse(b"99.9"));
+        assert_eq!(-999, parse(b"-99.9"));
+        assert_eq!(-99, parse(b"-9.9"));
+        assert_eq!(0, parse(b"0.0"));
+        // Malformed digits saturate per position instead of panicking.
+        assert_eq!(999, parse(b"Z\xff.~"));
+        assert_eq!(0, parse(b"\0./"));
+        assert_eq!(-999, parse(b"-\xff\xff.\xff"));
+        // So do lengths no value has.
+        assert_eq!(10, parse(b"1"));
+        assert_eq!(-999, parse(b"999.99"));
+    }
+
+    #[test]
+    fn test_entry_holds_wide_values() {
+        assert_eq!(48, std::mem::size_of::<Entry>());
+        let mut b = Bucket::new(Slotter::Seeded(0));
+        let h = key_hash(b"Oslo");
+        let entry = b.insert(h, b"Oslo");
+        entry.max = entry.max.max(40_000);
+        entry.min = entry.min.min(-40_000);
+        let entry = b.insert(h, b"Oslo");
+        assert_eq!((-40_000, 40_000), (entry.min, entry.max));
+    }
+
+    #[test]
+    fn test_table_grows() {
+        let names: Vec<String> = (0..3 * INITIAL_SLOTS).map(|i| format!("S{i}")).collect();
+        let mut b = Bucket::new(Slotter::Seeded(0));
+        for name in names.iter().chain(&names) {
+            let h = key_hash(name.as_bytes());
+            b.insert(h, name.as_bytes()).count += 1;
+        }
+        assert!(b.slots.len() >= 2 * names.len());
+        assert_eq!(names.len(), b.entries().count());
+        assert!(b.entries().all(|(_, e)| e.count == 2));
+    }
+
+    #[test]
+    fn test_buckets_merge() {
+        let fill = |rows: &[(&'static str, i32)]| {
+            let mut b = Bucket::new(Slotter::Seeded(0));
+            for &(name, temp) in rows {
+                b.insert(key_hash(name.as_bytes()), name.as_bytes())
+                    .add(temp);
+                b.records += 1;
+                b.lines += 1;
+            }
+            b
+        };
+        let a = fill(&[("Oslo", -30), ("Bulawayo", 89)]);
+        // Its own rekeyed slotter must not matter to the table it joins.
+        let mut b = fill(&[("Oslo", 12), ("Hamburg", 120)]);
+        b.rebuild(Slotter::Keyed(RandomState::new()), 8);
+        let all = a.merge(b);
+        let mut got: Vec<_> = all
+            .entries()
+            .map(|(k, e)| (k, e.min, e.max, e.sum, e.count))
+            .collect();
+        got.sort_unstable();
+        assert_eq!(
+            vec![
+                (&b"Bulawayo"[..], 89, 89, 89, 1),
+                (&b"Hamburg"[..], 120, 120, 120, 1),
+                (&b"Oslo"[..], -30, 12, -18, 2),
+            ],
+            got
+        );
+        assert_eq!((4, 4), (all.records, all.lines));
+    }
+
+    #[test]
+    fn test_chunks_fold_into_one_table() {
+        assert_eq!(4, chunk_count(1000, 4));
+        assert_eq!(10, chunk_count(10 * CHUNK, 4));
+        assert_eq!(11, chunk_count(10 * CHUNK + 1, 1));
+        assert_eq!(0, chunk_count(10 * CHUNK, 0));
+
+        let data = findlib::SAMPLE;
+        let kernel = Kernel::Scalar;
+        let mut whole = Bucket::new(Slotter::Seeded(0));
+        process_partition(&mut whole, data, 0..data.len(), kernel, b';');
+        // Many chunks, one table: counts add up across them.
+        let mut folded = Bucket::new(Slotter::Seeded(0));
+        for r in chunk_by_newlines(data, 37, kernel) {
+            process_partition(&mut folded, data, r, kernel, b';');
+        }
+        let entries = |b: &Bucket| {
+            let mut e: Vec<_> = b
+                .entries()
+                .map(|(k, e)| (k.to_vec(), e.min, e.max, e.sum, e.count))
+                .collect();
+            e.sort_unstable();
+            e
+        };
+        assert_eq!(entries(&whole), entries(&folded));
+        assert_eq!((whole.records, whole.lines), (folded.records, folded.lines));
+        assert_eq!(whole.records, folded.lines);
+    }
+
+    #[test]
+    fn test_long_names_hash_apart() {
+        let names: Vec<String> = (0..1000).map(|i| format!("Station {i}")).collect();
+        let hashes: std::collections::HashSet<u64> =
+            names.iter().map(|n| key_hash(n.as_bytes()).0).collect();
+        assert_eq!(names.len(), hashes.len());
+        // The hot path's word loads agree, up to the end of the input.
+        let data = b"Oslo;Hamburg;Station 1234567;Gr\xc3\xb6\xc3\x9fer Arber";
+        let reader = TailReader::new(data, Kernel::Swar);
+        for key in data.split(|&b| b == b';') {
+            let pos = key.as_ptr() as usize - data.as_ptr() as usize;
+            assert_eq!(key_hash(key), reader.key_hash(pos, key.len()), "{key:?}");
+        }
+        // Short names are their own hash.
+        assert_eq!(
+            Hash(u64::from_le_bytes(*b"Oslo\0\0\0\0")),
+            key_hash(b"Oslo")
+        );
+    }
+
+    #[test]
+    fn test_long_probe_rekeys() {
+        // Crafted 16-byte names: the second word cancels what the first
+        // contributes to the fold, so every name has the same hash.
+        let names: Vec<Vec<u8>> = (0..2 * MAX_CHAIN as u64)
+            .map(|i| {
+                let mut name = i.to_le_bytes().to_vec();
+                name.extend((i.rotate_left(5) ^ 0x2020).to_le_bytes());
+                name
+            })
+            .collect();
+        let mut b = Bucket::new(Slotter::Seeded(0));
+        for name in &names {
+            b.insert(key_hash(name), name).count += 1;
+        }
+        assert!(matches!(b.slotter, Slotter::Keyed(_)));
+        assert_eq!(1, b.rekeyed);
+        assert_eq!(names.len(), b.entries().count());
+        assert!(b.entries().all(|(_, e)| e.count == 1));
+
+        // A solve says so. The first byte of two names is a ';' or a '\n'.
+        let mut data = Vec::new();
+        for name in names.iter().filter(|n| !matches!(n[0], b';' | b'\n')) {
+            data.extend_from_slice(name);
+            data.extend_from_slice(b";1.0\n");
+        }
+        let warnings = findlib::Warnings::new();
+        let opts = SolveOptions::default()
+            .threads(1)
+            .collect_warnings(&warnings);
+        solve_stats_bytes_with(&data, &opts).unwrap();
+        // Half the names are not UTF-8 either, which is its own warning.
+        assert!(warnings.take().contains(&Warning::Rekeyed { tables: 1 }));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "Miri cannot mmap")]
+    fn test_sequential_names_stay_seeded() {
+        // Whatever the seed, names that count up neither pile up in one
+        // run nor make the table fall back to the keyed hash.
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../test_cases/measurements-10000-unique-keys.txt");
+        let names: Vec<String> = (0..200_000).map(|i| format!("S{i:07}")).collect();
+        for seed in [0, 1, 2, 3, 42] {
+            let warnings = findlib::Warnings::new();
+            let opts = SolveOptions::default()
+                .threads(1)
+                .hash_seed(seed)
+                .collect_warnings(&warnings);
+            solve_stats_with(path.display().to_string(), &opts).unwrap();
+            assert_eq!(Vec::<Warning>::new(), warnings.take(), "seed {seed}");
+
+            let mut b = Bucket::new(Slotter::Seeded(seed));
+            for name in &names {
+                b.insert(key_hash(name.as_bytes()), name.as_bytes()).count += 1;
+            }
+            assert_eq!(0, b.rekeyed, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_solve_bytes_count_mismatch() {
+        // Blank lines are skipped, trailing ones too, as sol1 and sol3 do.
+        for data in [
+            &b"Hamburg;12.0\n\nOslo;1.0\n"[..],
+            b"Hamburg;12.0\r\n\r\nOslo;1.0\r\n\r\n",
+            b"Hamburg;12.0\nOslo;1.0\n\n",
+        ] {
+            let got = solve_bytes(data).unwrap();
+            assert_eq!("{Hamburg=12.0/12.0/12.0, Oslo=1.0/1.0/1.0}\n", got);
+        }
+        let got = solve_bytes(b"a;1.0\n\n").unwrap();
+        assert_eq!("{a=1.0/1.0/1.0}\n", got);
+        let err = solve_bytes(b"Oslo;1.0\nnoise\n").unwrap_err();
+        assert_eq!("parsed 1 measurements from 2 lines", err.to_string());
+    }
+
+    // Small enough for Miri. With `small-table` the table starts at four
+    // slots, so it grows several times and its probes wrap around the end.
+    #[test]
+    fn test_small_input_agrees_with_naive() {
+        let mut data = String::new();
+        let mut want: std::collections::BTreeMap<&str, (i32, i32, i64, u64)> = Default::default();
+        for i in 0..500 {
+            let name = findlib::STATIONS[i * 7 % 150].0;
+            let tenths = (i as i32 * 37) % 1999 - 999;
+            data.push_str(name);
+            data.push(';');
+            findlib::write_tenths(&mut data, tenths as i64);
+            data.push('\n');
+            let e = want.entry(name).or_insert((i32::MAX, i32::MIN, 0, 0));
+            *e = (
+                e.0.min(tenths),
+                e.1.max(tenths),
+                e.2 + tenths as i64,
+                e.3 + 1,
+            );
+        }
+        for kernel in [Kernel::Scalar, Kernel::Swar] {
+            let opts = SolveOptions::default().force_kernel(kernel).threads(2);
+            let got = solve_stats_bytes_with(data.as_bytes(), &opts).unwrap();
+            let got: Vec<_> = got
+                .iter()
+                .map(|s| (s.name.as_str(), (s.min, s.max, s.sum, s.count)))
+                .collect();
+            assert_eq!(
+                want.clone().into_iter().collect::<Vec<_>>(),
+                got,
+                "{kernel}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        let data = b"Oslo;1.0\nBern;-2.5\n\nOslo;3.x\nRome;1.0\nA;B;1.0\n";
+        for kernel in [Kernel::Scalar, Kernel::Swar] {
+            let opts = SolveOptions::default().force_kernel(kernel).threads(2);
+            let err = solve_stats_bytes_with(data, &opts.clone().validate(Validation::Strict))
+                .unwrap_err();
+            let SolveError::Parse(err) = err else {
+                panic!("{kernel}: not a parse error: {err}");
+            };
+            assert_eq!(
+                ParseError {
+                    line: 4,
+                    byte_offset: 20,
+                    reason: "value is not -?d?d.d",
+                },
+                err,
+                "{kernel}"
+            );
+
+            let warnings = findlib::Warnings::new();
+            let opts = opts
+                .validate(Validation::Lenient)
+                .collect_warnings(&warnings);
+            let got = solve_bytes_with(data, &opts).unwrap();
+            assert_eq!(
+                "{Bern=-2.5/-2.5/-2.5, Oslo=1.0/1.0/1.0, Rome=1.0/1.0/1.0}\n",
+                got
+            );
+            assert_eq!(vec![Warning::SkippedRows { count: 2 }], warnings.take());
+        }
+    }
+
+    #[test]
+    fn test_lossy_names() {
+        let warnings = findlib::Warnings::new();
+        let opts = SolveOptions::default()
+            .threads(2)
+            .collect_warnings(&warnings);
+        let data = b"Oslo;1.0\nK\xf8benhavn;2.0\nK\xf8benhavn;3.0\n";
+        let got = solve_bytes_with(data, &opts).unwrap();
+        assert_eq!("{K\u{FFFD}benhavn=2.0/2.5/3.0, Oslo=1.0/1.0/1.0}\n", got);
+        assert_eq!(vec![Warning::LossyNames { count: 1 }], warnings.take());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_columns() {
+        let want = solve_stats_bytes(findlib::SAMPLE).unwrap();
+        let tsv: Vec<u8> = findlib::SAMPLE
+            .iter()
+            .map(|&b| if b == b';' { b'\t' } else { b })
+            .collect();
+        // A column between station and value.
+        let fields = String::from_utf8_lossy(findlib::SAMPLE)
+            .replace(';', "\t0\t")
+            .into_bytes();
+        for (value, data) in [(2, tsv), (3, fields)] {
+            let columns = Columns {
+                delimiter: b'\t',
+                value,
+            };
+            let opts = SolveOptions::default().threads(3).text_columns(columns);
+            for opts in [opts.clone(), opts.validate(Validation::Strict)] {
+                let got = solve_stats_bytes_with(&data, &opts);
+                assert_eq!(want, got.unwrap(), "{columns:?} {:?}", opts.validation());
+            }
+        }
+    }
+
+    #[test]
+    fn test_inputs_merge() {
+        let (a, b) = (
+            &b"Oslo;1.0\nBern;-2.5\nOslo;3.0"[..],
+            &b"Oslo;-1.0\nRome;1.0\n"[..],
+        );
+        let opts = SolveOptions::default().threads(3);
+        let by_input = |i: usize, e| {
+            let path = format!("input {i}");
+            SolveError::from(FileError {
+                path,
+                error: Box::new(e),
+            })
+        };
+        let got = solve_inputs(&[a, b], &opts, by_input).unwrap();
+        let want = solve_stats_bytes_with(
+            b"Oslo;1.0\nBern;-2.5\nOslo;3.0\nOslo;-1.0\nRome;1.0\n",
+            &opts,
+        );
+        assert_eq!(want.unwrap(), got);
+
+        let strict = opts.validate(Validation::Strict);
+        let err = solve_inputs(&[a, b"Rome;1.0\nOslo;x\n"], &strict, by_input).unwrap_err();
+        assert_eq!(
+            "input 1: line 2 (byte 9): value is not -?d?d.d",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "the 1 MiB sample takes too long under Miri")]
+    fn test_kernels_agree() {
+        let want = solve_builtin_sample().unwrap_or_else(|e| panic!("solve failed: {e}"));
+        for kernel in Kernel::ALL {
+            if !kernel.is_supported() {
+                continue;
+            }
+            let got = solve_bytes_with(
+                findlib::SAMPLE,
+                &SolveOptions::default().force_kernel(kernel),
+            )
+            .unwrap_or_else(|e| panic!("{kernel} failed: {e}"));
+            assert_eq!(want, got, "{kernel} disagrees");
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "the 1 MiB sample takes too long under Miri")]
+    fn test_hash_modes_agree() {
+        let want = solve_builtin_sample().unwrap_or_else(|e| panic!("solve failed: {e}"));
+        for opts in [
+            SolveOptions::default().hash_seed(0),
+            SolveOptions::default().hash_seed(u64::MAX),
+            SolveOptions::default().untrusted_input(true),
+        ] {
+            let got = solve_bytes_with(findlib::SAMPLE, &opts)
+                .unwrap_or_else(|e| panic!("{opts:?} failed: {e}"));
+            assert_eq!(want, got, "{opts:?} disagrees");
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "the 1 MiB sample takes too long under Miri")]
+    fn test_builtin_sample() {
+        let got = solve_builtin_sample().unwrap_or_else(|e| panic!("solve failed: {e}"));
+        for (name, _) in findlib::STATIONS {
+            assert!(got.contains(&format!("{name}=")), "missing {name}");
+        }
+    }
 }
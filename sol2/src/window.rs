@@ -0,0 +1,138 @@
+//! Files larger than `SolveOptions::max_resident_bytes`, mapped a window at
+//! a time instead of whole. Each window starts on an aligned offset at or
+//! before the first row it has not scanned yet and ends after its last
+//! whole row; it is scanned on the pool, folded into the result and
+//! unmapped before the next one is mapped.
+
+use crate::{aggregate, build_pool, check_options, kernel};
+use findlib::{Progress, SolveError, SolveOptions, StationStats, Validation, Warning};
+use memmap2::MmapOptions;
+use std::collections::BTreeSet;
+use std::fs::File;
+
+// Windows start on a multiple of this, which is a multiple of the page size
+// and of Windows' allocation granularity.
+const ALIGN: u64 = 64 << 10;
+
+/// Aggregates `filenames` one window at a time, each at most `max` bytes
+/// unless a single row is longer. `in_file` wraps an error that belongs to
+/// one of them, to say which.
+pub(crate) fn solve_stats(
+    filenames: &[String],
+    opts: &SolveOptions,
+    max: usize,
+    in_file: impl Fn(usize, SolveError) -> SolveError,
+) -> Result<Vec<StationStats>, SolveError> {
+    let kernel = check_options(opts)?;
+    let pool = build_pool(opts.workers(), opts);
+    // Room for the part of a row before the window's first one.
+    let window = (max as u64).max(2 * ALIGN);
+    let (mut all, mut skipped, mut rekeyed) = (Vec::new(), 0, 0);
+    // A station in several windows is converted in each.
+    let mut lossy = BTreeSet::new();
+    // A file that cannot be read fails below, before it is counted.
+    let total = filenames
+        .iter()
+        .map(|name| std::fs::metadata(name).map_or(0, |m| m.len()))
+        .sum();
+    let progress = Progress::new(opts, total);
+    for (i, name) in filenames.iter().enumerate() {
+        let file = File::open(name).map_err(|e| in_file(i, e.into()))?;
+        let len = file.metadata().map_err(|e| in_file(i, e.into()))?.len();
+        // The next row to scan, and the lines before it.
+        let (mut next, mut lines) = (0, 0);
+        while next < len {
+            let base = next / ALIGN * ALIGN;
+            let skip = (next - base) as usize;
+            let mut size = window;
+            let (map, end) = loop {
+                let size_now = size.min(len - base);
+                let map = unsafe {
+                    MmapOptions::new()
+                        .offset(base)
+                        .len(size_now as usize)
+                        .map(&file)
+                }
+                .map_err(|e| in_file(i, SolveError::Map(e)))?;
+                if base + size_now == len {
+                    break (map, size_now as usize);
+                }
+                // A row longer than the window makes it grow.
+                match map[skip..].iter().rposition(|&b| b == b'\n') {
+                    Some(at) => break (map, skip + at + 1),
+                    None => size *= 2,
+                }
+            };
+            let rows = &map[skip..end];
+            let at_window = |e| match e {
+                SolveError::Parse(mut e) => {
+                    e.line += lines;
+                    e.byte_offset += next as usize;
+                    SolveError::Parse(e)
+                }
+                e => e,
+            };
+            let in_window = |_, e| in_file(i, at_window(e));
+            let found = aggregate(
+                &[rows],
+                opts,
+                kernel,
+                pool.as_ref(),
+                &in_window,
+                progress.as_ref(),
+            )?;
+            all = findlib::merge_stats([all, found.stats]);
+            skipped += found.skipped;
+            rekeyed += found.rekeyed;
+            lossy.extend(found.lossy);
+            // Only a rejected row needs line numbers.
+            if opts.validation() == Validation::Strict {
+                lines += kernel::count_byte(kernel, rows, b'\n') as u64;
+            }
+            next = base + end as u64;
+        }
+    }
+    if skipped > 0 {
+        opts.warn(Warning::SkippedRows { count: skipped });
+    }
+    if !lossy.is_empty() {
+        opts.warn(Warning::LossyNames {
+            count: lossy.len() as u64,
+        });
+    }
+    if rekeyed > 0 {
+        opts.warn(Warning::Rekeyed { tables: rekeyed });
+    }
+    Ok(all)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore = "maps a file")]
+    fn test_windows_agree() {
+        let path = std::env::temp_dir().join(format!("windows-{}.txt", std::process::id()));
+        let name = path.to_string_lossy().into_owned();
+        let mut data = findlib::SAMPLE.to_vec();
+        let opts = SolveOptions::default().threads(2);
+        let want = crate::solve_stats_bytes_with(&data, &opts).unwrap();
+        std::fs::write(&path, &data).unwrap();
+        // Eight windows and change, then four.
+        for max in [1, 300_000] {
+            let windowed = opts.clone().max_resident_bytes(max);
+            let got = crate::solve_stats_with(name.clone(), &windowed);
+            assert_eq!(want, got.unwrap(), "max {max}");
+        }
+
+        // A rejected row far into the file is placed in the whole file.
+        data.extend_from_slice(b"Oslo;x\nRome;1.0\n");
+        std::fs::write(&path, &data).unwrap();
+        let strict = opts.validate(Validation::Strict);
+        let want = crate::solve_stats_bytes_with(&data, &strict).unwrap_err();
+        let got = crate::solve_stats_with(name, &strict.max_resident_bytes(1));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(want.to_string(), got.unwrap_err().to_string());
+    }
+}
@@ -0,0 +1,47 @@
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use findlib::FlamegraphProfiler;
+use std::fs;
+use std::hint::black_box;
+use std::path::PathBuf;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../data/measurements.txt");
+    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+
+    let mut group = c.benchmark_group("sol3");
+    group.sample_size(100);
+    group.throughput(Throughput::Bytes(size));
+
+    group.bench_function(BenchmarkId::from_parameter("measurements.txt"), |b| {
+        b.iter_batched(
+            || path.to_string_lossy().to_string(),
+            |filename| {
+                let out = sol3::solve(filename).unwrap();
+                black_box(out);
+            },
+            criterion::BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+// The built-in sample from memory, so there is a number to compare without
+// generating `data/measurements.txt` and without disk reads in the loop.
+fn sample_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sol3-bytes");
+    group.throughput(Throughput::Bytes(findlib::SAMPLE.len() as u64));
+    group.bench_function(BenchmarkId::from_parameter("sample"), |b| {
+        b.iter(|| black_box(sol3::solve_bytes(black_box(findlib::SAMPLE)).unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(
+    name = benches;
+    // `cargo bench -- --profile-time 10` writes a flamegraph per benchmark.
+    config = Criterion::default().with_profiler(FlamegraphProfiler::new(100));
+    targets = criterion_benchmark, sample_benchmark,
+);
+
+criterion_main!(benches);
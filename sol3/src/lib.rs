@@ -0,0 +1,458 @@
+//! A third take, built around how the bytes get in rather than how they are
+//! parsed: each worker reads its range of the file with io_uring into two
+//! buffers, parsing one while the kernel fills the other, instead of
+//! mapping the file and taking a page fault per 4 KiB. Rows are checked
+//! with `findlib::parse_row_with`, so `Trusted` input is held to `Strict`.
+
+use findlib::{Compression, Progress, format_stats, merge_stats};
+use scan::{Part, Scanner};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+mod scan;
+#[cfg(target_os = "linux")]
+mod uring;
+
+pub use findlib::{
+    FileError, InputFormat, ParseError, SolveError, SolveOptions, StationStats, Validation, Warning,
+};
+
+// Bytes per read. With one read in flight per worker, enough to keep an
+// NVMe drive streaming.
+const BLOCK: usize = 4 << 20;
+// Direct reads start on, and workers' ranges are cut at, multiples of this.
+const ALIGN: u64 = 4096;
+
+pub fn solve(filename: String) -> Result<String, SolveError> {
+    Ok(format_stats(&solve_stats(filename)?))
+}
+
+/// Same as [`solve`], but over measurements already held in memory.
+pub fn solve_bytes(data: &[u8]) -> Result<String, SolveError> {
+    Ok(format_stats(&solve_stats_bytes(data)?))
+}
+
+/// Like [`solve`], but returns the per-station aggregates sorted by name
+/// instead of the formatted summary.
+pub fn solve_stats(filename: String) -> Result<Vec<StationStats>, SolveError> {
+    solve_stats_with(filename, &SolveOptions::default())
+}
+
+/// Like [`solve_bytes`], but returns the per-station aggregates sorted by
+/// name instead of the formatted summary.
+pub fn solve_stats_bytes(data: &[u8]) -> Result<Vec<StationStats>, SolveError> {
+    solve_stats_bytes_with(data, &SolveOptions::default())
+}
+
+/// Like [`solve`], but honours the thread and validation settings in
+/// `opts`. Only text input is accepted; station filters, quotas and
+/// previews are not supported, and timings are never reported. No more
+/// than two blocks per worker are ever in memory, whatever
+/// [`SolveOptions::max_resident_bytes`] says.
+pub fn solve_with(filename: String, opts: &SolveOptions) -> Result<String, SolveError> {
+    Ok(format_stats(&solve_stats_with(filename, opts)?))
+}
+
+/// Like [`solve_bytes`], but honours the thread and validation settings in
+/// `opts`.
+pub fn solve_bytes_with(data: &[u8], opts: &SolveOptions) -> Result<String, SolveError> {
+    Ok(format_stats(&solve_stats_bytes_with(data, opts)?))
+}
+
+/// Like [`solve_with`], but returns the per-station aggregates sorted by
+/// name instead of the formatted summary.
+pub fn solve_stats_with(
+    filename: String,
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
+    solve_file(&filename, opts, BLOCK)
+}
+
+/// Like [`solve_bytes_with`], but returns the per-station aggregates sorted
+/// by name instead of the formatted summary.
+pub fn solve_stats_bytes_with(
+    data: &[u8],
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
+    check_options(opts)?;
+    check_uncompressed(data)?;
+    let ranges = ranges(data.len() as u64, opts.workers());
+    let progress = Progress::new(opts, data.len() as u64);
+    let parts = run(ranges.len(), opts, |i| {
+        let rows = ranges[i].clone();
+        let from = rows.start.saturating_sub(1);
+        let mut scanner = Scanner::new(rows.clone(), from, (opts.validation(), opts.columns()));
+        scanner.feed(&data[from as usize..]);
+        if let Some(progress) = &progress {
+            progress.add(rows.end - rows.start);
+        }
+        Ok(scanner.finish())
+    })?;
+    finish(parts, opts)
+}
+
+// `solve_stats_with`, reading `block` bytes at a time.
+fn solve_file(
+    filename: &str,
+    opts: &SolveOptions,
+    block: usize,
+) -> Result<Vec<StationStats>, SolveError> {
+    check_options(opts)?;
+    let mut file = File::open(filename)?;
+    let len = file.metadata()?.len();
+    let mut head = [0; 4];
+    let n = file.read(&mut head)?;
+    check_uncompressed(&head[..n])?;
+    let reader = Reader::new(filename, opts)?;
+    let ranges = ranges(len, opts.workers());
+    let progress = Progress::new(opts, len);
+    let parts = run(ranges.len(), opts, |i| {
+        let rows = ranges[i].clone();
+        // Far enough back for the byte before the first row, and aligned.
+        let from = rows.start.saturating_sub(ALIGN);
+        let mut scanner = Scanner::new(rows.clone(), from, (opts.validation(), opts.columns()));
+        let mut at = from;
+        reader.read(from, len, block, |buf| {
+            // Only the bytes of this worker's range count, once each.
+            if let Some(progress) = &progress {
+                let end = (at + buf.len() as u64).min(rows.end);
+                progress.add(end.saturating_sub(at.max(rows.start)));
+            }
+            at += buf.len() as u64;
+            scanner.feed(buf)
+        })?;
+        Ok(scanner.finish())
+    })?;
+    finish(parts, opts)
+}
+
+// Where workers get their bytes from.
+struct Reader {
+    path: String,
+    // The file opened for io_uring, if it could be set up.
+    #[cfg(target_os = "linux")]
+    direct: Option<File>,
+}
+
+impl Reader {
+    fn new(path: &str, opts: &SolveOptions) -> io::Result<Self> {
+        #[cfg(target_os = "linux")]
+        let direct = match uring::probe() {
+            Ok(()) => Some(uring::open(path)?),
+            Err(e) => {
+                opts.warn(Warning::NoIoUring {
+                    error: e.to_string(),
+                });
+                None
+            }
+        };
+        #[cfg(not(target_os = "linux"))]
+        opts.warn(Warning::NoIoUring {
+            error: "io_uring is Linux-only".into(),
+        });
+        Ok(Self {
+            path: path.to_owned(),
+            #[cfg(target_os = "linux")]
+            direct,
+        })
+    }
+
+    // Hands `f` the file from `from` to `len`, a block at a time, until it
+    // returns false.
+    fn read(
+        &self,
+        from: u64,
+        len: u64,
+        block: usize,
+        mut f: impl FnMut(&[u8]) -> bool,
+    ) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        if let Some(file) = &self.direct {
+            return uring::read_from(file, from, len, block, f);
+        }
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(from))?;
+        let mut buf = vec![0; block];
+        loop {
+            match file.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) if !f(&buf[..n]) => return Ok(()),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Whether [`solve_stats_with`] takes `opts` and reads with io_uring on
+/// this machine, rather than falling back to plain reads.
+pub fn reads_with_io_uring(opts: &SolveOptions) -> bool {
+    #[cfg(target_os = "linux")]
+    let ring = uring::probe().is_ok();
+    #[cfg(not(target_os = "linux"))]
+    let ring = false;
+    ring && check_options(opts).is_ok()
+}
+
+// Why `opts` asks for more than this solver does, if it does.
+fn check_options(opts: &SolveOptions) -> Result<(), SolveError> {
+    opts.check_plain_scan()?;
+    if opts.forced_kernel().is_some() {
+        return Err(SolveError::Unsupported(
+            "scan kernels are not supported by this solver".into(),
+        ));
+    }
+    Ok(())
+}
+
+fn check_uncompressed(head: &[u8]) -> Result<(), SolveError> {
+    match Compression::detect(head) {
+        Some(kind) => {
+            let e = format!("{kind}-compressed input is not supported by this solver");
+            Err(SolveError::Unsupported(e))
+        }
+        None => Ok(()),
+    }
+}
+
+// `len` bytes cut into at most `workers` ranges, each a multiple of
+// `ALIGN` long but the last. Rows are not looked at; each range stands for
+// the rows that start in it.
+fn ranges(len: u64, workers: usize) -> Vec<Range<u64>> {
+    let per = len
+        .div_ceil(workers.max(1) as u64)
+        .next_multiple_of(ALIGN)
+        .max(ALIGN);
+    let starts = std::iter::successors(Some(0), |s| Some(s + per));
+    starts
+        .take_while(|&s| s < len)
+        .map(|s| s..(s + per).min(len))
+        .collect()
+}
+
+// Runs `work` for each of `n` ranges, one thread each, this one included,
+// and returns their parts in order. A thread that cannot be started has its
+// range scanned on this one.
+fn run(
+    n: usize,
+    opts: &SolveOptions,
+    work: impl Fn(usize) -> Result<Part, SolveError> + Sync,
+) -> Result<Vec<Part>, SolveError> {
+    let work = &work;
+    std::thread::scope(|scope| {
+        let mut handles = Vec::with_capacity(n.saturating_sub(1));
+        let mut error = None;
+        for i in 1..n {
+            match std::thread::Builder::new().spawn_scoped(scope, move || work(i)) {
+                Ok(h) => handles.push(h),
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+        let started = handles.len() + 1;
+        if let Some(e) = error {
+            opts.warn(Warning::FewerWorkers {
+                wanted: n,
+                started,
+                error: e.to_string(),
+            });
+        }
+        let mut parts = Vec::with_capacity(n);
+        if n > 0 {
+            parts.push(work(0));
+        }
+        parts.extend(handles.into_iter().map(|h| h.join().unwrap()));
+        parts.extend((started..n).map(work));
+        parts.into_iter().collect()
+    })
+}
+
+// Merges the workers' parts, in input order. The first malformed row is
+// placed in the whole input by the lines of the ranges before it.
+fn finish(parts: Vec<Part>, opts: &SolveOptions) -> Result<Vec<StationStats>, SolveError> {
+    let mut lines = 0;
+    for p in &parts {
+        if let Some((byte_offset, line, reason)) = p.rejected {
+            return Err(ParseError {
+                line: lines + line,
+                byte_offset: byte_offset as usize,
+                reason,
+            }
+            .into());
+        }
+        lines += p.lines;
+    }
+    let skipped: u64 = parts.iter().map(|p| p.skipped).sum();
+    if skipped > 0 {
+        opts.warn(Warning::SkippedRows { count: skipped });
+    }
+    Ok(merge_stats(parts.into_iter().map(|p| p.stats)))
+}
+
+/// This crate as a [`findlib::Solver`].
+pub struct Sol3;
+
+impl findlib::Solver for Sol3 {
+    fn name(&self) -> &'static str {
+        "sol3"
+    }
+
+    fn solve_stats(
+        &self,
+        filename: String,
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        solve_stats_with(filename, opts)
+    }
+
+    fn solve_stats_bytes(
+        &self,
+        data: &[u8],
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        solve_stats_bytes_with(data, opts)
+    }
+}
+
+/// Runs [`solve_bytes`] over the dataset embedded in the crate.
+pub fn solve_builtin_sample() -> Result<String, SolveError> {
+    solve_bytes(findlib::SAMPLE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use findlib::{find, read_file};
+    use std::path::Path;
+
+    #[test]
+    fn test_solve() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_cases");
+        let files = find(&root, ".txt").unwrap_or_else(|e| panic!("walking test_cases: {e}"));
+        for name in files {
+            let txt_path = format!("{}.txt", name.display());
+            let out_path = format!("{}.out", name.display());
+            let got = solve(txt_path).unwrap_or_else(|e| panic!("solve failed: {e}"));
+            let want = read_file(out_path);
+            assert_eq!(want, got, "mismatch for {}", name.display())
+        }
+    }
+
+    #[test]
+    fn test_solve_bytes() {
+        let got = solve_bytes(b"Hamburg;12.0\nBulawayo;8.9\nHamburg;-3.4\n")
+            .unwrap_or_else(|e| panic!("solve_bytes failed: {e}"));
+        assert_eq!("{Bulawayo=8.9/8.9/8.9, Hamburg=-3.4/4.3/12.0}\n", got);
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(Vec::<Range<u64>>::new(), ranges(0, 4));
+        assert_eq!(vec![0..100], ranges(100, 4));
+        assert_eq!(vec![0..4096, 4096..8192, 8192..9000], ranges(9000, 3));
+        assert_eq!(vec![0..9000], ranges(9000, 0));
+    }
+
+    #[test]
+    fn test_small_blocks_agree() {
+        let path = std::env::temp_dir().join(format!("sol3-{}.txt", std::process::id()));
+        let name = path.to_string_lossy().into_owned();
+        let mut data = findlib::SAMPLE.repeat(100_000 / findlib::SAMPLE.len() + 1);
+        // No newline at the end, too.
+        data.extend_from_slice(b"Oslo;-1.5");
+        std::fs::write(&path, &data).unwrap();
+        let want = solve_stats_bytes(&data).unwrap();
+        for threads in [1, 3, 8] {
+            let opts = SolveOptions::default().threads(threads);
+            assert_eq!(want, solve_stats_bytes_with(&data, &opts).unwrap());
+            // Rows cut by blocks as well as by ranges.
+            let got = solve_file(&name, &opts, ALIGN as usize);
+            assert_eq!(want, got.unwrap(), "{threads} threads");
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_validation() {
+        let mut data = findlib::SAMPLE.repeat(4);
+        let bad = data.len();
+        let line = data.iter().filter(|&&b| b == b'\n').count() as u64 + 1;
+        data.extend_from_slice(b"Oslo;3.x\nRome;1.0\n\nA;B;1.0\n");
+        let opts = SolveOptions::default().threads(3);
+        let SolveError::Parse(err) = solve_stats_bytes_with(&data, &opts).unwrap_err() else {
+            panic!("not a parse error");
+        };
+        assert_eq!(
+            ParseError {
+                line,
+                byte_offset: bad,
+                reason: "value is not -?d?d.d",
+            },
+            err
+        );
+
+        let warnings = findlib::Warnings::new();
+        let opts = opts
+            .validate(Validation::Lenient)
+            .collect_warnings(&warnings);
+        let got = solve_stats_bytes_with(&data, &opts).unwrap();
+        let mut clean = findlib::SAMPLE.repeat(4);
+        clean.extend_from_slice(b"Rome;1.0\n");
+        assert_eq!(solve_stats_bytes(&clean).unwrap(), got);
+        assert_eq!(vec![Warning::SkippedRows { count: 2 }], warnings.take());
+    }
+
+    #[test]
+    fn test_solver_trait() {
+        use findlib::Solver;
+
+        let data = b"Hamburg;12.0\nBulawayo;8.9\nHamburg;-3.4\n";
+        let stats = Sol3
+            .solve_stats_bytes(data, &SolveOptions::default())
+            .unwrap();
+        assert_eq!(solve_bytes(data).unwrap(), format_stats(&stats));
+        assert_eq!((2, 86), (stats[1].count, stats[1].sum));
+
+        let filtered = SolveOptions::default().include_stations(["Hamburg"]);
+        assert!(Sol3.solve_stats_bytes(data, &filtered).is_err());
+        for opts in [
+            SolveOptions::default().report_chunks(|_| {}),
+            SolveOptions::default().on_chunk_panic(findlib::PanicPolicy::Skip),
+            SolveOptions::default().release_pages(true),
+            SolveOptions::default().memory_watermark(1 << 30),
+        ] {
+            let err = Sol3.solve_stats_bytes(data, &opts).unwrap_err();
+            assert!(matches!(err, SolveError::Unsupported(_)), "{err}");
+        }
+        let forced = SolveOptions::default().force_kernel(findlib::Kernel::Swar);
+        assert!(Sol3.solve_stats_bytes(data, &forced).is_err());
+        assert!(solve_bytes(b"\x1f\x8b\x08\0").is_err());
+    }
+
+    #[test]
+    fn test_builtin_sample() {
+        assert_eq!(naive_sample(), solve_builtin_sample().unwrap());
+    }
+
+    // The sample aggregated one `parse_row` at a time.
+    fn naive_sample() -> String {
+        let rows = findlib::SAMPLE
+            .split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty());
+        let parts = rows.map(|l| {
+            let (name, t) = findlib::parse_row(l).unwrap();
+            vec![StationStats::new(
+                String::from_utf8_lossy(name).into_owned(),
+                t,
+                t,
+                t.into(),
+                1,
+            )]
+        });
+        format_stats(&merge_stats(parts))
+    }
+}
@@ -0,0 +1,216 @@
+//! One worker's rows, fed a buffer at a time as reads complete. A worker
+//! owns the rows that start inside its range; a row cut by the end of a
+//! buffer is carried over to the next one, and the last row it owns may run
+//! past the range's end.
+
+use ahash::AHashMap;
+use findlib::{Columns, StationStats, Validation, parse_row_with};
+use std::ops::Range;
+
+pub(crate) struct Scanner {
+    table: AHashMap<Box<[u8]>, StationStats>,
+    validation: Validation,
+    columns: Columns,
+    // The input offset of the next byte fed.
+    pos: u64,
+    // Rows starting here or later are the next worker's.
+    end: u64,
+    // Until the range's first row is found: the offset of the byte before
+    // it, whose newline (if any) ends the previous worker's last row.
+    seek: Option<u64>,
+    // A row cut short by the end of the last buffer, and where it starts.
+    carry: Vec<u8>,
+    carry_start: u64,
+    done: bool,
+    part: Part,
+}
+
+/// What a worker found besides its table.
+#[derive(Default)]
+pub(crate) struct Part {
+    pub(crate) stats: Vec<StationStats>,
+    /// Lines in the rows owned, blank ones included.
+    pub(crate) lines: u64,
+    /// Malformed rows left out under [`Validation::Lenient`].
+    pub(crate) skipped: u64,
+    /// Otherwise the first malformed row, which stops the worker: where it
+    /// starts, its line among the worker's, and why.
+    pub(crate) rejected: Option<(u64, u64, &'static str)>,
+}
+
+impl Scanner {
+    /// A scanner for the rows starting in `rows`, to be fed the input from
+    /// `from`, which is at or before the byte preceding `rows`.
+    pub(crate) fn new(
+        rows: Range<u64>,
+        from: u64,
+        (validation, columns): (Validation, Columns),
+    ) -> Self {
+        let seek = rows.start.checked_sub(1);
+        debug_assert!(seek.is_none_or(|s| from <= s));
+        Self {
+            table: AHashMap::new(),
+            validation,
+            columns,
+            pos: from,
+            end: rows.end,
+            seek,
+            carry: Vec::new(),
+            carry_start: 0,
+            done: rows.is_empty(),
+            part: Part::default(),
+        }
+    }
+
+    /// Parses the whole rows in `buf`, the next bytes of the input. Returns
+    /// false once the scanner needs no more.
+    pub(crate) fn feed(&mut self, buf: &[u8]) -> bool {
+        let base = self.pos;
+        self.pos += buf.len() as u64;
+        if self.done {
+            return false;
+        }
+        let mut at = 0;
+        if let Some(seek) = self.seek {
+            if self.pos <= seek {
+                return true;
+            }
+            let i = seek.saturating_sub(base) as usize;
+            let Some(nl) = newline(&buf[i..]) else {
+                return true;
+            };
+            self.seek = None;
+            at = i + nl + 1;
+        }
+        if !self.carry.is_empty() {
+            let Some(nl) = newline(&buf[at..]) else {
+                self.carry.extend_from_slice(&buf[at..]);
+                return true;
+            };
+            let mut row = std::mem::take(&mut self.carry);
+            row.extend_from_slice(&buf[at..at + nl]);
+            self.row(self.carry_start, &row);
+            row.clear();
+            self.carry = row;
+            at += nl + 1;
+        }
+        while at < buf.len() && !self.done {
+            let start = base + at as u64;
+            if start >= self.end {
+                self.done = true;
+                break;
+            }
+            match newline(&buf[at..]) {
+                Some(nl) => {
+                    self.row(start, &buf[at..at + nl]);
+                    at += nl + 1;
+                }
+                None => {
+                    self.carry.extend_from_slice(&buf[at..]);
+                    self.carry_start = start;
+                    break;
+                }
+            }
+        }
+        !self.done
+    }
+
+    /// The input ended: parses the last row if it had no newline.
+    pub(crate) fn finish(mut self) -> Part {
+        if !self.carry.is_empty() && !self.done {
+            let row = std::mem::take(&mut self.carry);
+            self.row(self.carry_start, &row);
+        }
+        self.part.stats = self.table.into_values().collect();
+        self.part
+    }
+
+    fn row(&mut self, start: u64, line: &[u8]) {
+        self.part.lines += 1;
+        if matches!(line, b"" | b"\r") {
+            return;
+        }
+        match parse_row_with(line, self.columns) {
+            Ok((name, temp)) => match self.table.get_mut(name) {
+                Some(s) => {
+                    s.min = s.min.min(temp);
+                    s.max = s.max.max(temp);
+                    s.sum += i64::from(temp);
+                    s.count += 1;
+                }
+                None => {
+                    let stats = StationStats::new(
+                        String::from_utf8_lossy(name).into_owned(),
+                        temp,
+                        temp,
+                        i64::from(temp),
+                        1,
+                    );
+                    self.table.insert(name.into(), stats);
+                }
+            },
+            Err(_) if self.validation == Validation::Lenient => self.part.skipped += 1,
+            Err(reason) => {
+                self.part.rejected = Some((start, self.part.lines, reason));
+                self.done = true;
+            }
+        }
+    }
+}
+
+fn newline(buf: &[u8]) -> Option<usize> {
+    buf.iter().position(|&b| b == b'\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(data: &[u8], rows: Range<u64>, step: usize) -> Part {
+        let from = rows.start.saturating_sub(1);
+        let mut s = Scanner::new(rows, from, (Validation::Strict, Columns::CHALLENGE));
+        for buf in data[from as usize..].chunks(step) {
+            if !s.feed(buf) {
+                break;
+            }
+        }
+        s.finish()
+    }
+
+    fn names(p: &Part) -> Vec<(&str, u64)> {
+        let mut n: Vec<_> = p.stats.iter().map(|s| (&s.name[..], s.count)).collect();
+        n.sort_unstable();
+        n
+    }
+
+    #[test]
+    fn test_rows_carry_across_buffers() {
+        let data = b"Oslo;1.0\nRome;2.0\n\nLima;3.0";
+        for step in 1..=data.len() {
+            let p = scan(data, 0..data.len() as u64, step);
+            assert_eq!(vec![("Lima", 1), ("Oslo", 1), ("Rome", 1)], names(&p));
+            assert_eq!(4, p.lines, "step {step}");
+        }
+    }
+
+    #[test]
+    fn test_rows_belong_where_they_start() {
+        let data = b"Oslo;1.0\nRome;2.0\nLima;3.0\n";
+        // Every split point hands each row to exactly one side.
+        for at in 0..=data.len() as u64 {
+            let (a, b) = (scan(data, 0..at, 4), scan(data, at..data.len() as u64, 4));
+            assert_eq!(3, a.lines + b.lines, "split at {at}");
+            let mut all = names(&a);
+            all.extend(names(&b));
+            all.sort_unstable();
+            assert_eq!(vec![("Lima", 1), ("Oslo", 1), ("Rome", 1)], all);
+        }
+    }
+
+    #[test]
+    fn test_rejected_row() {
+        let data = b"Oslo;1.0\r\nRome;2\nLima;3.0\n";
+        let p = scan(data, 0..data.len() as u64, 3);
+        assert_eq!(Some((10, 2, "value is not -?d?d.d")), p.rejected);
+    }
+}
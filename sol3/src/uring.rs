@@ -0,0 +1,208 @@
+//! Reads with io_uring into two registered buffers: while the rows of one
+//! are parsed, the kernel fills the other. The file is opened with
+//! O_DIRECT where the filesystem allows it, so blocks go from the device
+//! to the buffers without passing through the page cache.
+
+use std::alloc::{Layout, alloc, dealloc};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::ptr::NonNull;
+
+use io_uring::{IoUring, opcode, types};
+
+// Alignment O_DIRECT asks of buffers, offsets and lengths.
+const ALIGN: usize = crate::ALIGN as usize;
+
+/// Whether this kernel (and its seccomp policy) lets us set up a ring.
+pub(crate) fn probe() -> io::Result<()> {
+    IoUring::new(2).map(drop)
+}
+
+/// Opens `path` for [`read_from`], bypassing the page cache unless the
+/// filesystem (tmpfs, say) refuses O_DIRECT.
+pub(crate) fn open(path: &str) -> io::Result<File> {
+    match OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(path)
+    {
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => File::open(path),
+        r => r,
+    }
+}
+
+/// Hands `f` the bytes of `file` from `from` to `len`, `block` bytes at a
+/// time (both multiples of `ALIGN`), until it returns false.
+pub(crate) fn read_from(
+    file: &File,
+    from: u64,
+    len: u64,
+    block: usize,
+    mut f: impl FnMut(&[u8]) -> bool,
+) -> io::Result<()> {
+    let mut ring = Ring::new(file, block)?;
+    let mut cur = 0;
+    let mut offset = from;
+    if offset >= len {
+        return Ok(());
+    }
+    ring.start(cur, offset)?;
+    loop {
+        let n = ring.finish()?;
+        let next = offset + n as u64;
+        // Read ahead before parsing; an O_DIRECT read past the end would be
+        // at an unaligned offset.
+        if n > 0 && next < len {
+            ring.start(1 - cur, next)?;
+        }
+        if !f(ring.filled(cur, n)) || n == 0 || next >= len {
+            return Ok(());
+        }
+        offset = next;
+        cur = 1 - cur;
+    }
+}
+
+// A block-sized buffer at an `ALIGN`ed address.
+struct Buf {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl Buf {
+    fn new(len: usize) -> Self {
+        let layout = Layout::from_size_align(len, ALIGN).unwrap();
+        // SAFETY: `len` is a nonzero multiple of `ALIGN`.
+        let ptr = unsafe { alloc(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| std::alloc::handle_alloc_error(layout));
+        Self { ptr, layout }
+    }
+}
+
+impl Drop for Buf {
+    fn drop(&mut self) {
+        // SAFETY: allocated in `new` with this layout.
+        unsafe { dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+struct Ring {
+    // Dropped before `bufs`, which stay registered with it until then.
+    ring: IoUring,
+    bufs: Vec<Buf>,
+    fd: types::Fd,
+    // Registering pins memory, which RLIMIT_MEMLOCK may not allow; plain
+    // reads into the same buffers do instead.
+    fixed: bool,
+    in_flight: bool,
+}
+
+impl Ring {
+    fn new(file: &File, block: usize) -> io::Result<Self> {
+        let ring = IoUring::new(2)?;
+        let bufs = vec![Buf::new(block), Buf::new(block)];
+        let iovecs: Vec<libc::iovec> = bufs
+            .iter()
+            .map(|b| libc::iovec {
+                iov_base: b.ptr.as_ptr().cast(),
+                iov_len: block,
+            })
+            .collect();
+        // SAFETY: the buffers outlive the ring (see the field order).
+        let fixed = unsafe { ring.submitter().register_buffers(&iovecs) }.is_ok();
+        Ok(Self {
+            ring,
+            bufs,
+            fd: types::Fd(file.as_raw_fd()),
+            fixed,
+            in_flight: false,
+        })
+    }
+
+    // Starts reading a block at `offset` into buffer `i`.
+    fn start(&mut self, i: usize, offset: u64) -> io::Result<()> {
+        let (ptr, len) = (self.bufs[i].ptr.as_ptr(), self.bufs[i].layout.size() as u32);
+        let entry = if self.fixed {
+            opcode::ReadFixed::new(self.fd, ptr, len, i as u16)
+                .offset(offset)
+                .build()
+        } else {
+            opcode::Read::new(self.fd, ptr, len).offset(offset).build()
+        };
+        // SAFETY: the buffer stays untouched until `finish` returns; a ring
+        // of two entries has room for the one read in flight.
+        unsafe { self.ring.submission().push(&entry) }
+            .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+        self.ring.submit()?;
+        self.in_flight = true;
+        Ok(())
+    }
+
+    // Waits for the read in flight and returns how many bytes it read.
+    fn finish(&mut self) -> io::Result<usize> {
+        let cqe = loop {
+            if let Some(cqe) = self.ring.completion().next() {
+                break cqe;
+            }
+            match self.ring.submit_and_wait(1) {
+                Err(e) if e.kind() != io::ErrorKind::Interrupted => return Err(e),
+                _ => {}
+            }
+        };
+        self.in_flight = false;
+        usize::try_from(cqe.result()).map_err(|_| io::Error::from_raw_os_error(-cqe.result()))
+    }
+
+    fn filled(&self, i: usize, n: usize) -> &[u8] {
+        // SAFETY: the kernel wrote `n` bytes there and no read is pending
+        // into buffer `i`.
+        unsafe { std::slice::from_raw_parts(self.bufs[i].ptr.as_ptr(), n) }
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // The kernel may still write into a buffer it was given; if waiting
+        // for that fails, the buffers are leaked rather than freed under it.
+        if self.in_flight && self.finish().is_err() {
+            std::mem::forget(std::mem::take(&mut self.bufs));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_from_double_buffers() {
+        if let Err(e) = probe() {
+            eprintln!("skipped: no io_uring ({e})");
+            return;
+        }
+        let path = std::env::temp_dir().join(format!("uring-{}.txt", std::process::id()));
+        let want: Vec<u8> = (0..5 * ALIGN + 123).map(|i| i as u8).collect();
+        std::fs::write(&path, &want).unwrap();
+        let file = open(path.to_str().unwrap()).unwrap();
+        let len = want.len() as u64;
+        let mut got = Vec::new();
+        read_from(&file, ALIGN as u64, len, 2 * ALIGN, |b| {
+            got.extend_from_slice(b);
+            true
+        })
+        .unwrap();
+        assert!(want[ALIGN..] == got[..]);
+
+        // Stopping early still waits for the read ahead.
+        let mut blocks = 0;
+        read_from(&file, 0, len, ALIGN, |_| {
+            blocks += 1;
+            blocks < 2
+        })
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(2, blocks);
+    }
+}
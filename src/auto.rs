@@ -0,0 +1,125 @@
+//! `--solver auto`: reads each file the way that suits where its bytes are,
+//! so a first run gets good numbers without knowing the I/O backends.
+
+use crate::cache;
+use findlib::{
+    Backend, Compression, SolveError, SolveOptions, SolveReport, Solver, StationStats, Warnings,
+    solve_in_turn,
+};
+use std::fs::File;
+use std::io::Read;
+
+// Share of a file's pages that must be cached for mapping it to win. A
+// cached file is scanned at memory speed once mapped; an uncached one
+// would wait on a page fault per page, where reads keep the disk busy.
+const CACHED: f64 = 0.5;
+
+/// Picks a [`Backend`] per file and solves it with the solver behind it:
+/// sol1 for mmap and streaming, sol3 for io_uring.
+pub struct Auto;
+
+/// How [`Auto`] reads `path` under `opts`. Pipes are streamed. A regular
+/// file is mapped if mincore(2) finds most of it cached (or cannot tell),
+/// and otherwise read with io_uring if sol3 can take `opts` here, else
+/// streamed. Compressed files are always mapped, since only sol1's mapped
+/// path decompresses, as are files solved with previews.
+pub fn choose(path: &str, opts: &SolveOptions) -> Result<Backend, SolveError> {
+    let mut file = File::open(path)?;
+    if !file.metadata()?.is_file() {
+        return Ok(Backend::Streaming);
+    }
+    let mut head = [0; 4];
+    let n = file.read(&mut head)?;
+    if Compression::detect(&head[..n]).is_some() || opts.preview().is_some() {
+        return Ok(Backend::Mmap);
+    }
+    Ok(match cache::cached_share(&file) {
+        Some(share) if share < CACHED && sol3::reads_with_io_uring(opts) => Backend::IoUring,
+        Some(share) if share < CACHED => Backend::Streaming,
+        _ => Backend::Mmap,
+    })
+}
+
+fn solve_with(
+    backend: Backend,
+    path: String,
+    opts: &SolveOptions,
+) -> Result<Vec<StationStats>, SolveError> {
+    match backend {
+        Backend::Mmap => sol1::Sol1.solve_stats(path, opts),
+        Backend::Streaming => sol1::solve_stats_reader(File::open(path)?, opts),
+        Backend::IoUring => sol3::Sol3.solve_stats(path, opts),
+    }
+}
+
+impl Solver for Auto {
+    fn name(&self) -> &'static str {
+        "auto"
+    }
+
+    fn solve_stats(
+        &self,
+        filename: String,
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        solve_with(choose(&filename, opts)?, filename, opts)
+    }
+
+    fn solve_stats_bytes(
+        &self,
+        data: &[u8],
+        opts: &SolveOptions,
+    ) -> Result<Vec<StationStats>, SolveError> {
+        sol1::Sol1.solve_stats_bytes(data, opts)
+    }
+
+    fn solve_report(
+        &self,
+        filenames: &[String],
+        opts: &SolveOptions,
+    ) -> Result<SolveReport, SolveError> {
+        let warnings = Warnings::new();
+        let opts = opts.clone().collect_warnings(&warnings);
+        let mut backends = Vec::with_capacity(filenames.len());
+        let stats = solve_in_turn(filenames, &opts, |path, opts| {
+            let backend = choose(&path, opts)?;
+            backends.push(backend);
+            solve_with(backend, path, opts)
+        })?;
+        Ok(SolveReport {
+            stats,
+            warnings: warnings.take(),
+            backends,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose() {
+        let opts = SolveOptions::default();
+        let path = std::env::temp_dir().join(format!("auto-{}.txt", std::process::id()));
+        std::fs::write(&path, "Oslo;1.0\nRome;-4.5\nOslo;2.0\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+        // Just written, so cached.
+        let chosen = choose(&path, &opts);
+        let report = Auto.solve_report(std::slice::from_ref(&path), &opts);
+        let each: Vec<_> = [Backend::Mmap, Backend::Streaming, Backend::IoUring]
+            .map(|backend| solve_with(backend, path.clone(), &opts).unwrap())
+            .into();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Backend::Mmap, chosen.unwrap());
+        assert_eq!(Backend::Streaming, choose("/dev/null", &opts).unwrap());
+        let report = report.unwrap();
+        assert_eq!(vec![Backend::Mmap], report.backends);
+        assert_eq!(
+            "{Oslo=1.0/1.5/2.0, Rome=-4.5/-4.5/-4.5}\n",
+            findlib::format_stats(&report.stats)
+        );
+        assert!(each.iter().all(|stats| *stats == report.stats));
+    }
+}
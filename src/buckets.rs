@@ -0,0 +1,163 @@
+use anyhow::Result;
+use findlib::{BucketStats, Stat};
+use std::io::Write;
+
+/// Writes `buckets` as CSV: a `station,bucket,<selection>` header, then one
+/// record per station and bucket, the bucket as its start in ISO 8601 UTC
+/// and the statistics as the text output prints them.
+pub fn write_csv(out: impl Write, buckets: &[BucketStats], selection: &[Stat]) -> Result<()> {
+    let mut w = csv::Writer::from_writer(out);
+    let header = ["station".to_string(), "bucket".to_string()];
+    w.write_record(
+        header
+            .into_iter()
+            .chain(selection.iter().map(Stat::to_string)),
+    )?;
+    let mut record = Vec::with_capacity(2 + selection.len());
+    for b in buckets {
+        record.clear();
+        record.push(b.stats.name.clone());
+        record.push(findlib::format_timestamp(b.start));
+        for &stat in selection {
+            let mut value = String::new();
+            findlib::write_stat(&mut value, &b.stats, stat);
+            record.push(value);
+        }
+        w.write_record(&record)?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes `buckets` as one Parquet row group with the columns of
+/// [`write_csv`]: `station` (UTF-8), `bucket` (a UTC timestamp in
+/// milliseconds), then the `selection` as doubles in degrees, `count` as an
+/// int64.
+#[cfg(feature = "parquet")]
+pub fn write_parquet(
+    out: impl Write + Send,
+    buckets: &[BucketStats],
+    selection: &[Stat],
+) -> Result<()> {
+    use anyhow::Context;
+    use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    let mut schema = String::from(
+        "message buckets { required binary station (UTF8); \
+         required int64 bucket (TIMESTAMP(MILLIS,true));",
+    );
+    for &stat in selection {
+        match stat {
+            Stat::Count => schema.push_str(" required int64 count;"),
+            _ => schema.push_str(&format!(" required double {stat};")),
+        }
+    }
+    schema.push_str(" }");
+    let schema = Arc::new(parse_message_type(&schema)?);
+    let mut writer = SerializedFileWriter::new(out, schema, Default::default())?;
+    let mut group = writer.next_row_group()?;
+    // One column's values, in schema order.
+    enum Values {
+        Text(Vec<ByteArray>),
+        Int(Vec<i64>),
+        Double(Vec<f64>),
+    }
+    let mut columns = vec![
+        Values::Text(
+            buckets
+                .iter()
+                .map(|b| b.stats.name.as_str().into())
+                .collect(),
+        ),
+        Values::Int(buckets.iter().map(|b| b.start * 1000).collect()),
+    ];
+    for &stat in selection {
+        columns.push(match stat {
+            Stat::Count => Values::Int(buckets.iter().map(|b| b.stats.count as i64).collect()),
+            _ => Values::Double(
+                buckets
+                    .iter()
+                    .map(|b| b.stats.get(stat) as f64 / 10.0)
+                    .collect(),
+            ),
+        });
+    }
+    for values in columns {
+        let mut column = group
+            .next_column()?
+            .context("parquet schema ran out of columns")?;
+        match values {
+            Values::Text(v) => column
+                .typed::<ByteArrayType>()
+                .write_batch(&v, None, None)?,
+            Values::Int(v) => column.typed::<Int64Type>().write_batch(&v, None, None)?,
+            Values::Double(v) => column.typed::<DoubleType>().write_batch(&v, None, None)?,
+        };
+        column.close()?;
+    }
+    group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use findlib::StationStats;
+
+    fn bucket(name: &str, start: i64, temps: &[i32]) -> BucketStats {
+        let mut stats = StationStats::new(name, i32::MAX, i32::MIN, 0, 0);
+        for &t in temps {
+            stats.min = stats.min.min(t);
+            stats.max = stats.max.max(t);
+            stats.sum += i64::from(t);
+            stats.count += 1;
+        }
+        BucketStats { start, stats }
+    }
+
+    fn sample() -> Vec<BucketStats> {
+        vec![
+            bucket("Washington, D.C.", 1_709_298_000, &[-12, 35]),
+            bucket("Washington, D.C.", 1_709_301_600, &[40]),
+        ]
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut out = Vec::new();
+        write_csv(&mut out, &sample(), &[Stat::Min, Stat::Mean, Stat::Count]).unwrap();
+        assert_eq!(
+            "station,bucket,min,mean,count\n\
+             \"Washington, D.C.\",2024-03-01T13:00:00Z,-1.2,1.2,2\n\
+             \"Washington, D.C.\",2024-03-01T14:00:00Z,4.0,4.0,1\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::RowAccessor;
+
+        let path = std::env::temp_dir().join(format!("buckets-{}.parquet", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        write_parquet(file, &sample(), &[Stat::Max, Stat::Count]).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(2, rows.len());
+        assert_eq!("Washington, D.C.", rows[0].get_string(0).unwrap());
+        assert_eq!(1_709_298_000_000, rows[0].get_timestamp_millis(1).unwrap());
+        assert_eq!(3.5, rows[0].get_double(2).unwrap());
+        assert_eq!(1, rows[1].get_long(3).unwrap());
+    }
+}
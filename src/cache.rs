@@ -0,0 +1,103 @@
+#[cfg(not(target_os = "linux"))]
+use anyhow::bail;
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::Path;
+
+/// How [`evict`] got the input out of the page cache.
+#[derive(Clone, Copy, Debug)]
+pub enum Eviction {
+    /// Wrote `/proc/sys/vm/drop_caches`, emptying the whole page cache.
+    DropCaches,
+    /// Advised the input's pages away; `left` of them were still cached
+    /// afterwards (mapped or locked by another process, say).
+    Fadvise { left: usize },
+}
+
+impl fmt::Display for Eviction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Eviction::DropCaches => f.write_str("drop_caches"),
+            Eviction::Fadvise { left: 0 } => f.write_str("fadvise"),
+            Eviction::Fadvise { left } => write!(f, "fadvise, {left} pages still cached"),
+        }
+    }
+}
+
+/// Gets `path` out of the page cache so the next read comes from disk. With
+/// the privileges for it this drops the whole cache; otherwise the file's
+/// own pages are advised away with `posix_fadvise(DONTNEED)` until none are
+/// left resident or a few rounds stop making progress.
+#[cfg(target_os = "linux")]
+pub fn evict(path: &Path) -> Result<Eviction> {
+    const ROUNDS: usize = 4;
+
+    unsafe { libc::sync() };
+    if std::fs::write("/proc/sys/vm/drop_caches", "1").is_ok() {
+        return Ok(Eviction::DropCaches);
+    }
+
+    let file = std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut left = resident_pages(&file)?;
+    for _ in 0..ROUNDS {
+        if left == 0 {
+            break;
+        }
+        let fd = std::os::fd::AsRawFd::as_raw_fd(&file);
+        let err = unsafe { libc::posix_fadvise(fd, 0, 0, libc::POSIX_FADV_DONTNEED) };
+        if err != 0 {
+            return Err(std::io::Error::from_raw_os_error(err))
+                .with_context(|| format!("posix_fadvise on {}", path.display()));
+        }
+        let now = resident_pages(&file)?;
+        if now >= left {
+            left = now;
+            break;
+        }
+        left = now;
+    }
+    Ok(Eviction::Fadvise { left })
+}
+
+/// The share of `file`'s pages in the page cache, if mincore(2) can tell.
+#[cfg(target_os = "linux")]
+pub fn cached_share(file: &std::fs::File) -> Option<f64> {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+    let pages = file.metadata().ok()?.len().div_ceil(page);
+    match pages {
+        0 => Some(1.0),
+        _ => Some(resident_pages(file).ok()? as f64 / pages as f64),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn cached_share(_file: &std::fs::File) -> Option<f64> {
+    None
+}
+
+// Pages of `file` in the page cache, per mincore(2).
+#[cfg(target_os = "linux")]
+fn resident_pages(file: &std::fs::File) -> Result<usize> {
+    if file.metadata()?.len() == 0 {
+        return Ok(0);
+    }
+    let map = unsafe { memmap2::Mmap::map(file) }.context("mapping input for mincore")?;
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let mut vec = vec![0u8; map.len().div_ceil(page)];
+    let rc = unsafe {
+        libc::mincore(
+            map.as_ptr() as *mut libc::c_void,
+            map.len(),
+            vec.as_mut_ptr(),
+        )
+    };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error()).context("mincore");
+    }
+    Ok(vec.iter().filter(|&&b| b & 1 != 0).count())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn evict(_path: &Path) -> Result<Eviction> {
+    bail!("--cold is only supported on Linux")
+}
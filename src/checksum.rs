@@ -0,0 +1,134 @@
+use crate::exit::Exit;
+use anyhow::{Context, Result, bail};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// Bytes covered by one block hash. A mismatch names the block, so the
+/// damaged part of a copy can be found without diffing whole files.
+const BLOCK_SIZE: u64 = 64 << 20;
+
+const ALGORITHM: &str = "xxh3";
+
+/// Size and per-block hashes of an input file, as printed by `checksum` and
+/// read back by `--verify-checksum`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub size: u64,
+    pub block_size: u64,
+    pub algorithm: String,
+    /// XXH3 (64-bit, seed 0) of each block, as 16 hex digits.
+    pub blocks: Vec<String>,
+}
+
+impl Manifest {
+    /// Hashes the file at `path` on a pool of `workers` threads of its own.
+    pub fn of_file(path: &Path, workers: usize) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        let data = unsafe { memmap2::Mmap::map(&file) }
+            .with_context(|| format!("mapping {}", path.display()))?;
+        Self::of_bytes(&data, BLOCK_SIZE, workers)
+    }
+
+    fn of_bytes(data: &[u8], block_size: u64, workers: usize) -> Result<Self> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(workers)
+            .build()
+            .context("starting checksum threads")?;
+        let blocks = pool.install(|| {
+            data.par_chunks(block_size as usize)
+                .map(|b| format!("{:016x}", xxh3_64(b)))
+                .collect()
+        });
+        Ok(Self {
+            size: data.len() as u64,
+            block_size,
+            algorithm: ALGORITHM.to_string(),
+            blocks,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading manifest {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("parsing manifest {}", path.display()))
+    }
+
+    /// Checks the file at `path` against this manifest, hashing it on
+    /// `workers` threads, and names the first block that differs.
+    pub fn verify(&self, path: &Path, workers: usize) -> Result<()> {
+        if self.algorithm != ALGORITHM {
+            bail!("unsupported checksum algorithm {:?}", self.algorithm);
+        }
+        if self.block_size == 0 {
+            bail!("manifest block size is zero");
+        }
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        let data = unsafe { memmap2::Mmap::map(&file) }
+            .with_context(|| format!("mapping {}", path.display()))?;
+        self.check(&Self::of_bytes(&data, self.block_size, workers)?)
+            .with_context(|| format!("{} does not match its manifest", path.display()))
+            .context(Exit::Mismatch)
+    }
+
+    fn check(&self, actual: &Manifest) -> Result<()> {
+        if self.size != actual.size {
+            bail!("size is {} bytes, expected {}", actual.size, self.size);
+        }
+        let bad = self
+            .blocks
+            .iter()
+            .zip(&actual.blocks)
+            .position(|(a, b)| a != b);
+        if let Some(i) = bad.or((self.blocks.len() != actual.blocks.len()).then_some(0)) {
+            let start = i as u64 * self.block_size;
+            let end = (start + self.block_size).min(self.size);
+            bail!("block {i} (bytes {start}..{end}) differs");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_hashes() {
+        let manifest = Manifest::of_bytes(b"Oslo;1.0\nRome;2.0\n", 8, 2).unwrap();
+        assert_eq!("xxh3", manifest.algorithm);
+        assert_eq!(3, manifest.blocks.len());
+        assert_eq!(
+            format!("{:016x}", xxh3_64(b"\nRome;2.")),
+            manifest.blocks[1]
+        );
+        assert_eq!("2d06800538d394c2", format!("{:016x}", xxh3_64(b"")));
+
+        let old = Manifest {
+            algorithm: "xxh64".to_string(),
+            ..manifest
+        };
+        let err = old.verify(Path::new("unused"), 1).unwrap_err();
+        assert_eq!("unsupported checksum algorithm \"xxh64\"", err.to_string());
+    }
+
+    #[test]
+    fn test_check_names_block() {
+        let want = Manifest::of_bytes(b"Oslo;1.0\nRome;2.0\n", 8, 2).unwrap();
+        assert!(
+            want.check(&Manifest::of_bytes(b"Oslo;1.0\nRome;2.0\n", 8, 2).unwrap())
+                .is_ok()
+        );
+
+        let err = want
+            .check(&Manifest::of_bytes(b"Oslo;1.0\nRome;2.5\n", 8, 2).unwrap())
+            .unwrap_err();
+        assert_eq!("block 2 (bytes 16..18) differs", err.to_string());
+        let err = want
+            .check(&Manifest::of_bytes(b"Oslo;1.0\n", 8, 2).unwrap())
+            .unwrap_err();
+        assert_eq!("size is 9 bytes, expected 18", err.to_string());
+    }
+}
@@ -0,0 +1,167 @@
+use clap::ValueEnum;
+use findlib::{Columns, InputFormat};
+use std::io::{self, Write};
+
+/// What `convert --to` writes: one of the row formats the solvers read, or
+/// zstd-compressed text or a Parquet file in builds with those features.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Target {
+    #[value(alias = "txt")]
+    Text,
+    Jsonl,
+    Binary,
+    /// `name;value` text in one zstd frame, which sol1 reads as it is.
+    Zstd,
+    /// `station` (UTF-8) and `temp` (a double, in degrees) columns.
+    Parquet,
+}
+
+// Rows per Parquet row group: the rows are buffered a group at a time, so
+// memory stays flat however long the input is.
+#[cfg(feature = "parquet")]
+const ROW_GROUP: usize = 1 << 20;
+
+/// Rewrites the rows of `data`, read as `from` and split by `columns`, to
+/// `out` as `to`; see [`sol1::convert`]. Returns how many were written.
+pub fn convert(
+    data: &[u8],
+    from: InputFormat,
+    columns: Columns,
+    to: Target,
+    out: impl Write + Send,
+) -> io::Result<u64> {
+    match to {
+        Target::Text => sol1::convert(data, from, columns, InputFormat::Text, out),
+        Target::Jsonl => sol1::convert(data, from, columns, InputFormat::Jsonl, out),
+        Target::Binary => sol1::convert(data, from, columns, InputFormat::Binary, out),
+        #[cfg(feature = "zstd")]
+        Target::Zstd => {
+            let mut out = zstd::Encoder::new(out, 0)?;
+            let rows = sol1::convert(data, from, columns, InputFormat::Text, &mut out)?;
+            out.finish()?;
+            Ok(rows)
+        }
+        #[cfg(feature = "parquet")]
+        Target::Parquet => write_parquet(data, from, columns, out),
+        #[allow(unreachable_patterns)]
+        _ => {
+            let name = to.to_possible_value().expect("no target is skipped");
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("this build has no {} support", name.get_name()),
+            ))
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(
+    data: &[u8],
+    from: InputFormat,
+    columns: Columns,
+    out: impl Write + Send,
+) -> io::Result<u64> {
+    use parquet::data_type::{ByteArray, ByteArrayType, DoubleType};
+    use parquet::errors::ParquetError;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    fn group<W: Write + Send>(
+        writer: &mut SerializedFileWriter<W>,
+        names: &mut Vec<ByteArray>,
+        temps: &mut Vec<f64>,
+    ) -> Result<(), ParquetError> {
+        let mut group = writer.next_row_group()?;
+        let mut column = group.next_column()?.expect("station column");
+        column
+            .typed::<ByteArrayType>()
+            .write_batch(names, None, None)?;
+        column.close()?;
+        let mut column = group.next_column()?.expect("temp column");
+        column
+            .typed::<DoubleType>()
+            .write_batch(temps, None, None)?;
+        column.close()?;
+        group.close()?;
+        names.clear();
+        temps.clear();
+        Ok(())
+    }
+
+    let schema = "message rows { required binary station (UTF8); required double temp; }";
+    let schema = Arc::new(parse_message_type(schema).map_err(io::Error::other)?);
+    let mut writer =
+        SerializedFileWriter::new(out, schema, Default::default()).map_err(io::Error::other)?;
+    let (mut names, mut temps) = (Vec::new(), Vec::new());
+    let rows = sol1::for_each_row(data, from, columns, |name, val| {
+        names.push(ByteArray::from(name.to_vec()));
+        temps.push(val as f64 / 10.0);
+        if names.len() == ROW_GROUP {
+            group(&mut writer, &mut names, &mut temps).map_err(io::Error::other)?;
+        }
+        Ok(())
+    })?;
+    if !names.is_empty() {
+        group(&mut writer, &mut names, &mut temps).map_err(io::Error::other)?;
+    }
+    writer.close().map_err(io::Error::other)?;
+    Ok(rows)
+}
+
+#[cfg(all(test, any(feature = "zstd", feature = "parquet")))]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        let mut out = Vec::new();
+        let rows = convert(
+            b"Oslo\t1.0\r\nRome\t-4.5\n",
+            InputFormat::Text,
+            Columns {
+                delimiter: b'\t',
+                value: 2,
+            },
+            Target::Zstd,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(2, rows);
+        let got = sol1::solve_stats_bytes(&out).unwrap();
+        assert_eq!(
+            "{Oslo=1.0/1.0/1.0, Rome=-4.5/-4.5/-4.5}\n",
+            findlib::format_stats(&got)
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::RowAccessor;
+
+        let path = std::env::temp_dir().join(format!("convert-{}.parquet", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        let data = b"Oslo;1.0\nS\xc3\xa3o Paulo;-4.5\n";
+        let rows = convert(
+            data,
+            InputFormat::Text,
+            Columns::CHALLENGE,
+            Target::Parquet,
+            file,
+        );
+        assert_eq!(2, rows.unwrap());
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!("São Paulo", rows[1].get_string(0).unwrap());
+        assert_eq!(-4.5, rows[1].get_double(1).unwrap());
+        assert_eq!(1.0, rows[0].get_double(1).unwrap());
+    }
+}
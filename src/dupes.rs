@@ -0,0 +1,38 @@
+use std::collections::BTreeMap;
+use unicode_normalization::UnicodeNormalization;
+
+/// Groups station names that are distinct byte strings but equal after NFKC
+/// normalization and case folding, e.g. `Zürich`, `ZÜRICH` and a decomposed
+/// `Zu\u{308}rich`. Each group is sorted and has at least two names.
+pub fn near_duplicates<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<Vec<&'a str>> {
+    let mut groups: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for name in names {
+        let key = name.nfkc().flat_map(char::to_lowercase).collect();
+        groups.entry(key).or_default().push(name);
+    }
+    groups
+        .into_values()
+        .filter_map(|mut g| {
+            g.sort_unstable();
+            g.dedup();
+            (g.len() > 1).then_some(g)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_near_duplicates() {
+        let names = ["Zürich", "Oslo", "ZÜRICH", "Zu\u{308}rich", "oslo", "Rome"];
+        assert_eq!(
+            vec![
+                vec!["Oslo", "oslo"],
+                vec!["Zu\u{308}rich", "ZÜRICH", "Zürich"]
+            ],
+            near_duplicates(names)
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+use std::process::ExitCode;
+
+/// Why a run failed, for scripts that branch on the exit status. Attached
+/// to an error as context (`.context(Exit::Parse)`); errors without one,
+/// bad arguments included, exit with 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exit {
+    /// The input file does not exist.
+    NotFound = 2,
+    /// The input has rows the solver cannot parse.
+    Parse = 3,
+    /// The input differs from its `--verify-checksum` or `--replay`
+    /// manifest, or `verify` found the solvers disagree.
+    Mismatch = 4,
+    /// The output was written but lacks the rows of blocks skipped by
+    /// `--on-chunk-panic skip`.
+    Partial = 5,
+}
+
+impl Exit {
+    /// The exit status for `err`: its outermost `Exit`, or 1.
+    pub fn code(err: &anyhow::Error) -> ExitCode {
+        ExitCode::from(err.downcast_ref::<Exit>().map_or(1, |&e| e as u8))
+    }
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Exit::NotFound => "input not found",
+            Exit::Parse => "malformed input",
+            Exit::Mismatch => "verification failed",
+            Exit::Partial => "partial result",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    #[test]
+    fn test_code_sees_through_context() {
+        let tagged = Err::<(), _>(anyhow!("block 0 differs"))
+            .context(Exit::Mismatch)
+            .context("verifying data/measurements.txt")
+            .unwrap_err();
+        assert_eq!(ExitCode::from(4), Exit::code(&tagged));
+        assert_eq!(ExitCode::from(1), Exit::code(&anyhow!("no such flag")));
+    }
+}
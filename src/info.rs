@@ -0,0 +1,118 @@
+use std::fmt::Write as _;
+
+/// Build and host facts worth pasting into a bug report or next to a
+/// benchmark result.
+pub fn report() -> String {
+    let mut out = String::new();
+    writeln!(out, "version: {}", env!("CARGO_PKG_VERSION")).unwrap();
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    writeln!(out, "build: {profile}").unwrap();
+    writeln!(
+        out,
+        "target: {}-{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    )
+    .unwrap();
+    writeln!(out, "features: {}", features().join(" ")).unwrap();
+    writeln!(out, "cpu features: {}", cpu_features().join(" ")).unwrap();
+    writeln!(out, "threads: {}", findlib::cpu::default_threads()).unwrap();
+    let hybrid = if findlib::cpu::is_hybrid() {
+        "yes"
+    } else {
+        "no"
+    };
+    writeln!(out, "hybrid cores: {hybrid}").unwrap();
+    match page_size() {
+        Some(size) => writeln!(out, "page size: {size}").unwrap(),
+        None => writeln!(out, "page size: unknown").unwrap(),
+    }
+    out
+}
+
+// The optional cargo features this binary was built with.
+fn features() -> Vec<&'static str> {
+    let built = [
+        ("regex", cfg!(feature = "regex")),
+        ("flate2", cfg!(feature = "flate2")),
+        ("zstd", cfg!(feature = "zstd")),
+        ("parquet", cfg!(feature = "parquet")),
+        ("arrow", cfg!(feature = "arrow")),
+    ];
+    let found: Vec<_> = built.iter().filter(|f| f.1).map(|f| f.0).collect();
+    if found.is_empty() {
+        vec!["none"]
+    } else {
+        found
+    }
+}
+
+fn cpu_features() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut found = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    {
+        if std::arch::is_x86_feature_detected!("sse4.2") {
+            found.push("sse4.2");
+        }
+        if std::arch::is_x86_feature_detected!("avx2") {
+            found.push("avx2");
+        }
+        if std::arch::is_x86_feature_detected!("bmi2") {
+            found.push("bmi2");
+        }
+        if std::arch::is_x86_feature_detected!("avx512f") {
+            found.push("avx512f");
+        }
+        if std::arch::is_x86_feature_detected!("avx512bw") {
+            found.push("avx512bw");
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            found.push("neon");
+        }
+        if std::arch::is_aarch64_feature_detected!("sve") {
+            found.push("sve");
+        }
+    }
+    if found.is_empty() {
+        found.push("none");
+    }
+    found
+}
+
+#[cfg(target_os = "linux")]
+fn page_size() -> Option<usize> {
+    usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn page_size() -> Option<usize> {
+    None
+}
+
+/// Time since the kernel started this process, from `/proc`, so at clock
+/// tick (usually 10 ms) resolution.
+#[cfg(target_os = "linux")]
+pub fn since_exec() -> Option<std::time::Duration> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name may contain spaces; fields after it start at the 3rd,
+    // so the 22nd (start time in ticks since boot) is the 20th here.
+    let rest = stat.get(stat.rfind(')')? + 2..)?;
+    let started: f64 = rest.split(' ').nth(19)?.parse().ok()?;
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let uptime: f64 = uptime.split(' ').next()?.parse().ok()?;
+    let hz = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64;
+    (hz > 0.0).then(|| std::time::Duration::from_secs_f64((uptime - started / hz).max(0.0)))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn since_exec() -> Option<std::time::Duration> {
+    None
+}
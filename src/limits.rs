@@ -0,0 +1,248 @@
+use anyhow::{Context, Result, bail};
+use std::str::FromStr;
+
+/// I/O scheduling class for `--ionice`, written as `idle`,
+/// `best-effort[:0-7]` or `realtime[:0-7]` like ionice(1).
+#[derive(Clone, Copy, Debug)]
+pub enum IoNice {
+    Realtime(u8),
+    BestEffort(u8),
+    Idle,
+}
+
+impl FromStr for IoNice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (class, level) = match s.split_once(':') {
+            Some((c, l)) => {
+                let l = l
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|l| *l <= 7)
+                    .ok_or_else(|| format!("invalid ionice level {l:?}, expected 0-7"))?;
+                (c, l)
+            }
+            None => (s, 4),
+        };
+        match class {
+            "realtime" => Ok(IoNice::Realtime(level)),
+            "best-effort" => Ok(IoNice::BestEffort(level)),
+            "idle" => Ok(IoNice::Idle),
+            _ => Err(format!(
+                "unknown ionice class {class:?}, expected realtime, best-effort or idle"
+            )),
+        }
+    }
+}
+
+/// Parses a byte count with an optional K/M/G/T suffix (powers of 1024).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let (digits, shift) = match s.as_bytes().last() {
+        Some(b'K' | b'k') => (&s[..s.len() - 1], 10),
+        Some(b'M' | b'm') => (&s[..s.len() - 1], 20),
+        Some(b'G' | b'g') => (&s[..s.len() - 1], 30),
+        Some(b'T' | b't') => (&s[..s.len() - 1], 40),
+        _ => (s, 0),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .ok_or_else(|| format!("invalid size {s:?}"))
+}
+
+/// Parses a share of the CPUs for `--cpu-budget`: `1%` to `100%`.
+pub fn parse_percent(s: &str) -> Result<u32, String> {
+    s.strip_suffix('%')
+        .and_then(|n| n.parse::<u32>().ok())
+        .filter(|n| (1..=100).contains(n))
+        .ok_or_else(|| format!("invalid CPU budget {s:?}, expected 1% to 100%"))
+}
+
+// The CPUs to keep out of `cores` (each the CPUs of one physical core, in
+// order): with `whole_cores`, every SMT sibling of as few cores as will
+// do; otherwise one CPU per core before any core's second.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn pick_cpus(cores: &[Vec<usize>], keep: usize, whole_cores: bool) -> Vec<usize> {
+    let mut cpus: Vec<usize> = if whole_cores {
+        cores.iter().flatten().copied().take(keep).collect()
+    } else {
+        let deepest = cores.iter().map(Vec::len).max().unwrap_or(0);
+        (0..deepest)
+            .flat_map(|i| cores.iter().filter_map(move |core| core.get(i).copied()))
+            .take(keep)
+            .collect()
+    };
+    cpus.sort_unstable();
+    cpus
+}
+
+/// Pins this process to `percent` of the CPUs it may run on (rounded up,
+/// at least one) and returns how many it kept of how many, so the solve
+/// starts that many workers. By default the CPUs are spread one per
+/// physical core, which loses the least throughput; with `whole_cores`
+/// they are the SMT siblings of as few cores as will do, so the other cores
+/// stay entirely free. Like [`set_nice`], this must run before the solver
+/// starts its workers, which inherit the mask.
+#[cfg(target_os = "linux")]
+pub fn set_cpu_budget(percent: u32, whole_cores: bool) -> Result<(usize, usize)> {
+    // SAFETY: cpu_set_t is plain data, and the calls get its real size.
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("sched_getaffinity");
+    }
+    let allowed: Vec<usize> = (0..libc::CPU_SETSIZE as usize)
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect();
+    // Siblings share a thread_siblings_list; a CPU without one is a core.
+    let mut cores: Vec<(String, Vec<usize>)> = Vec::new();
+    for &cpu in &allowed {
+        let path = format!("/sys/devices/system/cpu/cpu{cpu}/topology/thread_siblings_list");
+        let key = std::fs::read_to_string(path).unwrap_or_else(|_| format!("cpu{cpu}"));
+        match cores.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, cpus)) => cpus.push(cpu),
+            None => cores.push((key, vec![cpu])),
+        }
+    }
+    let cores: Vec<Vec<usize>> = cores.into_iter().map(|(_, cpus)| cpus).collect();
+    let keep = (allowed.len() * percent as usize).div_ceil(100).max(1);
+    let cpus = pick_cpus(&cores, keep, whole_cores);
+
+    unsafe { libc::CPU_ZERO(&mut set) };
+    for &cpu in &cpus {
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    if unsafe { libc::sched_setaffinity(0, size, &set) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("sched_setaffinity");
+    }
+    Ok((cpus.len(), allowed.len()))
+}
+
+/// Lowers the CPU priority of the calling thread and every thread it spawns
+/// afterwards, so this must run before the solver starts its workers.
+#[cfg(target_os = "linux")]
+pub fn set_nice(nice: i32) -> Result<()> {
+    // On Linux PRIO_PROCESS with who == 0 applies to the calling thread.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("setpriority");
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn set_ionice(io: IoNice) -> Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: u32 = 13;
+
+    let (class, level) = match io {
+        IoNice::Realtime(l) => (1, l),
+        IoNice::BestEffort(l) => (2, l),
+        IoNice::Idle => (3, 0),
+    };
+    let prio = (class << IOPRIO_CLASS_SHIFT) | level as u32;
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, prio) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("ioprio_set");
+    }
+    Ok(())
+}
+
+/// Writes `memory.max` of the cgroup-v2 group this process belongs to. This
+/// limits every process in that group, so it is meant for a dedicated
+/// (delegated) cgroup; without write access it fails with a clear error.
+#[cfg(target_os = "linux")]
+pub fn set_memory_max(bytes: u64) -> Result<()> {
+    use std::io::Write;
+
+    let cgroups =
+        std::fs::read_to_string("/proc/self/cgroup").context("reading /proc/self/cgroup")?;
+    let Some(path) = cgroups.lines().find_map(|l| l.strip_prefix("0::")) else {
+        bail!("--memory-max needs cgroup v2, but this process is not in a unified hierarchy");
+    };
+    let file = format!("/sys/fs/cgroup{}/memory.max", path.trim_end_matches('/'));
+    // Open without create: on hybrid hierarchies /sys/fs/cgroup is a tmpfs and
+    // a missing memory.max must not be silently created as a plain file.
+    let mut f = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&file)
+        .with_context(|| format!("opening {file}"))?;
+    f.write_all(bytes.to_string().as_bytes())
+        .with_context(|| format!("writing {file}"))?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_nice(_nice: i32) -> Result<()> {
+    bail!("--nice is only supported on Linux")
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_ionice(_io: IoNice) -> Result<()> {
+    bail!("--ionice is only supported on Linux")
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_memory_max(_bytes: u64) -> Result<()> {
+    bail!("--memory-max is only supported on Linux")
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_cpu_budget(_percent: u32, _whole_cores: bool) -> Result<(usize, usize)> {
+    bail!("--cpu-budget is only supported on Linux")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(Ok(512), parse_size("512"));
+        assert_eq!(Ok(4 << 30), parse_size("4G"));
+        assert_eq!(Ok(256 << 20), parse_size("256m"));
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(Ok(50), parse_percent("50%"));
+        assert_eq!(Ok(100), parse_percent("100%"));
+        for bad in ["50", "0%", "101%", "%", "0.5%"] {
+            assert!(parse_percent(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn test_pick_cpus() {
+        // Four cores with two threads each, siblings numbered apart.
+        let cores: Vec<Vec<usize>> = (0..4).map(|c| vec![c, c + 4]).collect();
+        assert_eq!(vec![0, 1, 2], pick_cpus(&cores, 3, false));
+        assert_eq!(vec![0, 1, 2, 3, 4, 5], pick_cpus(&cores, 6, false));
+        assert_eq!(vec![0, 1, 4, 5], pick_cpus(&cores, 4, true));
+        assert_eq!(vec![0, 1, 4], pick_cpus(&cores, 3, true));
+        assert_eq!(8, pick_cpus(&cores, 100, false).len());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_set_cpu_budget() {
+        // On a thread of its own, as the mask sticks to the calling thread.
+        let (kept, of) = std::thread::spawn(|| set_cpu_budget(100, false))
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(kept, of);
+        assert!(kept >= 1);
+    }
+
+    #[test]
+    fn test_parse_ionice() {
+        assert!(matches!("idle".parse(), Ok(IoNice::Idle)));
+        assert!(matches!("best-effort".parse(), Ok(IoNice::BestEffort(4))));
+        assert!(matches!("realtime:0".parse(), Ok(IoNice::Realtime(0))));
+        assert!("best-effort:8".parse::<IoNice>().is_err());
+        assert!("fast".parse::<IoNice>().is_err());
+    }
+}
@@ -1,30 +1,1173 @@
-use anyhow::{Result, bail};
-use clap::{Parser, arg};
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand, ValueEnum};
+use exit::Exit;
+use findlib::Stat;
+use std::hash::{BuildHasher, RandomState};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+mod auto;
+mod buckets;
+mod cache;
+mod checksum;
+mod convert;
+mod dupes;
+mod exit;
+mod info;
+mod limits;
+mod metadata;
+mod output;
+mod profiling;
+mod progress;
+mod replay;
+mod results_log;
+mod sandbox;
+mod shards;
+mod verify;
+
+#[cfg(feature = "dhat-heap")]
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum GroupBy {
+    Country,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ChunkReport {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum BucketFormat {
+    Csv,
+    Parquet,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+    Canonical,
+}
+
+// Prints a note to stderr unless `--quiet`; errors are printed regardless.
+macro_rules! note {
+    ($quiet:expr, $($arg:tt)*) => {
+        if !$quiet {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SolverKind {
+    Sol1,
+    Sol2,
+    Sol3,
+    Auto,
+}
+
+impl SolverKind {
+    fn solver(self) -> &'static dyn findlib::Solver {
+        match self {
+            SolverKind::Sol1 => &sol1::Sol1,
+            SolverKind::Sol2 => &sol2::Sol2,
+            SolverKind::Sol3 => &sol3::Sol3,
+            SolverKind::Auto => &auto::Auto,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Report the shape of the input (value range, name lengths, delimiters,
+    /// invalid lines) instead of aggregating it.
+    Profile,
+    /// Print version, build and host details (CPU features, thread count,
+    /// page size) for bug reports and benchmark comparisons.
+    Info,
+    /// Print a JSON manifest of the input (size and a hash per 64 MiB
+    /// block) for `--verify-checksum` on another machine.
+    Checksum,
+    /// Rewrite the input in another format (text, jsonl, binary, zstd or
+    /// parquet), e.g. binary rows for timing the tables with no text
+    /// parsing. zstd and parquet need builds with those features.
+    Convert {
+        #[arg(long, default_value = "text")]
+        from: findlib::InputFormat,
+        #[arg(long)]
+        to: convert::Target,
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Write a spec-compliant measurements file (to the input path unless
+    /// --out is given) with normally distributed values per station.
+    Generate {
+        #[arg(long)]
+        rows: u64,
+        #[arg(long, default_value_t = 413, value_parser = clap::value_parser!(u64).range(1..=generate::MAX_STATIONS as u64))]
+        stations: u64,
+        /// Same seed, same file. A random one is used if not given.
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Solve the input several times with the chosen solver and print the
+    /// wall time of each run.
+    Bench {
+        #[arg(long, default_value_t = 5)]
+        runs: usize,
+        /// Evict the input from the page cache before every run, so each
+        /// one reads from disk. As root the whole cache is dropped;
+        /// otherwise only the input's pages are advised away.
+        #[arg(long)]
+        cold: bool,
+    },
+    /// Solve the input with both sol1 and sol2 and compare their output
+    /// station by station. Fails with the first station they disagree on.
+    Verify,
+    /// Solve the input and print only the `--n` stations that rank lowest
+    /// (or with --desc highest) by min, mean or max, in that order, e.g.
+    /// the ten hottest with `top --by mean --desc`.
+    Top {
+        #[arg(long, default_value = "mean")]
+        by: findlib::SortKey,
+        #[arg(long, default_value_t = 10)]
+        n: usize,
+        #[arg(long)]
+        desc: bool,
+    },
+    /// Thin out a --results-log: keep only the latest line of each window
+    /// (e.g. 1h or 1d, aligned to the epoch in UTC), rewriting it in place.
+    /// Lines a run appends meanwhile are lost, so run it between runs.
+    Compact {
+        #[arg(long)]
+        log: PathBuf,
+        #[arg(long, value_parser = findlib::parse_width)]
+        window: u64,
+    },
+}
 
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(long, default_value = "measurements.txt")]
-    name: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input file, relative to ./data unless absolute. `-` reads stdin, and
+    /// pipes (e.g. process substitution) are streamed rather than mapped.
+    /// Several files (`--name a b`, or a shell glob of absolute paths) or a
+    /// directory, which stands for the files in it, are aggregated into one
+    /// result.
+    #[arg(long, global = true, num_args = 1.., default_value = "measurements.txt")]
+    name: Vec<String>,
+
+    /// Which implementation aggregates the input. sol2 and sol3 only read
+    /// uncompressed text, reject --stations-include, --preview-interval and
+    /// the other options marked sol1 only, and report no --timings; sol3
+    /// reads with io_uring. Each solver rejects what it cannot honour. `auto`
+    /// picks per file: sol1 mapping a file mostly in the page cache, sol3
+    /// (or sol1 streaming) reading one that is not, and says which.
+    #[arg(long, global = true, value_enum, default_value = "sol1")]
+    solver: SolverKind,
+
+    /// Scan one byte at a time instead of the best kernel for this CPU, so
+    /// benchmarks on different machines run the same code (sol2 only).
+    #[arg(long, group = "kernel")]
+    force_scalar: bool,
+
+    /// Scan eight bytes at a time in a general-purpose register (sol2 only).
+    #[arg(long, group = "kernel")]
+    force_swar: bool,
+
+    /// Scan with this SIMD kernel; only `avx2` exists (sol2 only).
+    #[arg(long, group = "kernel", value_parser = parse_simd_kernel)]
+    force_simd: Option<findlib::Kernel>,
+
+    /// Sample where the CPU time goes and write a flamegraph SVG of it to
+    /// this file.
+    #[arg(long, conflicts_with = "sandbox")]
+    cpuprofile: Option<PathBuf>,
+
+    /// Record every heap allocation with its backtrace and write them to
+    /// this file for DHAT's viewer (dh_view.html), with totals and the peak
+    /// in use. Needs a build with the `dhat-heap` feature.
+    #[arg(long, conflicts_with = "sandbox")]
+    mem_profile: Option<PathBuf>,
+
+    /// Write the run's phases to this file as a Chrome trace, for
+    /// chrome://tracing or Perfetto: startup, setup, the (last) solve's
+    /// load, spawn, scan and merge, and output. Without --timings from the
+    /// solver (sol2, or no solve) the run is one span.
+    #[arg(long, conflicts_with = "sandbox")]
+    exec_profile: Option<PathBuf>,
+
+    /// Restrict the process to reading the input file (no other filesystem
+    /// or network access) before parsing starts. Linux only.
+    #[arg(long)]
+    sandbox: bool,
+
+    /// Drop stations with fewer than this many measurements from the output.
+    #[arg(long, default_value_t = 0)]
+    min_count: u64,
+
+    /// Comma-separated statistics to print per station (min, mean, max,
+    /// range, count, stddev, p50, last). stddev, p50 (which keep a
+    /// histogram per station) and last (the value furthest into the input)
+    /// need sol1.
+    #[arg(long, value_delimiter = ',', default_value = "min,mean,max")]
+    stats: Vec<Stat>,
+
+    /// Print the result as the challenge's `{name=min/mean/max, ...}` line
+    /// (`text`) or as one JSON document (`json`), `{"stations": [{"name":
+    /// ..., "min": ..., "mean": ..., "max": ...}, ...]}` with the --stats
+    /// fields in that order. With `json` nothing else goes to stdout.
+    /// `canonical` prints one `name;` line per station with fixed-width
+    /// values, each line ending in a newline, for diffing runs with git.
+    #[arg(long, value_enum, default_value = "text", conflicts_with = "split")]
+    format: OutputFormat,
+
+    /// Print exactly what the challenge's Java baseline prints, to compare
+    /// byte for byte with its published expected outputs: its name order
+    /// and mean rounding, and no options that change the result.
+    #[arg(long, conflicts_with_all = [
+        "stats", "format", "min_count", "metadata", "group_by", "station_dict", "split",
+        "stations_include", "max_rows_per_station", "lenient", "on_chunk_panic",
+    ])]
+    official: bool,
+
+    /// Print the stations in whatever order the solver's tables hold them
+    /// instead of sorted by name, for results with millions of stations
+    /// where the sort costs as much as a good part of the scan. sol1 and
+    /// sol2, one input only; other runs come out sorted regardless.
+    #[arg(long, conflicts_with_all = ["official", "output_dir", "bucket"])]
+    no_sort: bool,
+
+    /// Print nothing to stderr but errors: no warnings, previews, progress
+    /// or summaries of what was dropped or written.
+    #[arg(long, global = true)]
+    quiet: bool,
+
+    /// CSV of `name,country[,lat,lon]` rows describing each station.
+    #[arg(long, requires = "group_by")]
+    metadata: Option<PathBuf>,
+
+    /// Roll stations up using --metadata before printing.
+    #[arg(long, value_enum, requires = "metadata")]
+    group_by: Option<GroupBy>,
+
+    /// CPU scheduling niceness (-20..19) for the whole run.
+    #[arg(long, allow_negative_numbers = true, value_parser = clap::value_parser!(i32).range(-20..=19))]
+    nice: Option<i32>,
+
+    /// I/O scheduling class: idle, best-effort[:0-7] or realtime[:0-7].
+    #[arg(long)]
+    ionice: Option<limits::IoNice>,
+
+    /// Cap the memory of this process's cgroup (v2) to this many bytes,
+    /// e.g. 4G. Requires write access to the cgroup's memory.max.
+    #[arg(long, value_parser = limits::parse_size)]
+    memory_max: Option<u64>,
+
+    /// Run on this share of the CPUs this process may use, e.g. 50%, as a
+    /// background job on a busy host: the process is pinned to that many
+    /// CPUs (rounded up), one per physical core first, and scans with as
+    /// many threads. Linux only.
+    #[arg(long, value_parser = limits::parse_percent, conflicts_with = "threads")]
+    cpu_budget: Option<u32>,
+
+    /// With --cpu-budget, take both SMT siblings of as few physical cores as
+    /// will do, leaving the other cores wholly to the rest of the host.
+    #[arg(long, requires = "cpu_budget")]
+    cpu_budget_smt: bool,
+
+    /// File with one station name per line; only those stations are
+    /// aggregated, everything else is skipped while scanning.
+    #[arg(long)]
+    stations_include: Option<PathBuf>,
+
+    /// With --stations-include, stop scanning once every listed station has
+    /// this many measurements, for quick looks at huge files.
+    #[arg(long, requires = "stations_include")]
+    max_rows_per_station: Option<u64>,
+
+    /// Print the aggregate so far to stderr at this interval, e.g. 10s,
+    /// 500ms or 2m. With --quiet previews only go to --results-log.
+    #[arg(long, value_parser = parse_duration)]
+    preview_interval: Option<Duration>,
+
+    /// Draw a progress bar on stderr while scanning, with the throughput
+    /// and an estimate of the time left, if stderr is a terminal. Where
+    /// several inputs are solved one at a time, it starts over for each.
+    #[arg(long)]
+    progress: bool,
 
-    #[arg(long, default_value = "")]
-    cpuprofile: String,
+    /// Append each preview and the final result to this file as a line of
+    /// JSON with the time it was taken (see `compact` to thin it out), to
+    /// keep a record across long-running or repeated runs.
+    #[arg(long, conflicts_with_all = ["sandbox", "bucket"])]
+    results_log: Option<PathBuf>,
 
-    #[arg(long, default_value = "")]
-    mem_profile: String,
+    /// List station names that differ only by case or Unicode normalization
+    /// on stderr; these usually point at dirty input.
+    #[arg(long)]
+    report_near_duplicates: bool,
 
-    #[arg(long, default_value = "")]
-    exec_profile: String,
+    /// Seed the station table's hash instead of drawing a random seed per
+    /// run, for reproducible timings.
+    #[arg(long, conflicts_with = "untrusted_input")]
+    hash_seed: Option<u64>,
+
+    /// Station names may be hostile: hash them with randomly keyed hashing
+    /// so crafted names cannot pile up in one slot.
+    #[arg(long)]
+    untrusted_input: bool,
+
+    /// Check the input against a manifest from the `checksum` command
+    /// before solving, and fail if any block differs.
+    #[arg(long)]
+    verify_checksum: Option<PathBuf>,
+
+    /// Save the command line, hash seed, host details and an input manifest
+    /// to this file so the run can be repeated with `--replay`.
+    #[arg(long)]
+    record_run: Option<PathBuf>,
+
+    /// Rerun a recording from `--record-run`: same options and seed, after
+    /// checking the input is unchanged. Host differences are printed.
+    #[arg(long, exclusive = true)]
+    replay: Option<PathBuf>,
+
+    /// Row layout of the input: `text` (`name;value` lines), `jsonl` (one
+    /// `{"station": ..., "temp": ...}` object per line), `binary`
+    /// (length-prefixed rows from the `convert` command) or `arrow` (an
+    /// Arrow IPC/Feather file with `station` and `temp` columns, in builds
+    /// with the `arrow` feature; sol1 only).
+    #[arg(long, default_value = "text")]
+    input_format: findlib::InputFormat,
+
+    /// Byte that separates the fields of text rows: one ASCII character,
+    /// or `tab` (also `\t`) for tab-separated files.
+    #[arg(long, default_value = ";", value_parser = parse_delimiter)]
+    delimiter: u8,
+
+    /// Which field of a text row holds the value, counting from 1 as `cut
+    /// -f` does; the station is always the first. Rows may have more
+    /// fields than this; the ones after it are not looked at.
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u16).range(2..))]
+    value_column: u16,
+
+    /// Which field of a text row holds its time, counting from 1 like
+    /// --value-column: whole seconds since the epoch, or ISO 8601 such as
+    /// `2024-03-01T13:05:00Z` (UTC unless it has an offset). For --bucket.
+    #[arg(long, requires = "bucket", value_parser = clap::value_parser!(u16).range(2..))]
+    timestamp_column: Option<u16>,
+
+    /// Aggregate per station and per time bucket of this width (e.g. 15m,
+    /// 1h or 1d, aligned to the epoch in UTC) instead of per station, and
+    /// print one row per station and bucket as --bucket-format. Every row is
+    /// checked; malformed ones fail the run unless --lenient. sol1 and one
+    /// input only.
+    #[arg(long, value_parser = findlib::parse_width, requires = "timestamp_column",
+          conflicts_with_all = [
+              "official", "format", "min_count", "group_by", "station_dict", "split",
+              "max_rows_per_station", "preview_interval", "report_near_duplicates",
+          ])]
+    bucket: Option<u64>,
+
+    /// How --bucket prints its rows: `csv` with a `station,bucket,<--stats>`
+    /// header, or `parquet` (in builds with the `parquet` feature), with the
+    /// bucket as a UTC timestamp and the statistics as doubles.
+    #[arg(long, value_enum, default_value = "csv", requires = "bucket")]
+    bucket_format: BucketFormat,
+
+    /// Scan with this many threads instead of one per available core, e.g.
+    /// to sweep thread counts with `bench`. Checksums are hashed on as many.
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+
+    /// Print to stderr where the time went: process start, setup, loading
+    /// the input, starting workers, scanning and merging.
+    #[arg(long)]
+    timings: bool,
+
+    /// Print each block the scan handed to a worker to stderr: its byte
+    /// range, worker, rows, distinct stations and time, as a `table` (the
+    /// default) or one `json` object per line. sol1 only.
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "table")]
+    debug_chunks: Option<ChunkReport>,
+
+    /// What to do when scanning a block fails (on a value with a symbol
+    /// the parser does not handle, or in a panic): `fail` naming the block's byte
+    /// range, or `skip` its rows with a warning. sol1 only.
+    #[arg(long, default_value = "fail")]
+    on_chunk_panic: findlib::PanicPolicy,
+
+    /// Drop each block's pages from memory once scanned, so a file larger
+    /// than RAM does not bloat the process. sol1 only.
+    #[arg(long)]
+    release_pages: bool,
+
+    /// Keep no more than this much of a file in memory, e.g. 256M: a larger
+    /// file is read (sol1) or mapped (sol2) in windows of whole rows, each
+    /// dropped once scanned. For 32-bit builds and small boxes.
+    #[arg(long, value_parser = limits::parse_size)]
+    max_resident: Option<u64>,
+
+    /// Soft limit on the process's resident memory, e.g. 2G: past it the
+    /// scan releases scanned pages, flushes its tables and goes on with
+    /// fewer workers, warning each time. sol1 on Linux only. It reads
+    /// /proc, so it cannot be used with --sandbox.
+    #[arg(long, value_parser = limits::parse_size, conflicts_with = "sandbox")]
+    memory_watermark: Option<u64>,
+
+    /// Check every row instead of trusting the input, and fail on a
+    /// malformed one with its line and byte offset. Slower; text input only.
+    #[arg(long, group = "validation")]
+    strict: bool,
+
+    /// Check every row instead of trusting the input, leave malformed ones
+    /// out and warn how many were skipped. Slower; text input only.
+    #[arg(long, group = "validation")]
+    lenient: bool,
+
+    /// Print stable numeric station IDs instead of names, sorted by ID. IDs
+    /// come from this file (one name per line, ID = line number) and new
+    /// stations are appended to it, so it cannot be used with --sandbox.
+    #[arg(long, conflicts_with = "sandbox")]
+    station_dict: Option<PathBuf>,
+
+    /// Write the output to this many files in --output-dir instead of
+    /// stdout, each holding a contiguous run of stations of nearly equal
+    /// length, for pipelines that post-process in parallel.
+    #[arg(long, group = "split", requires = "output_dir", conflicts_with = "sandbox",
+          value_parser = clap::value_parser!(u64).range(1..))]
+    output_shards: Option<u64>,
+
+    /// Write the output to one file per first character of the station
+    /// name in --output-dir instead of stdout, named by its code point
+    /// (`prefix-u0041.txt` for `A`).
+    #[arg(
+        long,
+        group = "split",
+        requires = "output_dir",
+        conflicts_with = "sandbox"
+    )]
+    split_by_prefix: bool,
+
+    /// Directory for --output-shards or --split-by-prefix files.
+    #[arg(long, requires = "split")]
+    output_dir: Option<PathBuf>,
+
+    /// Aggregate any line-based log: a regex whose first and second capture
+    /// groups are the station and the value. Lines that do not match are
+    /// skipped. Much slower than the default `name;value` parser. sol1 only.
+    #[cfg(feature = "regex")]
+    #[arg(long, conflicts_with = "official")]
+    extract: Option<String>,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            Exit::code(&e)
+        }
+    }
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+fn run() -> Result<()> {
+    let main_start = Instant::now();
+    let exec = info::since_exec();
+    let mut args = Args::try_parse().unwrap_or_else(|e| {
+        // clap exits with 2 on bad arguments, which here means a missing
+        // input; those are plain failures.
+        let _ = e.print();
+        std::process::exit(if e.use_stderr() { 1 } else { 0 });
+    });
+    let mut recorded_input = None;
+    if let Some(path) = &args.replay {
+        let record = replay::RunRecord::load(path)?;
+        for change in record.host_changes() {
+            note!(args.quiet, "host differs from recording: {change}");
+        }
+        args = Args::try_parse_from(std::iter::once("onebrc".to_string()).chain(record.args))?;
+        recorded_input = Some(record.input);
+    }
+
+    let profiles = profiling::Profiles::start(
+        args.cpuprofile.as_deref(),
+        args.mem_profile.as_deref(),
+        args.exec_profile.as_deref(),
+        main_start,
+        exec,
+    )?;
+    let phases = profiles.phases();
+    // A failed run still writes its profiles, but reports its own error.
+    let res = run_args(args, recorded_input, phases, main_start, exec);
+    res.and(profiles.finish())
+}
+
+// Everything after the arguments are settled, reporting the solve's
+// timings to `phases` for --exec-profile.
+fn run_args(
+    mut args: Args,
+    recorded_input: Option<checksum::Manifest>,
+    phases: Option<Arc<profiling::Phases>>,
+    main_start: Instant,
+    exec: Option<Duration>,
+) -> Result<()> {
+    // `top` solves like no subcommand, and only trims the result.
+    let solving = matches!(args.command, None | Some(Command::Top { .. }));
+    if args.format != OutputFormat::Text && !solving {
+        bail!("--format json and canonical only apply to solving, not to subcommands");
+    }
+    if args.official && args.command.is_some() {
+        bail!("--official only applies to solving, not to subcommands");
+    }
+
+    if let Some(Command::Info) = args.command {
+        print!("{}", info::report());
+        return Ok(());
+    }
+    if let Some(Command::Compact { log, window }) = &args.command {
+        let (kept, lines) = results_log::compact(log, *window)?;
+        note!(
+            args.quiet,
+            "kept {kept} of {lines} lines in {}",
+            log.display()
+        );
+        return Ok(());
+    }
 
-    if args.name.is_empty() {
+    if args.name.iter().any(String::is_empty) {
         bail!("Filename param is missing");
     }
 
-    let input_path = format!("./data/{}", args.name);
-    sol1::solve(input_path).map_err(|e| anyhow::anyhow!("{}", e))?;
+    if let Some(nice) = args.nice {
+        limits::set_nice(nice)?;
+    }
+    if let Some(io) = args.ionice {
+        limits::set_ionice(io)?;
+    }
+    if let Some(bytes) = args.memory_max {
+        limits::set_memory_max(bytes)?;
+    }
+    if let Some(percent) = args.cpu_budget {
+        let (kept, of) = limits::set_cpu_budget(percent, args.cpu_budget_smt)?;
+        note!(args.quiet, "cpu budget: running on {kept} of {of} CPUs");
+        args.threads = Some(kept);
+    }
 
+    if args.record_run.is_some() && args.hash_seed.is_none() && !args.untrusted_input {
+        // Pin the seed this run would have drawn, so a replay hashes alike.
+        args.hash_seed = Some(RandomState::new().hash_one(0u64));
+    }
+
+    let mut opts = findlib::SolveOptions::default()
+        .input_format(args.input_format)
+        .text_columns(findlib::Columns {
+            delimiter: args.delimiter,
+            value: usize::from(args.value_column),
+        });
+    if let Some(path) = &args.stations_include {
+        opts = opts.include_stations(read_station_list(path)?);
+    }
+    if let Some(n) = args.max_rows_per_station {
+        opts = opts.max_rows_per_station(n);
+    }
+    #[cfg(feature = "regex")]
+    if let Some(pattern) = &args.extract {
+        let re = findlib::Regex::new(pattern).context("parsing --extract")?;
+        opts = opts.extract_with(re);
+    }
+    if let Some(seed) = args.hash_seed {
+        opts = opts.hash_seed(seed);
+    }
+    if let Some(n) = args.threads {
+        opts = opts.threads(n);
+    }
+    opts = opts.untrusted_input(args.untrusted_input);
+    opts = opts.on_chunk_panic(args.on_chunk_panic);
+    opts = opts.release_pages(args.release_pages);
+    opts = opts.unsorted(args.no_sort);
+    if let Some(max) = args.max_resident {
+        opts = opts.max_resident_bytes(usize::try_from(max).unwrap_or(usize::MAX));
+    }
+    if let Some(bytes) = args.memory_watermark {
+        opts = opts.memory_watermark(bytes);
+    }
+    if args.stats.iter().any(|s| s.needs_distribution()) {
+        opts = opts.collect_distribution(true);
+    }
+    if args.stats.contains(&Stat::Last) {
+        opts = opts.keep_last(1);
+    }
+    if args.strict {
+        opts = opts.validate(findlib::Validation::Strict);
+    } else if args.lenient {
+        opts = opts.validate(findlib::Validation::Lenient);
+    }
+    if args.force_scalar {
+        opts = opts.force_kernel(findlib::Kernel::Scalar);
+    } else if args.force_swar {
+        opts = opts.force_kernel(findlib::Kernel::Swar);
+    } else if let Some(kernel) = args.force_simd {
+        opts = opts.force_kernel(kernel);
+    }
+    let results_log = match &args.results_log {
+        Some(path) => Some(Arc::new(results_log::ResultsLog::open(path, &args.stats)?)),
+        None => None,
+    };
+    if let Some(interval) = args.preview_interval {
+        let (log, quiet) = (results_log.clone(), args.quiet);
+        opts = opts.preview_every(interval, move |stats| {
+            if !quiet {
+                eprint!("preview: {}", findlib::format_stats(stats));
+            }
+            // The final result is still appended if a preview is not.
+            if let Some(Err(e)) = log.as_ref().map(|log| log.append(stats, false)) {
+                note!(quiet, "warning: {e:#}");
+            }
+        });
+    }
+
+    let inputs = input_paths(&args.name)?;
+    if args.bucket.is_some()
+        && (args.command.is_some() || inputs.len() > 1 || !matches!(args.solver, SolverKind::Sol1))
+    {
+        bail!("--bucket only applies to solving one input with sol1");
+    }
+    if inputs.len() > 1
+        && (!solving || args.verify_checksum.is_some() || args.record_run.is_some() || args.sandbox)
+    {
+        bail!(
+            "several inputs can only be solved, with no subcommand but top, --sandbox, --verify-checksum or --record-run"
+        );
+    }
+    for path in &inputs {
+        let meta = std::fs::metadata(path);
+        if let Err(e) = &meta
+            && e.kind() == io::ErrorKind::NotFound
+            && !matches!(args.command, Some(Command::Generate { .. }))
+        {
+            return Err(meta.unwrap_err())
+                .with_context(|| format!("opening {path}"))
+                .context(Exit::NotFound);
+        }
+        // Pipes and terminals can be read once, front to back, which only a
+        // plain sol1 solve does (see `sol1::solve_stats_reader`).
+        let streamed = meta.is_ok_and(|m| !m.is_file());
+        if streamed
+            && (!(solving || matches!(args.command, Some(Command::Generate { .. })))
+                || !matches!(args.solver, SolverKind::Sol1 | SolverKind::Auto)
+                || args.verify_checksum.is_some()
+                || args.record_run.is_some()
+                || recorded_input.is_some()
+                || args.sandbox)
+        {
+            bail!(
+                "{path} is not a regular file; it can only be solved with sol1 or auto and no --sandbox, --verify-checksum or --record-run"
+            );
+        }
+    }
+    let input_path = inputs[0].clone();
+    if let Some(Command::Checksum) = args.command {
+        let manifest = checksum::Manifest::of_file(Path::new(&input_path), opts.workers())?;
+        println!("{}", serde_json::to_string_pretty(&manifest)?);
+        return Ok(());
+    }
+    if let Some(Command::Convert { from, to, output }) = &args.command {
+        let file =
+            std::fs::File::open(&input_path).with_context(|| format!("opening {input_path}"))?;
+        let data = unsafe { memmap2::Mmap::map(&file) }
+            .with_context(|| format!("mapping {input_path}"))?;
+        let out = std::fs::File::create(output)
+            .with_context(|| format!("creating {}", output.display()))?;
+        let mut out = std::io::BufWriter::new(out);
+        let rows = convert::convert(&data, *from, opts.columns(), *to, &mut out).map_err(|e| {
+            let malformed = e.kind() == io::ErrorKind::InvalidData;
+            let e = anyhow::Error::new(e).context(format!("converting {input_path}"));
+            if malformed { e.context(Exit::Parse) } else { e }
+        })?;
+        out.into_inner()
+            .map_err(|e| e.into_error())
+            .with_context(|| format!("writing {}", output.display()))?;
+        note!(args.quiet, "wrote {rows} rows to {}", output.display());
+        return Ok(());
+    }
+    if let Some(Command::Generate {
+        rows,
+        stations,
+        seed,
+        out,
+    }) = &args.command
+    {
+        let path = out.clone().unwrap_or_else(|| PathBuf::from(&input_path));
+        let seed = seed.unwrap_or_else(|| RandomState::new().hash_one(0u64));
+        let start = Instant::now();
+        let file =
+            std::fs::File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+        let mut file = std::io::BufWriter::with_capacity(1 << 20, file);
+        generate::generate(&mut file, *rows, *stations as usize, seed, opts.workers())
+            .with_context(|| format!("writing {}", path.display()))?;
+        file.into_inner()
+            .map_err(|e| e.into_error())
+            .with_context(|| format!("writing {}", path.display()))?;
+        note!(
+            args.quiet,
+            "wrote {rows} rows to {} in {:.1?} (seed {seed})",
+            path.display(),
+            start.elapsed()
+        );
+        return Ok(());
+    }
+    if let Some(path) = &args.verify_checksum {
+        checksum::Manifest::load(path)?.verify(Path::new(&input_path), opts.workers())?;
+    }
+    if let Some(manifest) = &recorded_input {
+        manifest.verify(Path::new(&input_path), opts.workers())?;
+    }
+    if let Some(path) = &args.record_run {
+        let input = checksum::Manifest::of_file(Path::new(&input_path), opts.workers())?;
+        replay::RunRecord::new(std::env::args().skip(1), args.hash_seed, input).save(path)?;
+    }
+
+    // Read while every file is still in reach; --sandbox shuts them out.
+    let metadata = match &args.metadata {
+        Some(path) => Some(metadata::Metadata::load(path)?),
+        None => None,
+    };
+    if args.sandbox {
+        sandbox::enter(Path::new(&input_path))?;
+    }
+
+    if let Some(Command::Profile) = args.command {
+        let profile = sol1::profile(input_path).map_err(|e| anyhow::anyhow!("{}", e))?;
+        print!("{}", format_profile(&profile));
+        return Ok(());
+    }
+
+    if args.timings || phases.is_some() {
+        let print = args.timings;
+        let setup = main_start.elapsed();
+        let exec = exec.map_or("unknown".to_string(), |d| format!("{}ms", d.as_millis()));
+        opts = opts.report_timings(move |t| {
+            if print {
+                eprintln!(
+                    "timings: exec {exec}, setup {setup:.1?}, load {:.1?}, spawn {:.1?}, scan {:.1?}, merge {:.1?}",
+                    t.load, t.spawn, t.scan, t.merge
+                );
+            }
+            if let Some(phases) = &phases {
+                phases.record(t);
+            }
+        });
+    }
+
+    if let Some(report) = args.debug_chunks {
+        opts = opts.report_chunks(move |chunks| eprint!("{}", format_chunks(chunks, report)));
+    }
+    // Only a solve reports progress, and the bar's line is ended before
+    // anything else is printed.
+    let bar = match solving && args.progress && !args.quiet {
+        true => progress::Bar::new().map(Arc::new),
+        false => None,
+    };
+    if let Some(bar) = bar.clone() {
+        opts = opts.report_progress(move |done, total| bar.update(done, total));
+    }
+    // Blocks skipped under `--on-chunk-panic skip`; any make the run partial.
+    let skipped = Arc::new(AtomicUsize::new(0));
+    opts = opts.report_warnings({
+        let (skipped, quiet) = (skipped.clone(), args.quiet);
+        move |w| {
+            if let findlib::Warning::SkippedChunk(_) = w {
+                skipped.fetch_add(1, Ordering::Relaxed);
+            }
+            note!(quiet, "warning: {w}");
+        }
+    });
+
+    let solver = args.solver.solver();
+    if let Some(Command::Bench { runs, cold }) = args.command {
+        bench(solver, &input_path, &opts, runs, cold)?;
+        return check_complete(&skipped);
+    }
+    if let Some(Command::Verify) = args.command {
+        verify(&input_path, &opts, &args.stats)?;
+        return check_complete(&skipped);
+    }
+    if let (Some(width), Some(column)) = (args.bucket, args.timestamp_column) {
+        let buckets = findlib::Buckets {
+            column: usize::from(column),
+            width,
+        };
+        let found = sol1::solve_buckets(input_path, &opts, buckets);
+        if let Some(bar) = &bar {
+            bar.finish();
+        }
+        let found = found.map_err(|e| solver_error(solver, e))?;
+        let out = io::BufWriter::new(io::stdout());
+        match args.bucket_format {
+            BucketFormat::Csv => buckets::write_csv(out, &found, &args.stats)?,
+            #[cfg(feature = "parquet")]
+            BucketFormat::Parquet => buckets::write_parquet(out, &found, &args.stats)?,
+            #[cfg(not(feature = "parquet"))]
+            BucketFormat::Parquet => bail!("this build has no parquet support"),
+        }
+        return check_complete(&skipped);
+    }
+    // Only `auto` says how it read each input.
+    let mut backends = Vec::new();
+    let stats = match (args.solver, inputs.as_slice()) {
+        (SolverKind::Auto, all) => solver.solve_report(all, &opts).map(|report| {
+            backends = report.backends;
+            report.stats
+        }),
+        (_, [one]) => solver.solve_stats(one.clone(), &opts),
+        (_, many) => solver.solve_stats_files(many, &opts),
+    };
+    if let Some(bar) = &bar {
+        bar.finish();
+    }
+    for (path, backend) in inputs.iter().zip(backends) {
+        note!(args.quiet, "auto: read {path} with {backend}");
+    }
+    let mut stats = stats.map_err(|e| solver_error(solver, e))?;
+
+    if args.report_near_duplicates {
+        for group in dupes::near_duplicates(stats.iter().map(|s| s.name.as_str())) {
+            eprintln!("near-duplicate stations: {}", group.join(", "));
+        }
+    }
+
+    if args.min_count > 0 {
+        let before = stats.len();
+        stats.retain(|s| s.count >= args.min_count);
+        note!(
+            args.quiet,
+            "dropped {} stations with fewer than {} measurements",
+            before - stats.len(),
+            args.min_count
+        );
+    }
+
+    if let (Some(meta), Some(GroupBy::Country)) = (&metadata, args.group_by) {
+        let (countries, unmatched) = meta.group_by_country(&stats)?;
+        if unmatched > 0 {
+            note!(
+                args.quiet,
+                "{unmatched} stations missing from metadata, grouped as unknown"
+            );
+        }
+        stats = countries;
+    }
+
+    if let Some(path) = &args.station_dict {
+        let dict = findlib::StationDict::load(path)
+            .with_context(|| format!("reading station dictionary {}", path.display()))?;
+        stats = dict.key_by_id(stats);
+        dict.save(path)
+            .with_context(|| format!("writing station dictionary {}", path.display()))?;
+    }
+
+    if let Some(Command::Top { by, n, desc }) = args.command {
+        let order = if desc {
+            findlib::Order::Desc
+        } else {
+            findlib::Order::Asc
+        };
+        stats = findlib::query(&stats, by, order, n);
+    }
+
+    if let Some(log) = &results_log {
+        log.append(&stats, true)?;
+    }
+
+    if let Some(dir) = &args.output_dir {
+        let count = stats.len();
+        let shards = match args.output_shards {
+            Some(n) => shards::by_count(stats, n as usize),
+            None => shards::by_prefix(stats),
+        };
+        shards::write(dir, &shards, &args.stats)?;
+        note!(
+            args.quiet,
+            "wrote {count} stations to {} files in {}",
+            shards.len(),
+            dir.display()
+        );
+        return check_complete(&skipped);
+    }
+
+    if args.official {
+        print!("{}", findlib::format_official(&stats));
+        return check_complete(&skipped);
+    }
+    match args.format {
+        OutputFormat::Text => {
+            // A station at a time: the line can be as long as the input.
+            let mut out = io::BufWriter::new(io::stdout().lock());
+            findlib::stream_selected(&mut out, &stats, &args.stats)?;
+            io::Write::flush(&mut out)?;
+        }
+        OutputFormat::Json => print!("{}", output::json(&stats, &args.stats)),
+        OutputFormat::Canonical => print!("{}", output::canonical(&stats, &args.stats)),
+    }
+
+    check_complete(&skipped)
+}
+
+// The error for a solve that failed, tagged with an exit code when the
+// input is at fault.
+fn solver_error(solver: &dyn findlib::Solver, e: findlib::SolveError) -> anyhow::Error {
+    let malformed = e.is_malformed();
+    let e = anyhow::anyhow!("{}: {}", solver.name(), e);
+    if malformed { e.context(Exit::Parse) } else { e }
+}
+
+// Fails with `Exit::Partial` once the output has been written, if blocks
+// were skipped on the way.
+fn check_complete(skipped: &AtomicUsize) -> Result<()> {
+    match skipped.load(Ordering::Relaxed) {
+        0 => Ok(()),
+        n => Err(anyhow::anyhow!(
+            "skipped {n} blocks that failed; their rows are missing"
+        ))
+        .context(Exit::Partial),
+    }
+}
+
+fn bench(
+    solver: &dyn findlib::Solver,
+    input_path: &str,
+    opts: &findlib::SolveOptions,
+    runs: usize,
+    cold: bool,
+) -> Result<()> {
+    let mut times = Vec::with_capacity(runs);
+    for run in 1..=runs {
+        let eviction = if cold {
+            Some(cache::evict(Path::new(input_path))?)
+        } else {
+            None
+        };
+        let start = Instant::now();
+        solver
+            .solve_stats(input_path.to_string(), opts)
+            .map_err(|e| solver_error(solver, e))?;
+        let took = start.elapsed();
+        match eviction {
+            Some(how) => println!("run {run}: {took:.3?} (cold, {how})"),
+            None => println!("run {run}: {took:.3?}"),
+        }
+        times.push(took);
+    }
+    times.sort();
+    if let (Some(min), Some(max)) = (times.first(), times.last()) {
+        let median = times[times.len() / 2];
+        println!(
+            "{}: min {min:.3?}, median {median:.3?}, max {max:.3?}",
+            solver.name()
+        );
+    }
     Ok(())
 }
+
+fn verify(input_path: &str, opts: &findlib::SolveOptions, selection: &[Stat]) -> Result<()> {
+    let [left, right] = [SolverKind::Sol1, SolverKind::Sol2].map(|kind| {
+        let solver = kind.solver();
+        solver
+            .solve_stats(input_path.to_string(), opts)
+            .map_err(|e| solver_error(solver, e))
+    });
+    let (left, right) = (left?, right?);
+    if let Some(m) = verify::first_mismatch(&left, &right, selection) {
+        return Err(anyhow::anyhow!(
+            "first differing station (sol1 vs sol2): {m}"
+        ))
+        .context(Exit::Mismatch);
+    }
+    println!("sol1 and sol2 agree on {} stations", left.len());
+    Ok(())
+}
+
+fn format_chunks(chunks: &[findlib::ChunkStats], report: ChunkReport) -> String {
+    use std::fmt::Write as _;
+
+    let mut out = String::new();
+    if let ChunkReport::Table = report {
+        writeln!(
+            out,
+            "{:>12} {:>12} {:>6} {:>10} {:>6} {:>10}",
+            "start", "end", "worker", "rows", "keys", "time"
+        )
+        .unwrap();
+    }
+    for c in chunks {
+        match report {
+            ChunkReport::Table => writeln!(
+                out,
+                "{:>12} {:>12} {:>6} {:>10} {:>6} {:>10}",
+                c.range.start,
+                c.range.end,
+                c.worker,
+                c.rows,
+                c.keys,
+                format!("{:.1?}", c.elapsed)
+            ),
+            ChunkReport::Json => writeln!(
+                out,
+                "{}",
+                serde_json::json!({
+                    "start": c.range.start,
+                    "end": c.range.end,
+                    "worker": c.worker,
+                    "rows": c.rows,
+                    "keys": c.keys,
+                    "micros": c.elapsed.as_micros() as u64,
+                })
+            ),
+        }
+        .unwrap();
+    }
+    out
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (digits, unit) = s
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or((s, "s"), |i| s.split_at(i));
+    let n: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid duration {s:?}"))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(n)),
+        "s" => Ok(Duration::from_secs(n)),
+        "m" => Ok(Duration::from_secs(n * 60)),
+        _ => Err(format!(
+            "invalid duration {s:?}, expected e.g. 500ms, 10s or 2m"
+        )),
+    }
+}
+
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+    let byte = match s.as_bytes() {
+        b"tab" | b"\\t" => b'\t',
+        &[byte] => byte,
+        _ => 0,
+    };
+    // Bytes that can be part of a value or end a line cannot split one.
+    if byte == 0 || byte.is_ascii_digit() || b"-.\r\n".contains(&byte) {
+        return Err(format!(
+            "invalid delimiter {s:?}, expected one ASCII character other than a digit, '-' or '.', or tab"
+        ));
+    }
+    Ok(byte)
+}
+
+fn parse_simd_kernel(s: &str) -> Result<findlib::Kernel, String> {
+    match s.parse()? {
+        findlib::Kernel::Avx2 => Ok(findlib::Kernel::Avx2),
+        other => Err(format!("{other} is not a SIMD kernel, expected avx2")),
+    }
+}
+
+// Paths of the `--name` inputs: relative names are looked up in ./data,
+// `-` is stdin, and a directory is replaced by the files in it, by name.
+fn input_paths(names: &[String]) -> Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for name in names {
+        let path = match name.as_str() {
+            "-" => PathBuf::from("/dev/stdin"),
+            name => Path::new("./data").join(name),
+        };
+        if !path.is_dir() {
+            paths.push(path.display().to_string());
+            continue;
+        }
+        let mut files = Vec::new();
+        for entry in
+            std::fs::read_dir(&path).with_context(|| format!("listing {}", path.display()))?
+        {
+            let file = entry
+                .with_context(|| format!("listing {}", path.display()))?
+                .path();
+            let hidden = file
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if file.is_file() && !hidden {
+                files.push(file);
+            }
+        }
+        if files.is_empty() {
+            bail!("{} has no files to read", path.display());
+        }
+        files.sort();
+        paths.extend(files.iter().map(|f| f.display().to_string()));
+    }
+    Ok(paths)
+}
+
+fn read_station_list(path: &Path) -> Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("reading station list {}", path.display()))?;
+    Ok(text
+        .lines()
+        .map(|l| l.trim_end_matches('\r'))
+        .filter(|l| !l.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+fn format_profile(p: &sol1::Profile) -> String {
+    use std::fmt::Write as _;
+
+    let mut out = String::new();
+    let valid = p.rows - p.invalid_rows;
+    writeln!(out, "rows: {}", p.rows).unwrap();
+    let rate = if p.rows == 0 {
+        0.0
+    } else {
+        p.invalid_rows as f64 * 100.0 / p.rows as f64
+    };
+    writeln!(out, "invalid rows: {} ({rate:.4}%)", p.invalid_rows).unwrap();
+    if valid > 0 {
+        out.push_str("value min/mean/max: ");
+        findlib::write_tenths(&mut out, p.value_min as i64);
+        out.push('/');
+        findlib::write_tenths(&mut out, p.value_mean_tenths());
+        out.push('/');
+        findlib::write_tenths(&mut out, p.value_max as i64);
+        out.push('\n');
+    }
+    let chunks = &p.rows_per_chunk;
+    writeln!(
+        out,
+        "rows per chunk: {} chunks, min {}, max {}",
+        chunks.len(),
+        chunks.iter().min().unwrap_or(&0),
+        chunks.iter().max().unwrap_or(&0)
+    )
+    .unwrap();
+    out.push_str("name lengths:\n");
+    for (len, n) in p.name_lengths.iter().enumerate().filter(|(_, n)| **n > 0) {
+        if len > sol1::MAX_NAME_LEN {
+            writeln!(out, "  >{}: {n}", sol1::MAX_NAME_LEN).unwrap();
+        } else {
+            writeln!(out, "  {len}: {n}").unwrap();
+        }
+    }
+    let [none, one, more] = p.delimiters;
+    writeln!(out, "semicolons per line: 0: {none}, 1: {one}, 2+: {more}").unwrap();
+    out
+}
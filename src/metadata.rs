@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use findlib::StationStats;
+use std::collections::HashMap;
+use std::path::Path;
+
+// The group of stations missing from the metadata.
+const UNKNOWN: &str = "unknown";
+
+/// Station name -> country, loaded from a `name,country[,lat,lon,...]` CSV
+/// file. A header row is optional; columns after the country are ignored.
+pub struct Metadata {
+    countries: HashMap<String, String>,
+}
+
+impl Metadata {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)
+            .with_context(|| format!("opening metadata {}", path.display()))?;
+
+        let mut countries = HashMap::new();
+        for (idx, record) in reader.records().enumerate() {
+            let record = record.with_context(|| format!("reading {}", path.display()))?;
+            let (Some(name), Some(country)) = (record.get(0), record.get(1)) else {
+                anyhow::bail!("{}:{}: expected name,country", path.display(), idx + 1);
+            };
+            if idx == 0 && name == "name" {
+                continue;
+            }
+            countries.insert(name.to_string(), country.to_string());
+        }
+
+        Ok(Self { countries })
+    }
+
+    /// Rolls stations up into one aggregate per country, sorted by country.
+    /// Stations missing from the metadata are collected under `unknown`; the
+    /// second value is how many there were. Fails if the metadata also has a
+    /// country named `unknown`, rather than mixing the two.
+    pub fn group_by_country(&self, stats: &[StationStats]) -> Result<(Vec<StationStats>, usize)> {
+        // `None` for stations missing from the metadata.
+        let mut groups: HashMap<Option<&str>, StationStats> = HashMap::new();
+        let mut unmatched = 0;
+
+        for s in stats {
+            let country = self.countries.get(&s.name).map(String::as_str);
+            unmatched += usize::from(country.is_none());
+            groups
+                .entry(country)
+                .and_modify(|g| g.merge(s))
+                .or_insert_with(|| StationStats {
+                    name: country.unwrap_or(UNKNOWN).to_string(),
+                    ..s.clone()
+                });
+        }
+        if unmatched > 0 && groups.contains_key(&Some(UNKNOWN)) {
+            anyhow::bail!(
+                "the metadata has a country named `{UNKNOWN}`, which would mix with the \
+                 {unmatched} stations missing from it"
+            );
+        }
+
+        let mut res: Vec<StationStats> = groups.into_values().collect();
+        res.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        Ok((res, unmatched))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_by_country() {
+        let meta = Metadata {
+            countries: HashMap::from([
+                ("Boston".to_string(), "United States".to_string()),
+                ("Washington, D.C.".to_string(), "United States".to_string()),
+            ]),
+        };
+        let stats = [
+            StationStats::new("Boston", -50, 120, 200, 4),
+            StationStats::new("Oslo", -80, 40, -10, 2),
+            StationStats::new("Washington, D.C.", 10, 300, 600, 3),
+        ];
+
+        let (got, unmatched) = meta.group_by_country(&stats).unwrap();
+        assert_eq!(1, unmatched);
+        assert_eq!(
+            vec![
+                StationStats::new("United States", -50, 300, 800, 7),
+                StationStats::new("unknown", -80, 40, -10, 2),
+            ],
+            got
+        );
+    }
+
+    #[test]
+    fn test_group_by_country_unknown() {
+        let meta = Metadata {
+            countries: HashMap::from([("Boston".to_string(), "unknown".to_string())]),
+        };
+        let mut stats = vec![StationStats::new("Boston", -50, 120, 200, 4)];
+        let (got, unmatched) = meta.group_by_country(&stats).unwrap();
+        assert_eq!((1, 0), (got.len(), unmatched));
+
+        stats.push(StationStats::new("Oslo", -80, 40, -10, 2));
+        let err = meta.group_by_country(&stats).unwrap_err();
+        assert_eq!(
+            "the metadata has a country named `unknown`, which would mix with the 1 stations \
+             missing from it",
+            err.to_string()
+        );
+    }
+}
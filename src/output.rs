@@ -0,0 +1,168 @@
+use findlib::{Stat, StationStats};
+use serde::Serialize;
+
+/// The whole output of `--format json`: one object, so fields can be added
+/// next to `stations` later without breaking readers.
+#[derive(Serialize)]
+struct Document<'a> {
+    stations: Vec<Station<'a>>,
+}
+
+/// A line of `--results-log`, taken at `time` (ISO 8601 UTC).
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    time: String,
+    #[serde(rename = "final")]
+    last: bool,
+    stations: Vec<Station<'a>>,
+}
+
+// Fields serialize in declaration order, so the order is fixed no matter
+// how --stats lists them; unselected ones are left out.
+#[derive(Serialize)]
+struct Station<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mean: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    range: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stddev: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p50: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last: Option<f64>,
+}
+
+/// Formats `stats` as one JSON document on a single line, with the
+/// `selection` of statistics per station in degrees, and `count` as an
+/// integer.
+pub fn json(stats: &[StationStats], selection: &[Stat]) -> String {
+    let doc = Document {
+        stations: stations(stats, selection),
+    };
+    let mut out = serde_json::to_string(&doc).unwrap();
+    out.push('\n');
+    out
+}
+
+/// One line of a `--results-log`: [`json`]'s document with the time it was
+/// taken, `secs` since the epoch, in front and whether it is the final
+/// result or a preview.
+pub fn snapshot(secs: i64, last: bool, stats: &[StationStats], selection: &[Stat]) -> String {
+    let line = Snapshot {
+        time: findlib::format_timestamp(secs),
+        last,
+        stations: stations(stats, selection),
+    };
+    let mut out = serde_json::to_string(&line).unwrap();
+    out.push('\n');
+    out
+}
+
+fn stations<'a>(stats: &'a [StationStats], selection: &[Stat]) -> Vec<Station<'a>> {
+    let value =
+        |s: &StationStats, stat| selection.contains(&stat).then(|| s.get(stat) as f64 / 10.0);
+    stats
+        .iter()
+        .map(|s| Station {
+            name: &s.name,
+            min: value(s, Stat::Min),
+            mean: value(s, Stat::Mean),
+            max: value(s, Stat::Max),
+            range: value(s, Stat::Range),
+            count: selection.contains(&Stat::Count).then_some(s.count),
+            stddev: value(s, Stat::Stddev),
+            p50: value(s, Stat::P50),
+            last: value(s, Stat::Last),
+        })
+        .collect()
+}
+
+/// Formats `stats` for diffing results between runs: one station per line
+/// as `name;` followed by the `selection` of statistics, each right-aligned
+/// to five characters (`-99.9`) and separated by a space. `;` cannot occur
+/// in a name, so the line splits unambiguously. Every line, the last one
+/// included, ends in `\n`, and no stations print nothing at all, so
+/// concatenated or appended results stay line-aligned.
+pub fn canonical(stats: &[StationStats], selection: &[Stat]) -> String {
+    let mut out = String::with_capacity(stats.len().saturating_mul(32));
+    let mut value = String::new();
+    for s in stats {
+        out.push_str(&s.name);
+        out.push(';');
+        for (i, &stat) in selection.iter().enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+            value.clear();
+            findlib::write_stat(&mut value, s, stat);
+            out.extend(std::iter::repeat_n(' ', 5usize.saturating_sub(value.len())));
+            out.push_str(&value);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_field_order() {
+        let stats = [StationStats::new("Oslo \"N\"", -32, 105, 150, 3)];
+        assert_eq!(
+            "{\"stations\":[{\"name\":\"Oslo \\\"N\\\"\",\"min\":-3.2,\"mean\":5.0,\"max\":10.5}]}\n",
+            json(&stats, Stat::DEFAULT)
+        );
+        assert_eq!(
+            "{\"stations\":[{\"name\":\"Oslo \\\"N\\\"\",\"max\":10.5,\"range\":13.7}]}\n",
+            json(&stats, &[Stat::Range, Stat::Max])
+        );
+        assert_eq!("{\"stations\":[]}\n", json(&[], Stat::DEFAULT));
+        // A mean that rounds to zero from below is a plain 0.0.
+        let chilly = [StationStats::new("Oslo", -1, 0, -1, 3)];
+        assert_eq!(
+            "{\"stations\":[{\"name\":\"Oslo\",\"mean\":0.0}]}\n",
+            json(&chilly, &[Stat::Mean])
+        );
+        assert_eq!(
+            "{\"stations\":[{\"name\":\"Oslo\",\"count\":3}]}\n",
+            json(&chilly, &[Stat::Count])
+        );
+    }
+
+    #[test]
+    fn test_canonical_alignment() {
+        let stats = [
+            StationStats::new("Abha", -999, 999, 0, 2),
+            StationStats::new("Oslo", 5, 105, 110, 2),
+        ];
+        assert_eq!(
+            "Abha;-99.9   0.0  99.9\nOslo;  0.5   5.5  10.5\n",
+            canonical(&stats, Stat::DEFAULT)
+        );
+        assert_eq!("Oslo; 10.0\n", canonical(&stats[1..], &[Stat::Range]));
+        assert_eq!(
+            "Oslo;    2  10.5\n",
+            canonical(&stats[1..], &[Stat::Count, Stat::Max])
+        );
+        assert_eq!("", canonical(&[], Stat::DEFAULT));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let stats = [StationStats::new("Oslo", -1, 3, 2, 2)];
+        assert_eq!(
+            "{\"time\":\"2024-03-01T13:00:00Z\",\"final\":true,\"stations\":[{\"name\":\"Oslo\",\"max\":0.3}]}\n",
+            snapshot(1_709_298_000, true, &stats, &[Stat::Max])
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! The `--cpuprofile`, `--mem-profile` and `--exec-profile` outputs.
+
+use anyhow::{Context, Result};
+use findlib::Timings;
+use pprof::ProfilerGuard;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Samples per second; off the round 100 so sampling does not beat in step
+// with timers in the profiled code.
+const CPU_FREQUENCY: i32 = 99;
+
+/// The profiles a run asked for, from [`Profiles::start`] until
+/// [`Profiles::finish`] writes them.
+pub struct Profiles {
+    cpu: Option<(PathBuf, ProfilerGuard<'static>)>,
+    // Writes its file when dropped.
+    #[cfg(feature = "dhat-heap")]
+    mem: Option<dhat::Profiler>,
+    exec: Option<(PathBuf, Arc<Phases>)>,
+}
+
+/// What an execution trace is built from: when the run started, and the
+/// solve's [`Timings`] with the time they were reported.
+pub struct Phases {
+    main_start: Instant,
+    since_exec: Option<Duration>,
+    solved: Mutex<Option<(Instant, Timings)>>,
+}
+
+impl Phases {
+    /// Records a finished solve; pass it to `report_timings`.
+    pub fn record(&self, timings: &Timings) {
+        *self.solved.lock().unwrap() = Some((Instant::now(), *timings));
+    }
+}
+
+impl Profiles {
+    /// Starts the profiles whose paths are set. `main_start` and
+    /// `since_exec` place the execution trace's first phases.
+    pub fn start(
+        cpu: Option<&Path>,
+        mem: Option<&Path>,
+        exec: Option<&Path>,
+        main_start: Instant,
+        since_exec: Option<Duration>,
+    ) -> Result<Self> {
+        let cpu = match cpu {
+            Some(path) => {
+                let guard = pprof::ProfilerGuardBuilder::default()
+                    .frequency(CPU_FREQUENCY)
+                    .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+                    .build()
+                    .context("starting the CPU profiler")?;
+                Some((path.to_path_buf(), guard))
+            }
+            None => None,
+        };
+        #[cfg(feature = "dhat-heap")]
+        let mem = mem.map(|path| dhat::Profiler::builder().file_name(path).build());
+        #[cfg(not(feature = "dhat-heap"))]
+        if mem.is_some() {
+            anyhow::bail!("--mem-profile needs a build with the `dhat-heap` feature");
+        }
+        let exec = exec.map(|path| {
+            let phases = Phases {
+                main_start,
+                since_exec,
+                solved: Mutex::new(None),
+            };
+            (path.to_path_buf(), Arc::new(phases))
+        });
+        Ok(Self {
+            cpu,
+            #[cfg(feature = "dhat-heap")]
+            mem,
+            exec,
+        })
+    }
+
+    /// Where the solve should report its timings for the execution trace,
+    /// if one is being taken.
+    pub fn phases(&self) -> Option<Arc<Phases>> {
+        self.exec.as_ref().map(|(_, phases)| phases.clone())
+    }
+
+    /// Stops profiling and writes each profile to its file.
+    pub fn finish(self) -> Result<()> {
+        let end = Instant::now();
+        #[cfg(feature = "dhat-heap")]
+        drop(self.mem);
+        if let Some((path, guard)) = &self.cpu {
+            let report = guard.report().build().context("building the CPU profile")?;
+            let file =
+                File::create(path).with_context(|| format!("creating {}", path.display()))?;
+            report
+                .flamegraph(file)
+                .with_context(|| format!("writing {}", path.display()))?;
+        }
+        if let Some((path, phases)) = &self.exec {
+            write(path, trace(phases, end).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+fn write(path: &Path, data: &[u8]) -> Result<()> {
+    std::fs::write(path, data).with_context(|| format!("writing {}", path.display()))
+}
+
+// The run's phases in Chrome's trace event format, which chrome://tracing
+// and Perfetto open, with times in microseconds since the process started
+// (or since `main`, if that is unknown).
+fn trace(phases: &Phases, end: Instant) -> String {
+    let origin = phases.since_exec.unwrap_or_default();
+    let at = |t: Instant| origin + t.duration_since(phases.main_start);
+    let mut spans = Vec::new();
+    if let Some(startup) = phases.since_exec {
+        spans.push(("startup", Duration::ZERO, startup));
+    }
+    match *phases.solved.lock().unwrap() {
+        Some((solved, t)) => {
+            let mut from = at(solved).saturating_sub(t.load + t.spawn + t.scan + t.merge);
+            spans.push(("setup", origin, from));
+            for (name, took) in [
+                ("load", t.load),
+                ("spawn", t.spawn),
+                ("scan", t.scan),
+                ("merge", t.merge),
+            ] {
+                spans.push((name, from, from + took));
+                from += took;
+            }
+            spans.push(("output", at(solved), at(end)));
+        }
+        // Subcommands, and solvers that measure no timings.
+        None => spans.push(("run", origin, at(end))),
+    }
+    let events: Vec<_> = spans
+        .into_iter()
+        .map(|(name, from, to)| {
+            serde_json::json!({
+                "name": name,
+                "ph": "X",
+                "ts": from.as_micros() as u64,
+                "dur": to.saturating_sub(from).as_micros() as u64,
+                "pid": std::process::id(),
+                "tid": 0,
+            })
+        })
+        .collect();
+    let mut out = serde_json::json!({ "traceEvents": events }).to_string();
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_phases() {
+        let main_start = Instant::now();
+        let phases = Phases {
+            main_start,
+            since_exec: Some(Duration::from_micros(500)),
+            solved: Mutex::new(None),
+        };
+        let ms = Duration::from_millis;
+        let solved = main_start + ms(10);
+        let timings = Timings {
+            load: ms(1),
+            spawn: ms(1),
+            scan: ms(5),
+            merge: ms(1),
+        };
+        *phases.solved.lock().unwrap() = Some((solved, timings));
+
+        let doc: serde_json::Value =
+            serde_json::from_str(&trace(&phases, main_start + ms(12))).unwrap();
+        let spans: Vec<(&str, u64, u64)> = doc["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| {
+                let n = |k: &str| e[k].as_u64().unwrap();
+                (e["name"].as_str().unwrap(), n("ts"), n("dur"))
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("startup", 0, 500),
+                ("setup", 500, 2000),
+                ("load", 2500, 1000),
+                ("spawn", 3500, 1000),
+                ("scan", 4500, 5000),
+                ("merge", 9500, 1000),
+                ("output", 10500, 2000),
+            ],
+            spans
+        );
+    }
+}
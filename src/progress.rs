@@ -0,0 +1,71 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::IsTerminal;
+
+/// A one-line progress bar on stderr for `--progress`, fed by
+/// `SolveOptions::report_progress`: how far the scan is, how fast it goes
+/// and when it should be done. Draws nothing unless stderr is a terminal.
+pub struct Bar(ProgressBar);
+
+impl Bar {
+    pub fn new() -> Option<Self> {
+        std::io::stderr().is_terminal().then(|| {
+            let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+            // With no total (a pipe), just the bytes and throughput.
+            bar.set_style(style("{binary_bytes} {binary_bytes_per_sec}"));
+            Self(bar)
+        })
+    }
+
+    /// Moves the bar to `done` of `total` bytes. A new total, or going
+    /// backwards, is the next of several inputs solved one at a time, and
+    /// starts the bar over.
+    pub fn update(&self, done: u64, total: u64) {
+        let bar = &self.0;
+        if total > 0 && (bar.length() != Some(total) || done < bar.position()) {
+            bar.reset();
+            bar.set_length(total);
+            bar.set_style(style(
+                "[{bar:30}] {percent:>3}% {binary_bytes}/{binary_total_bytes} \
+                 {binary_bytes_per_sec} ETA {eta_precise}",
+            ));
+        }
+        bar.set_position(done);
+    }
+
+    /// Leaves the bar as it ended, if it was drawn, once the solve is over.
+    pub fn finish(&self) {
+        match self.0.length().is_some() || self.0.position() > 0 {
+            true => self.0.finish(),
+            false => self.0.finish_and_clear(),
+        }
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template)
+        .expect("a valid template")
+        .progress_chars("#-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let bar = Bar(ProgressBar::hidden());
+        bar.update(3 << 20, 0);
+        assert_eq!((None, 3 << 20), (bar.0.length(), bar.0.position()));
+        bar.update(256 << 20, 1 << 30);
+        bar.update(512 << 20, 1 << 30);
+        assert_eq!(
+            (Some(1 << 30), 512 << 20),
+            (bar.0.length(), bar.0.position())
+        );
+        // The next input, of the same size: the bar starts over.
+        bar.update(1 << 20, 1 << 30);
+        assert_eq!(1 << 20, bar.0.position());
+        bar.update(4 << 20, 2 << 30);
+        assert_eq!((Some(2 << 30), 4 << 20), (bar.0.length(), bar.0.position()));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::checksum::Manifest;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Everything needed to rerun a solve the same way: the command line (with
+/// the hash seed pinned), the host it ran on and the input it read.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub args: Vec<String>,
+    /// `info` output, one entry per line.
+    pub host: Vec<String>,
+    pub input: Manifest,
+}
+
+impl RunRecord {
+    /// `argv` is the command line without the program name. Recording and
+    /// seed flags are dropped and `seed`, if any, is put back in front.
+    pub fn new(argv: impl IntoIterator<Item = String>, seed: Option<u64>, input: Manifest) -> Self {
+        let mut args = Vec::new();
+        if let Some(seed) = seed {
+            args.extend(["--hash-seed".to_string(), seed.to_string()]);
+        }
+        args.extend(strip_flags(argv, &["--record-run", "--hash-seed"]));
+        Self {
+            args,
+            host: crate::info::report().lines().map(str::to_owned).collect(),
+            input,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("writing run record {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading run record {}", path.display()))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("parsing run record {}", path.display()))
+    }
+
+    /// Host lines that differ from the recording, as `recorded -> now`.
+    pub fn host_changes(&self) -> Vec<String> {
+        let now = crate::info::report();
+        let now: Vec<&str> = now.lines().collect();
+        let mut changes = Vec::new();
+        for (i, was) in self.host.iter().enumerate() {
+            match now.get(i) {
+                Some(is) if is == was => {}
+                Some(is) => changes.push(format!("{was} -> {is}")),
+                None => changes.push(format!("{was} -> (missing)")),
+            }
+        }
+        changes
+    }
+}
+
+// Drops `--flag value` and `--flag=value` for each of `flags`.
+fn strip_flags(argv: impl IntoIterator<Item = String>, flags: &[&str]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut argv = argv.into_iter();
+    while let Some(arg) = argv.next() {
+        if flags.contains(&arg.as_str()) {
+            argv.next();
+        } else if !flags
+            .iter()
+            .any(|f| arg.strip_prefix(f).is_some_and(|v| v.starts_with('=')))
+        {
+            out.push(arg);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_flags() {
+        let argv = [
+            "--record-run",
+            "run.json",
+            "--name=m.txt",
+            "--hash-seed=7",
+            "profile",
+        ]
+        .map(String::from);
+        assert_eq!(
+            vec!["--name=m.txt", "profile"],
+            strip_flags(argv, &["--record-run", "--hash-seed"])
+        );
+    }
+}
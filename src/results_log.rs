@@ -0,0 +1,135 @@
+use anyhow::{Context, Result, bail};
+use findlib::{Stat, StationStats};
+use serde::Deserialize;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// An append-only JSONL file of results (see `output::snapshot`), one line
+/// per preview and one for the final result of each run, for deployments
+/// that keep a record across runs. `compact` thins it out.
+pub struct ResultsLog {
+    path: PathBuf,
+    file: Mutex<File>,
+    selection: Vec<Stat>,
+}
+
+impl ResultsLog {
+    /// Opens `path` for appending, creating it if needed. Each line holds
+    /// the `selection` of statistics per station.
+    pub fn open(path: &Path, selection: &[Stat]) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("opening results log {}", path.display()))?;
+        Ok(Self {
+            path: path.to_owned(),
+            file: Mutex::new(file),
+            selection: selection.to_vec(),
+        })
+    }
+
+    /// Appends `stats` as taken now, marked final or not. A line goes out
+    /// in one write, so a reader never sees half of it.
+    pub fn append(&self, stats: &[StationStats], last: bool) -> Result<()> {
+        let line = crate::output::snapshot(now(), last, stats, &self.selection);
+        self.file
+            .lock()
+            .unwrap()
+            .write_all(line.as_bytes())
+            .with_context(|| format!("appending to results log {}", self.path.display()))
+    }
+}
+
+// Just what `compact` reads of a line.
+#[derive(Deserialize)]
+struct Stamp {
+    time: String,
+}
+
+/// Rewrites the results log at `path` to keep only the latest line of each
+/// window of `window` seconds (aligned to the epoch in UTC, by the line's
+/// time), in their original order. The new log replaces the old in one
+/// rename. Returns how many lines were kept and how many there were.
+pub fn compact(path: &Path, window: u64) -> Result<(usize, usize)> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("reading results log {}", path.display()))?;
+    let mut windows = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let stamp: Stamp = serde_json::from_str(line)
+            .with_context(|| format!("{}: line {}", path.display(), i + 1))?;
+        let Some(secs) = findlib::parse_timestamp(stamp.time.as_bytes()) else {
+            bail!(
+                "{}: line {}: bad time {:?}",
+                path.display(),
+                i + 1,
+                stamp.time
+            );
+        };
+        windows.push(secs.div_euclid(window as i64));
+    }
+    // A line is kept unless a later one falls in its window.
+    let mut seen = std::collections::HashSet::new();
+    let mut keep: Vec<bool> = windows.iter().rev().map(|w| seen.insert(*w)).collect();
+    keep.reverse();
+
+    let mut out = String::with_capacity(text.len());
+    for (line, _) in text.lines().zip(&keep).filter(|(_, k)| **k) {
+        out.push_str(line);
+        out.push('\n');
+    }
+    let tmp = path.with_extension("compacting");
+    write_synced(&tmp, out.as_bytes())
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .with_context(|| format!("rewriting results log {}", path.display()))?;
+    Ok((seen.len(), windows.len()))
+}
+
+fn write_synced(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_keeps_latest_per_window() {
+        let path = std::env::temp_dir().join(format!("results-{}.jsonl", std::process::id()));
+        let at = |time: &str, n: u32| {
+            format!("{{\"time\":\"{time}\",\"final\":false,\"stations\":[{{\"name\":\"{n}\"}}]}}\n")
+        };
+        let log = [
+            at("2024-03-01T13:00:00Z", 1),
+            at("2024-03-01T13:59:59Z", 2),
+            at("2024-03-01T14:00:00Z", 3),
+            at("2024-03-01T15:30:00Z", 4),
+            at("2024-03-01T15:31:00Z", 5),
+        ];
+        std::fs::write(&path, log.concat()).unwrap();
+        assert_eq!((3, 5), compact(&path, 3600).unwrap());
+        let kept = std::fs::read_to_string(&path).unwrap();
+        assert_eq!([&log[1][..], &log[2], &log[4]].concat(), kept);
+
+        // Compacting again changes nothing; a day keeps the last line.
+        assert_eq!((3, 3), compact(&path, 3600).unwrap());
+        assert_eq!((1, 3), compact(&path, 86_400).unwrap());
+        assert_eq!(log[4], std::fs::read_to_string(&path).unwrap());
+
+        std::fs::write(&path, "not json\n").unwrap();
+        let err = compact(&path, 3600).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(format!("{err}").ends_with("line 1"), "{err}");
+    }
+}
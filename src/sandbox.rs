@@ -0,0 +1,44 @@
+use anyhow::{Result, bail};
+use std::path::Path;
+
+#[cfg(target_os = "linux")]
+pub fn enter(input: &Path) -> Result<()> {
+    use landlock::{
+        ABI, Access, AccessFs, AccessNet, PathBeneath, PathFd, Ruleset, RulesetAttr,
+        RulesetCreatedAttr, RulesetStatus,
+    };
+    use seccompiler::{BpfProgram, SeccompAction, SeccompFilter};
+    use std::collections::BTreeMap;
+
+    // Landlock: the input file stays readable, every other path and all TCP
+    // bind/connect attempts are denied for this thread and any it spawns.
+    let abi = ABI::V4;
+    let status = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))?
+        .handle_access(AccessNet::from_all(abi))?
+        .create()?
+        .add_rule(PathBeneath::new(PathFd::new(input)?, AccessFs::ReadFile))?
+        .restrict_self()?;
+    if status.ruleset == RulesetStatus::NotEnforced {
+        bail!("--sandbox requested but the kernel does not support landlock");
+    }
+
+    // Seccomp: refuse to create sockets at all, which also covers UDP and
+    // unix sockets that landlock does not mediate.
+    let rules = BTreeMap::from([(libc::SYS_socket, vec![])]);
+    let filter = SeccompFilter::new(
+        rules,
+        SeccompAction::Allow,
+        SeccompAction::Errno(libc::EPERM as u32),
+        std::env::consts::ARCH.try_into()?,
+    )?;
+    let program: BpfProgram = filter.try_into()?;
+    seccompiler::apply_filter(&program)?;
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn enter(_input: &Path) -> Result<()> {
+    bail!("--sandbox is only supported on Linux")
+}
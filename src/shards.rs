@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use findlib::{Stat, StationStats};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Splits `stats` into `n` runs of nearly equal length, keeping their order,
+/// so reading the files in name order yields the stations in output order.
+/// Every shard gets a file, even if it is empty, so the file set only
+/// depends on `n`.
+pub fn by_count(stats: Vec<StationStats>, n: usize) -> Vec<(String, Vec<StationStats>)> {
+    let n = n.max(1);
+    let (base, extra) = (stats.len() / n, stats.len() % n);
+    let mut rest = stats.into_iter();
+    (0..n)
+        .map(|i| {
+            let len = base + usize::from(i < extra);
+            (format!("part-{i:05}"), rest.by_ref().take(len).collect())
+        })
+        .collect()
+}
+
+/// Groups `stats` by the first character of the name, keeping their order
+/// within each group. Files are named by the character's code point
+/// (`prefix-u0041` for `A`, `prefix-u0061` for `a`), so every file name is
+/// portable and names differing only in case do not collide on
+/// case-insensitive filesystems.
+pub fn by_prefix(stats: Vec<StationStats>) -> Vec<(String, Vec<StationStats>)> {
+    let mut groups: BTreeMap<String, Vec<StationStats>> = BTreeMap::new();
+    for s in stats {
+        let key = match s.name.chars().next() {
+            Some(c) => format!("prefix-u{:04x}", c as u32),
+            None => "prefix-empty".to_string(),
+        };
+        groups.entry(key).or_default().push(s);
+    }
+    groups.into_iter().collect()
+}
+
+/// Writes each shard to `dir/<name>.txt` in the usual output format.
+pub fn write(dir: &Path, shards: &[(String, Vec<StationStats>)], selection: &[Stat]) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    for (name, stats) in shards {
+        let path = dir.join(format!("{name}.txt"));
+        std::fs::write(&path, findlib::format_selected(stats, selection))
+            .with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(names: &[&str]) -> Vec<StationStats> {
+        names
+            .iter()
+            .map(|name| StationStats::new(*name, 0, 0, 0, 1))
+            .collect()
+    }
+
+    fn names(shards: &[(String, Vec<StationStats>)]) -> Vec<(&str, Vec<&str>)> {
+        shards
+            .iter()
+            .map(|(file, s)| (file.as_str(), s.iter().map(|s| s.name.as_str()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_by_count() {
+        let shards = by_count(stats(&["Abha", "Accra", "Bern", "Oslo", "Rome"]), 3);
+        assert_eq!(
+            vec![
+                ("part-00000", vec!["Abha", "Accra"]),
+                ("part-00001", vec!["Bern", "Oslo"]),
+                ("part-00002", vec!["Rome"]),
+            ],
+            names(&shards)
+        );
+        assert_eq!(4, by_count(stats(&["Oslo"]), 4).len());
+    }
+
+    #[test]
+    fn test_by_prefix() {
+        let shards = by_prefix(stats(&["Abha", "Accra", "Oslo", "Ürümqi", "Ålesund"]));
+        assert_eq!(
+            vec![
+                ("prefix-u0041", vec!["Abha", "Accra"]),
+                ("prefix-u004f", vec!["Oslo"]),
+                ("prefix-u00c5", vec!["Ålesund"]),
+                ("prefix-u00dc", vec!["Ürümqi"]),
+            ],
+            names(&shards)
+        );
+    }
+
+    #[test]
+    fn test_by_prefix_case() {
+        let shards = by_prefix(stats(&["Abha", "abha", "Oslo", "oslo"]));
+        assert_eq!(
+            vec![
+                ("prefix-u0041", vec!["Abha"]),
+                ("prefix-u004f", vec!["Oslo"]),
+                ("prefix-u0061", vec!["abha"]),
+                ("prefix-u006f", vec!["oslo"]),
+            ],
+            names(&shards)
+        );
+        let files: std::collections::HashSet<_> =
+            shards.iter().map(|(f, _)| f.to_lowercase()).collect();
+        assert_eq!(shards.len(), files.len());
+    }
+}
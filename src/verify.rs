@@ -0,0 +1,113 @@
+use findlib::{Stat, StationStats};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// The first station, by name, where two results print differently.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub name: String,
+    /// What each side prints for the station, or `None` if it lacks it.
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+/// Compares two results sorted by name, as they would print with the
+/// `selection` of statistics, and returns the first station that differs.
+pub fn first_mismatch(
+    left: &[StationStats],
+    right: &[StationStats],
+    selection: &[Stat],
+) -> Option<Mismatch> {
+    let (mut l, mut r) = (left.iter().peekable(), right.iter().peekable());
+    loop {
+        // Step past the lower name, or both on a tie; a name on one side
+        // only shows up as `None` on the other.
+        let (a, b) = match (l.peek(), r.peek()) {
+            (None, None) => return None,
+            (Some(a), Some(b)) => match a.name.cmp(&b.name) {
+                Ordering::Less => (l.next(), None),
+                Ordering::Greater => (None, r.next()),
+                Ordering::Equal => (l.next(), r.next()),
+            },
+            (Some(_), None) => (l.next(), None),
+            (None, Some(_)) => (None, r.next()),
+        };
+        let name = a.or(b).map(|s| s.name.clone()).unwrap();
+        let (a, b) = (
+            a.map(|s| values(s, selection)),
+            b.map(|s| values(s, selection)),
+        );
+        if a != b {
+            return Some(Mismatch {
+                name,
+                left: a,
+                right: b,
+            });
+        }
+    }
+}
+
+// `min/mean/max` (or whatever `selection` asks for) as the output prints it.
+fn values(s: &StationStats, selection: &[Stat]) -> String {
+    let mut out = String::new();
+    for (i, &stat) in selection.iter().enumerate() {
+        if i > 0 {
+            out.push('/');
+        }
+        findlib::write_stat(&mut out, s, stat);
+    }
+    out
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = |v: &Option<String>| v.as_deref().unwrap_or("missing").to_string();
+        write!(
+            f,
+            "{}: {} vs {}",
+            self.name,
+            side(&self.left),
+            side(&self.right)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_mismatch() {
+        let left = [
+            StationStats::new("Abha", -10, 20, 5, 2),
+            StationStats::new("Oslo", 0, 5, 5, 2),
+        ];
+        assert_eq!(None, first_mismatch(&left, &left, Stat::DEFAULT));
+
+        // A sum that only differs after rounding prints the same.
+        let same = [
+            StationStats::new("Abha", -10, 20, 5, 2),
+            StationStats::new("Oslo", 0, 5, 6, 2),
+        ];
+        assert_eq!(None, first_mismatch(&left, &same, Stat::DEFAULT));
+
+        let rounded = [
+            StationStats::new("Abha", -10, 20, 5, 2),
+            StationStats::new("Oslo", 0, 5, 4, 2),
+        ];
+        assert_eq!(
+            Some(Mismatch {
+                name: "Oslo".to_string(),
+                left: Some("0.0/0.3/0.5".to_string()),
+                right: Some("0.0/0.2/0.5".to_string()),
+            }),
+            first_mismatch(&left, &rounded, Stat::DEFAULT)
+        );
+        // The mean is not printed, so it is not compared.
+        assert_eq!(None, first_mismatch(&left, &rounded, &[Stat::Max]));
+
+        let missing = [StationStats::new("Oslo", 0, 5, 5, 2)];
+        let got = first_mismatch(&left, &missing, Stat::DEFAULT).unwrap();
+        assert_eq!("Abha: -1.0/0.3/2.0 vs missing", got.to_string());
+    }
+}